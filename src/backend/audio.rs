@@ -0,0 +1,390 @@
+use rodio::{ OutputStreamHandle, PlayError, Sink };
+use serde::{ Deserialize, Serialize };
+use std::collections::{ HashMap, VecDeque };
+use std::sync::{ Arc, Weak };
+use std::time::{ Duration, Instant };
+
+// fraction of the TTS volume kept while a sound effect ducks it
+pub const DUCKED_TTS_VOLUME: f32 = 0.3;
+
+/// What happens when a sound effect is triggered while TTS is playing.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum AudioPolicy {
+    // both play at the same time
+    #[default]
+    Overlap,
+    // the sound effect waits until TTS has finished
+    QueueBehindTTS,
+    // the sound effect plays right away and TTS is turned down until it ends
+    Duck,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SfxRoute {
+    PlayNow,
+    WaitForTTS,
+    DuckTTS,
+}
+
+impl AudioPolicy {
+    pub fn route_sfx(&self, tts_active: bool) -> SfxRoute {
+        if !tts_active {
+            return SfxRoute::PlayNow;
+        }
+        match self {
+            AudioPolicy::Overlap => SfxRoute::PlayNow,
+            AudioPolicy::QueueBehindTTS => SfxRoute::WaitForTTS,
+            AudioPolicy::Duck => SfxRoute::DuckTTS,
+        }
+    }
+}
+
+/// What happens when a sound is triggered while an earlier instance of it is still playing.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum RepeatPolicy {
+    #[default]
+    Overlap,
+    // stop the earlier instance and start over
+    Restart,
+    IgnoreUntilDone,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepeatAction {
+    Play,
+    StopAndPlay,
+    Skip,
+}
+
+impl RepeatPolicy {
+    pub fn action(&self, already_playing: bool) -> RepeatAction {
+        if !already_playing {
+            return RepeatAction::Play;
+        }
+        match self {
+            RepeatPolicy::Overlap => RepeatAction::Play,
+            RepeatPolicy::Restart => RepeatAction::StopAndPlay,
+            RepeatPolicy::IgnoreUntilDone => RepeatAction::Skip,
+        }
+    }
+}
+
+/// Drops exact re-triggers of a sound within a short window, e.g. from a message sent twice.
+#[derive(Default)]
+pub struct SoundDeduper {
+    last_triggered: HashMap<String, Instant>,
+}
+
+impl SoundDeduper {
+    /// Returns false when `sound` was already triggered less than `window` ago.
+    pub fn should_play(&mut self, sound: &str, window: Duration) -> bool {
+        let now = Instant::now();
+        self.last_triggered.retain(|_, triggered_at| now.duration_since(*triggered_at) < window);
+        if self.last_triggered.contains_key(sound) {
+            return false;
+        }
+        self.last_triggered.insert(sound.to_string(), now);
+        true
+    }
+}
+
+/// Whose turn it is to play in serial playback mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SfxTurn {
+    Play,
+    Wait,
+    // the queue was cleared while waiting
+    Dropped,
+}
+
+/// Sound effects waiting to play one after another in serial playback mode. Each trigger takes
+/// a ticket and plays once every earlier ticket has finished.
+#[derive(Default)]
+pub struct SfxQueue {
+    waiting: VecDeque<u64>,
+    next_ticket: u64,
+    playing: bool,
+}
+
+impl SfxQueue {
+    /// Takes a ticket at the back of the queue, None when `max_len` tickets are already
+    /// waiting. A `max_len` of 0 means no limit.
+    pub fn join(&mut self, max_len: usize) -> Option<u64> {
+        if max_len > 0 && self.waiting.len() >= max_len {
+            return None;
+        }
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.waiting.push_back(ticket);
+        Some(ticket)
+    }
+
+    /// Starts `ticket` when it's first in line and nothing else is playing.
+    pub fn turn(&mut self, ticket: u64) -> SfxTurn {
+        if !self.waiting.contains(&ticket) {
+            return SfxTurn::Dropped;
+        }
+        if self.playing || self.waiting.front() != Some(&ticket) {
+            return SfxTurn::Wait;
+        }
+        self.waiting.pop_front();
+        self.playing = true;
+        SfxTurn::Play
+    }
+
+    /// Lets the next ticket play, called once a started sound has ended.
+    pub fn finish(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn clear(&mut self) {
+        self.waiting.clear();
+    }
+}
+
+/// Sinks kept attached to the output and reused for sound effects, so rapid triggers
+/// don't pay for creating a new one each time.
+pub struct SinkPool {
+    // attached to the output stream, a detached sink in tests
+    new_sink: Box<dyn Fn() -> Result<Sink, PlayError> + Send>,
+    sinks: Vec<Arc<Sink>>,
+    size: usize,
+    // latest sink each sound was played on
+    active: HashMap<String, Weak<Sink>>,
+    // when each sound last started, for per-sound cooldowns
+    last_started: HashMap<String, Instant>,
+    // sounds waiting their turn in serial playback mode
+    pub queue: SfxQueue,
+}
+
+impl SinkPool {
+    pub fn new(stream_handle: Arc<OutputStreamHandle>, size: usize) -> Self {
+        Self::with_sinks(size, move || Sink::try_new(&stream_handle))
+    }
+
+    fn with_sinks(
+        size: usize,
+        new_sink: impl Fn() -> Result<Sink, PlayError> + Send + 'static
+    ) -> Self {
+        let sinks = (0..size)
+            .filter_map(|_| new_sink().ok())
+            .map(Arc::new)
+            .collect();
+        Self {
+            new_sink: Box::new(new_sink),
+            sinks,
+            size,
+            active: HashMap::new(),
+            last_started: HashMap::new(),
+            queue: SfxQueue::default(),
+        }
+    }
+
+    /// The sink `sound` is still playing on, if any.
+    pub fn playing(&self, sound: &str) -> Option<Arc<Sink>> {
+        self.active
+            .get(sound)
+            .and_then(Weak::upgrade)
+            .filter(|sink| !sink.empty())
+    }
+
+    /// Hands out an idle sink for `sound`, creating a new one when they are all busy. Sinks
+    /// created over the pool size are dropped again once they go idle.
+    pub fn acquire(&mut self, sound: &str) -> Result<Arc<Sink>, PlayError> {
+        self.active.retain(|_, sink| sink.strong_count() > 0);
+        let sink = self.idle_sink()?;
+        self.active.insert(sound.to_string(), Arc::downgrade(&sink));
+        self.last_started.insert(sound.to_string(), Instant::now());
+        Ok(sink)
+    }
+
+    /// Whether `sound` last started less than `cooldown` ago.
+    pub fn is_cooling_down(&self, sound: &str, cooldown: Duration) -> bool {
+        self.last_started
+            .get(sound)
+            .is_some_and(|started_at| started_at.elapsed() < cooldown)
+    }
+
+    /// Number of sound effects playing right now.
+    pub fn playing_count(&self) -> usize {
+        self.sinks
+            .iter()
+            .filter(|sink| !sink.empty())
+            .count()
+    }
+
+    /// Stops every sound effect that is playing, along with those waiting in the queue.
+    pub fn stop_all(&mut self) {
+        for sink in &self.sinks {
+            sink.stop();
+        }
+        self.active.clear();
+        self.queue.clear();
+    }
+
+    fn idle_sink(&mut self) -> Result<Arc<Sink>, PlayError> {
+        let mut surplus = self.sinks.len().saturating_sub(self.size);
+        self.sinks.retain(|sink| {
+            if surplus > 0 && Self::is_idle(sink) {
+                surplus -= 1;
+                return false;
+            }
+            true
+        });
+        if let Some(sink) = self.sinks.iter().find(|sink| Self::is_idle(sink)) {
+            return Ok(sink.clone());
+        }
+        let sink = Arc::new((self.new_sink)()?);
+        self.sinks.push(sink.clone());
+        Ok(sink)
+    }
+
+    // a sink is busy while it still has sounds queued or someone holds on to it
+    fn is_idle(sink: &Arc<Sink>) -> bool {
+        Arc::strong_count(sink) == 1 && sink.empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detached_pool(size: usize) -> SinkPool {
+        SinkPool::with_sinks(size, || Ok(Sink::new_idle().0))
+    }
+
+    #[test]
+    fn released_sinks_are_reused() {
+        let mut pool = detached_pool(2);
+        let first = pool.acquire("airhorn").unwrap();
+        let reused = Arc::as_ptr(&first);
+        drop(first);
+        let second = pool.acquire("bonk").unwrap();
+        assert_eq!(Arc::as_ptr(&second), reused);
+        assert_eq!(pool.sinks.len(), 2);
+    }
+
+    #[test]
+    fn sinks_in_use_are_not_handed_out_again() {
+        let mut pool = detached_pool(2);
+        let first = pool.acquire("airhorn").unwrap();
+        let second = pool.acquire("bonk").unwrap();
+        assert_ne!(Arc::as_ptr(&first), Arc::as_ptr(&second));
+    }
+
+    #[test]
+    fn pool_grows_when_busy_and_shrinks_back_to_its_size() {
+        let mut pool = detached_pool(1);
+        let held: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|sound| pool.acquire(sound).unwrap())
+            .collect();
+        assert_eq!(pool.sinks.len(), 3);
+        drop(held);
+        let _sink = pool.acquire("d").unwrap();
+        assert_eq!(pool.sinks.len(), 1);
+    }
+
+    #[test]
+    fn sounds_play_right_away_without_tts() {
+        for policy in [AudioPolicy::Overlap, AudioPolicy::QueueBehindTTS, AudioPolicy::Duck] {
+            assert_eq!(policy.route_sfx(false), SfxRoute::PlayNow);
+        }
+    }
+
+    #[test]
+    fn overlap_plays_over_tts() {
+        assert_eq!(AudioPolicy::Overlap.route_sfx(true), SfxRoute::PlayNow);
+    }
+
+    #[test]
+    fn queue_behind_tts_waits_for_it() {
+        assert_eq!(AudioPolicy::QueueBehindTTS.route_sfx(true), SfxRoute::WaitForTTS);
+    }
+
+    #[test]
+    fn duck_turns_tts_down() {
+        assert_eq!(AudioPolicy::Duck.route_sfx(true), SfxRoute::DuckTTS);
+    }
+
+    #[test]
+    fn sounds_that_are_not_playing_always_play() {
+        use RepeatPolicy::*;
+        for policy in [Overlap, Restart, IgnoreUntilDone] {
+            assert_eq!(policy.action(false), RepeatAction::Play);
+        }
+    }
+
+    #[test]
+    fn overlap_plays_the_sound_again() {
+        assert_eq!(RepeatPolicy::Overlap.action(true), RepeatAction::Play);
+    }
+
+    #[test]
+    fn restart_stops_the_earlier_instance() {
+        assert_eq!(RepeatPolicy::Restart.action(true), RepeatAction::StopAndPlay);
+    }
+
+    #[test]
+    fn ignore_until_done_skips_the_sound() {
+        assert_eq!(RepeatPolicy::IgnoreUntilDone.action(true), RepeatAction::Skip);
+    }
+
+    #[test]
+    fn repeat_within_the_window_is_dropped() {
+        let mut deduper = SoundDeduper::default();
+        let window = Duration::from_secs(60);
+        assert!(deduper.should_play("airhorn", window));
+        assert!(!deduper.should_play("airhorn", window));
+        assert!(deduper.should_play("bonk", window));
+    }
+
+    #[test]
+    fn repeat_after_the_window_plays() {
+        let mut deduper = SoundDeduper::default();
+        let window = Duration::from_millis(20);
+        assert!(deduper.should_play("airhorn", window));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(deduper.should_play("airhorn", window));
+    }
+
+    #[test]
+    fn empty_window_never_drops_anything() {
+        let mut deduper = SoundDeduper::default();
+        assert!(deduper.should_play("airhorn", Duration::ZERO));
+        assert!(deduper.should_play("airhorn", Duration::ZERO));
+    }
+
+    #[test]
+    fn tickets_play_in_the_order_they_were_taken() {
+        let mut queue = SfxQueue::default();
+        let first = queue.join(0).unwrap();
+        let second = queue.join(0).unwrap();
+        assert_eq!(queue.turn(second), SfxTurn::Wait);
+        assert_eq!(queue.turn(first), SfxTurn::Play);
+        // still waits while the first sound is playing
+        assert_eq!(queue.turn(second), SfxTurn::Wait);
+        queue.finish();
+        assert_eq!(queue.turn(second), SfxTurn::Play);
+    }
+
+    #[test]
+    fn full_queue_hands_out_no_ticket() {
+        let mut queue = SfxQueue::default();
+        assert!(queue.join(2).is_some());
+        assert!(queue.join(2).is_some());
+        assert!(queue.join(2).is_none());
+        assert!(queue.join(0).is_some());
+    }
+
+    #[test]
+    fn cleared_tickets_are_dropped() {
+        let mut queue = SfxQueue::default();
+        let ticket = queue.join(0).unwrap();
+        queue.clear();
+        assert_eq!(queue.turn(ticket), SfxTurn::Dropped);
+        let next = queue.join(0).unwrap();
+        assert_ne!(next, ticket);
+        assert_eq!(queue.turn(next), SfxTurn::Play);
+    }
+}
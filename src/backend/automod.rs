@@ -0,0 +1,103 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::ui::PermitedRoles;
+
+// bytes a compiled pattern may take up, so a pathological one gets rejected instead of used
+const MAX_PATTERN_SIZE: usize = 64 * 1024;
+
+/// What happens to a message that breaks an automod rule.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum AutomodAction {
+    #[default]
+    Delete,
+    // deletes the message as well, timing the user out clears their messages
+    Timeout,
+    // only logs it
+    WarnOnly,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AutomodConfig {
+    pub enabled: bool,
+    // matched as whole words, ignoring case
+    pub words: Vec<String>,
+    // regexes matched anywhere in the message, ignoring case
+    pub patterns: Vec<String>,
+    pub action: AutomodAction,
+    pub timeout_secs: u32,
+    // roles automod leaves alone, the broadcaster always is
+    pub exempt_roles: PermitedRoles,
+}
+
+impl Default for AutomodConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            words: Vec::new(),
+            patterns: Vec::new(),
+            action: AutomodAction::Delete,
+            timeout_secs: 60,
+            exempt_roles: PermitedRoles {
+                subs: false,
+                vips: false,
+                mods: true,
+            },
+        }
+    }
+}
+
+/// The config's words and patterns compiled, recompiled only when they change.
+#[derive(Default)]
+pub struct Automod {
+    words: Vec<String>,
+    patterns: Vec<String>,
+    // the word or pattern each regex came from
+    rules: Vec<(String, regex::Regex)>,
+}
+
+impl Automod {
+    /// The word or pattern `text` breaks, the first one in the config when it breaks several.
+    pub fn check(&mut self, text: &str, config: &AutomodConfig) -> Option<&str> {
+        if !config.enabled {
+            return None;
+        }
+        if self.words != config.words || self.patterns != config.patterns {
+            self.compile(config);
+        }
+        self.rules
+            .iter()
+            .find(|(_, regex)| regex.is_match(text))
+            .map(|(rule, _)| rule.as_str())
+    }
+
+    fn compile(&mut self, config: &AutomodConfig) {
+        self.words = config.words.clone();
+        self.patterns = config.patterns.clone();
+        let words = config.words
+            .iter()
+            .map(|word| word.trim())
+            .filter(|word| !word.is_empty())
+            .map(|word| (word.to_string(), format!(r"\b{}\b", regex::escape(word))));
+        let patterns = config.patterns
+            .iter()
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| (pattern.clone(), pattern.clone()));
+        self.rules = words
+            .chain(patterns)
+            .filter_map(|(rule, pattern)| {
+                let compiled = regex::RegexBuilder
+                    ::new(&pattern)
+                    .case_insensitive(true)
+                    .size_limit(MAX_PATTERN_SIZE)
+                    .build();
+                match compiled {
+                    Ok(regex) => Some((rule, regex)),
+                    Err(e) => {
+                        log::warn!("Skipping automod pattern {}: {}", rule, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+    }
+}
@@ -0,0 +1,1488 @@
+use serde::{ Deserialize, Serialize };
+use std::collections::{ HashMap, HashSet };
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex };
+use std::time::{ Duration, Instant };
+use tokio::task::JoinSet;
+
+use super::helix::AnnouncementColor;
+
+pub const DEFAULT_PREFIX: &str = "!";
+// longest pattern a regex or contains command may have
+const MAX_PATTERN_LENGTH: usize = 200;
+// bytes a compiled regex may take up, so a pathological pattern gets rejected at load
+const MAX_REGEX_SIZE: usize = 64 * 1024;
+// longest cooldown an imported command may have, in seconds
+const MAX_IMPORTED_COOLDOWN: u64 = 24 * 60 * 60;
+// lowest level allowed to change a counter, everyone else only reads it
+pub const COUNTER_EDIT_LEVEL: UserLevel = UserLevel::Moderator;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UserLevel {
+    Viewer,
+    Subscriber,
+    Vip,
+    Moderator,
+    Broadcaster,
+}
+
+impl UserLevel {
+    pub const ALL: [UserLevel; 5] = [
+        UserLevel::Viewer,
+        UserLevel::Subscriber,
+        UserLevel::Vip,
+        UserLevel::Moderator,
+        UserLevel::Broadcaster,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            UserLevel::Viewer => "Everyone",
+            UserLevel::Subscriber => "Subs",
+            UserLevel::Vip => "VIPs",
+            UserLevel::Moderator => "Mods",
+            UserLevel::Broadcaster => "Broadcaster",
+        }
+    }
+}
+
+/// What in a chat message fires a command.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TriggerKind {
+    // `!name` at the start of the message
+    #[default]
+    Prefix,
+    // `pattern` matching anywhere in the message
+    Regex,
+    // `pattern` appearing anywhere in the message, ignoring case
+    Contains,
+}
+
+/// A message sent `delay_ms` after the command was triggered.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandStep {
+    pub delay_ms: u64,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Command {
+    pub name: String,
+    #[serde(default)]
+    pub response: String,
+    // when set, each run replies with one of these picked at random instead of `response`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub responses: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // seconds
+    #[serde(default)]
+    pub cooldown: u64,
+    // seconds each chatter has to wait before using the command again, on top of `cooldown`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_cooldown: Option<u64>,
+    #[serde(default = "default_permission")]
+    pub permission: UserLevel,
+    #[serde(default)]
+    pub steps: Vec<CommandStep>,
+    // run the command's effects without replying in chat
+    #[serde(default)]
+    pub silent: bool,
+    // other triggers for the same command, sharing its cooldown
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub trigger_kind: TriggerKind,
+    // what regex and contains commands look for in messages
+    #[serde(default)]
+    pub pattern: String,
+    // makes this a counter command, the value is kept here and read as $count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counter: Option<i64>,
+}
+
+impl Command {
+    /// A plain `!name` command with no response yet, open to everyone.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            response: String::new(),
+            responses: Vec::new(),
+            enabled: default_enabled(),
+            cooldown: 0,
+            user_cooldown: None,
+            permission: default_permission(),
+            steps: Vec::new(),
+            silent: false,
+            aliases: Vec::new(),
+            trigger_kind: TriggerKind::default(),
+            pattern: String::new(),
+            counter: None,
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_permission() -> UserLevel {
+    UserLevel::Viewer
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CommandResult {
+    Success(Option<String>),
+    OnCooldown(Duration),
+    PermissionDenied,
+    NotFound,
+}
+
+/// How a command run ended, without the details `CommandResult` carries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandOutcome {
+    Success,
+    OnCooldown,
+    PermissionDenied,
+    NotFound,
+}
+
+impl CommandOutcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CommandOutcome::Success => "success",
+            CommandOutcome::OnCooldown => "cooldown",
+            CommandOutcome::PermissionDenied => "denied",
+            CommandOutcome::NotFound => "not found",
+        }
+    }
+}
+
+/// A command run as shown in the UI's recent runs, so it's visible why a command didn't respond.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandExecution {
+    pub trigger: String,
+    pub username: String,
+    pub outcome: CommandOutcome,
+    // the reply sent to chat, if any
+    pub response: Option<String>,
+    pub timestamp: String,
+}
+
+impl CommandExecution {
+    pub fn new(trigger: &str, context: &CommandContext, result: &CommandResult) -> Self {
+        let (outcome, response) = match result {
+            CommandResult::Success(response) => (CommandOutcome::Success, response.clone()),
+            CommandResult::OnCooldown(_) => (CommandOutcome::OnCooldown, None),
+            CommandResult::PermissionDenied => (CommandOutcome::PermissionDenied, None),
+            CommandResult::NotFound => (CommandOutcome::NotFound, None),
+        };
+        Self {
+            trigger: trigger.to_string(),
+            username: context.username.clone(),
+            outcome,
+            response,
+            timestamp: chrono::Local::now().format("%H:%M:%S").to_string(),
+        }
+    }
+
+    /// How a successful run is written to the transcript, with the prefix it was run with.
+    pub fn transcript_text(&self, command_prefix: &str) -> Option<String> {
+        (self.outcome == CommandOutcome::Success).then(|| {
+            let response = self.response.as_deref().unwrap_or_default();
+            format!("{}{} {}", command_prefix, self.trigger, response)
+        })
+    }
+}
+
+/// Where a command's response goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseTarget {
+    Chat,
+    // privately to the user who ran the command
+    Whisper,
+    Announcement(AnnouncementColor),
+}
+
+impl ResponseTarget {
+    /// Splits a `whisper:` or `announce:` prefix off `response`, the latter optionally followed
+    /// by a color as in `announce:purple:`. Responses without one go to chat.
+    pub fn parse(response: &str) -> (Self, &str) {
+        if let Some(text) = response.strip_prefix("whisper:") {
+            return (ResponseTarget::Whisper, text.trim_start());
+        }
+        let Some(text) = response.strip_prefix("announce:") else {
+            return (ResponseTarget::Chat, response);
+        };
+        let color = text
+            .split_once(':')
+            .and_then(|(color, rest)| Some((AnnouncementColor::parse(color)?, rest)));
+        match color {
+            Some((color, text)) => (ResponseTarget::Announcement(color), text.trim_start()),
+            None => (ResponseTarget::Announcement(AnnouncementColor::Primary), text.trim_start()),
+        }
+    }
+}
+
+pub struct CommandContext {
+    pub username: String,
+    // empty for manual runs from the UI
+    pub user_id: String,
+    pub channel: String,
+    pub args: Vec<String>,
+    pub user_level: UserLevel,
+    // manual runs from the UI as the broadcaster ignore the cooldown
+    pub bypass_cooldown: bool,
+}
+
+/// A compiled regex or contains trigger.
+enum PatternMatcher {
+    Regex(regex::Regex),
+    Contains(String),
+}
+
+impl PatternMatcher {
+    fn new(command: &Command) -> Result<Option<Self>, String> {
+        if command.trigger_kind == TriggerKind::Prefix {
+            return Ok(None);
+        }
+        if command.pattern.is_empty() {
+            return Err("the pattern is empty".to_string());
+        }
+        if command.pattern.len() > MAX_PATTERN_LENGTH {
+            return Err(format!("the pattern is longer than {} characters", MAX_PATTERN_LENGTH));
+        }
+        match command.trigger_kind {
+            TriggerKind::Prefix => Ok(None),
+            TriggerKind::Regex =>
+                regex::RegexBuilder
+                    ::new(&command.pattern)
+                    .case_insensitive(true)
+                    .size_limit(MAX_REGEX_SIZE)
+                    .build()
+                    .map(|regex| Some(PatternMatcher::Regex(regex)))
+                    .map_err(|e| e.to_string()),
+            TriggerKind::Contains =>
+                Ok(Some(PatternMatcher::Contains(command.pattern.to_lowercase()))),
+        }
+    }
+
+    fn is_match(&self, message: &str) -> bool {
+        match self {
+            PatternMatcher::Regex(regex) => regex.is_match(message),
+            PatternMatcher::Contains(pattern) => message.to_lowercase().contains(pattern.as_str()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: HashMap<String, Command>,
+    // lowercase alias to the lowercase name of its command
+    aliases: HashMap<String, String>,
+    // lowercase names of regex and contains commands with their triggers, sorted by name
+    matchers: Vec<(String, PatternMatcher)>,
+    last_used: HashMap<String, Instant>,
+    // keyed by lowercase command name and lowercase username
+    user_last_used: HashMap<(String, String), Instant>,
+    // index of the last picked entry of commands with random responses
+    last_response: HashMap<String, usize>,
+    // where changed counts are saved, commands.json outside of tests
+    counters_path: PathBuf,
+}
+
+impl CommandRegistry {
+    pub fn new(commands: Vec<Command>) -> Self {
+        let mut registry = Self {
+            counters_path: commands_path(),
+            ..Self::default()
+        };
+        for command in commands {
+            registry.register(command);
+        }
+        registry
+    }
+
+    /// Swaps in a new set of commands, keeping the cooldowns of the ones that still exist.
+    pub fn replace(&mut self, commands: Vec<Command>) {
+        self.commands.clear();
+        self.aliases.clear();
+        self.matchers.clear();
+        for command in commands {
+            self.register(command);
+        }
+        let commands = &self.commands;
+        self.last_used.retain(|name, _| commands.contains_key(name));
+        self.user_last_used.retain(|(name, _), _| commands.contains_key(name));
+        self.last_response.clear();
+    }
+
+    pub fn register(&mut self, command: Command) {
+        let name = command.name.to_lowercase();
+        self.matchers.retain(|(matcher_name, _)| *matcher_name != name);
+        match PatternMatcher::new(&command) {
+            Ok(Some(matcher)) => {
+                let index = self.matchers.partition_point(|(other, _)| *other < name);
+                self.matchers.insert(index, (name.clone(), matcher));
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("!{} will never trigger from chat, {}", command.name, e),
+        }
+        for alias in command.aliases.iter() {
+            self.aliases.insert(alias.to_lowercase(), name.clone());
+        }
+        self.commands.insert(name, command);
+    }
+
+    /// The lowercase name of the command `name` triggers, following aliases. A command's own
+    /// name wins over another command's alias.
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        if let Some((name, _)) = self.commands.get_key_value(&name) {
+            return Some(name);
+        }
+        self.aliases.get(&name).map(String::as_str)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.get(self.resolve(name)?)
+    }
+
+    /// The lowercase name of the first regex or contains command, by name, `message` triggers.
+    pub fn find_pattern_match(&self, message: &str) -> Option<&str> {
+        self.matchers
+            .iter()
+            .find(|(_, matcher)| matcher.is_match(message))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// All registered commands, sorted by name.
+    pub fn commands(&self) -> Vec<Command> {
+        let mut commands: Vec<Command> = self.commands.values().cloned().collect();
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+        commands
+    }
+
+    /// Names of the commands whose cooldown hasn't expired yet.
+    pub fn on_cooldown(&self) -> HashSet<String> {
+        self.last_used
+            .iter()
+            .filter(|(name, last_used)| {
+                self.commands
+                    .get(*name)
+                    .is_some_and(|command| last_used.elapsed() < Duration::from_secs(command.cooldown))
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CooldownChanges {
+    pub started: Vec<String>,
+    pub ready: Vec<String>,
+}
+
+impl CooldownChanges {
+    pub fn is_empty(&self) -> bool {
+        self.started.is_empty() && self.ready.is_empty()
+    }
+}
+
+/// Remembers which commands were on cooldown, to notice when they become ready again.
+#[derive(Default)]
+pub struct CooldownWatcher {
+    cooling: HashSet<String>,
+}
+
+impl CooldownWatcher {
+    pub fn cooling(&self) -> &HashSet<String> {
+        &self.cooling
+    }
+
+    pub fn update(&mut self, cooling: HashSet<String>) -> CooldownChanges {
+        let changes = CooldownChanges {
+            started: cooling.difference(&self.cooling).cloned().collect(),
+            ready: self.cooling.difference(&cooling).cloned().collect(),
+        };
+        self.cooling = cooling;
+        changes
+    }
+}
+
+pub struct ParsedCommand {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+pub struct CommandParser {
+    prefix: String,
+}
+
+impl CommandParser {
+    pub fn with_default_prefix() -> Self {
+        Self {
+            prefix: DEFAULT_PREFIX.to_string(),
+        }
+    }
+
+    /// A parser for `prefix`, or for the default one when `prefix` is empty or has whitespace in
+    /// it, which no message could start a command with.
+    pub fn new(prefix: &str) -> Self {
+        if prefix.is_empty() || prefix.contains(char::is_whitespace) {
+            return Self::with_default_prefix();
+        }
+        Self {
+            prefix: prefix.to_string(),
+        }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn parse(&self, message: &str) -> Option<ParsedCommand> {
+        let mut parts = message.trim().strip_prefix(&self.prefix)?.split_whitespace();
+        let name = parts.next()?.to_lowercase();
+        Some(ParsedCommand {
+            name,
+            args: parts.map(|arg| arg.to_string()).collect(),
+        })
+    }
+
+    /// Like `parse`, also firing the registry's regex and contains commands. Messages starting
+    /// with the prefix are only ever prefix commands, so `!name` can't trigger a pattern command
+    /// and a pattern can't shadow a prefix one.
+    pub fn parse_with_patterns(
+        &self,
+        message: &str,
+        registry: &CommandRegistry
+    ) -> Option<ParsedCommand> {
+        if message.trim().starts_with(&self.prefix) {
+            let parsed = self.parse(message)?;
+            let is_pattern_command = registry
+                .get(&parsed.name)
+                .is_some_and(|command| command.trigger_kind != TriggerKind::Prefix);
+            return if is_pattern_command { None } else { Some(parsed) };
+        }
+        let name = registry.find_pattern_match(message)?;
+        Some(ParsedCommand {
+            name: name.to_string(),
+            args: Vec::new(),
+        })
+    }
+}
+
+pub struct CommandExecutor;
+
+impl CommandExecutor {
+    pub fn execute(
+        registry: &mut CommandRegistry,
+        name: &str,
+        context: &CommandContext
+    ) -> CommandResult {
+        Self::execute_with_rng(registry, name, context, &mut fastrand::Rng::new())
+    }
+
+    /// Like `execute`, drawing `{random:...}` and `{choose:...}` values from `rng`.
+    pub fn execute_with_rng(
+        registry: &mut CommandRegistry,
+        name: &str,
+        context: &CommandContext,
+        rng: &mut fastrand::Rng
+    ) -> CommandResult {
+        let Some(name) = registry.resolve(name).map(str::to_string) else {
+            return CommandResult::NotFound;
+        };
+        let command = &registry.commands[&name];
+        if !command.enabled {
+            return CommandResult::NotFound;
+        }
+        if context.user_level < command.permission {
+            return CommandResult::PermissionDenied;
+        }
+        let username = context.username.to_lowercase();
+        if !context.bypass_cooldown {
+            let global = remaining_cooldown(registry.last_used.get(&name), command.cooldown);
+            let user = command.user_cooldown.and_then(|cooldown| {
+                let last_used = registry.user_last_used.get(&(name.clone(), username.clone()));
+                remaining_cooldown(last_used, cooldown)
+            });
+            // with both running the longer one is the one that matters
+            if let Some(remaining) = global.max(user) {
+                return CommandResult::OnCooldown(remaining);
+            }
+        }
+        let count = match command.counter {
+            Some(count) if context.user_level >= COUNTER_EDIT_LEVEL => {
+                let changed = apply_counter_change(count, &context.args);
+                if changed != count {
+                    registry.commands.get_mut(&name).unwrap().counter = Some(changed);
+                    if let Err(e) = save_counter(&registry.counters_path, &name, changed) {
+                        log::warn!("Could not save the count of !{}: {}", name, e);
+                    }
+                }
+                Some(changed)
+            }
+            count => count,
+        };
+        let command = &registry.commands[&name];
+        let template = if command.responses.is_empty() {
+            &command.response
+        } else {
+            let previous = registry.last_response.get(&name).copied();
+            let index = pick_response(command.responses.len(), previous, rng);
+            registry.last_response.insert(name.clone(), index);
+            &command.responses[index]
+        };
+        let response = if command.silent || template.is_empty() {
+            None
+        } else {
+            let response = resolve_random_variables(template, rng);
+            Some(expand_variables(&response, context, count))
+        };
+        let now = Instant::now();
+        if command.user_cooldown.is_some() {
+            registry.user_last_used.insert((name.clone(), username), now);
+        }
+        registry.last_used.insert(name, now);
+        CommandResult::Success(response)
+    }
+}
+
+/// Picks one of `len` responses, never the `previous` one again unless it's the only one.
+fn pick_response(len: usize, previous: Option<usize>, rng: &mut fastrand::Rng) -> usize {
+    match previous {
+        Some(previous) if len > 1 && previous < len => {
+            // draw from the others and skip over the previous one
+            let index = rng.usize(..len - 1);
+            if index >= previous { index + 1 } else { index }
+        }
+        _ => rng.usize(..len),
+    }
+}
+
+/// How long is left of a `cooldown` in seconds that started at `last_used`, if any.
+fn remaining_cooldown(last_used: Option<&Instant>, cooldown: u64) -> Option<Duration> {
+    let elapsed = last_used?.elapsed();
+    Duration::from_secs(cooldown).checked_sub(elapsed).filter(|remaining| !remaining.is_zero())
+}
+
+/// The count after a counter command was run with `args`: no argument or "+" adds one, "+N"
+/// and "-N" add or subtract N, "set N" sets it and "reset" sets it to 0. Anything else leaves
+/// the count as it is.
+pub fn apply_counter_change(count: i64, args: &[String]) -> i64 {
+    let Some(arg) = args.first() else {
+        return count.saturating_add(1);
+    };
+    let change = match arg.to_lowercase().as_str() {
+        "+" => Some(count.saturating_add(1)),
+        "-" => Some(count.saturating_sub(1)),
+        "reset" => Some(0),
+        "set" => args.get(1).and_then(|value| value.parse().ok()),
+        arg => {
+            if let Some(amount) = arg.strip_prefix('+') {
+                amount.parse().ok().map(|amount| count.saturating_add(amount))
+            } else if let Some(amount) = arg.strip_prefix('-') {
+                amount.parse().ok().map(|amount| count.saturating_sub(amount))
+            } else {
+                None
+            }
+        }
+    };
+    change.unwrap_or(count)
+}
+
+/// Replaces `{random:1-100}` with a number in the range, bounds included and in either order,
+/// and `{choose:a|b|c}` with one of the choices. Malformed placeholders are left as they are.
+pub fn resolve_random_variables(template: &str, rng: &mut fastrand::Rng) -> String {
+    let mut resolved = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        resolved.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let placeholder = &rest[1..end];
+        let value = if let Some(range) = placeholder.strip_prefix("random:") {
+            parse_range(range).map(|(low, high)| rng.i64(low..=high).to_string())
+        } else if let Some(choices) = placeholder.strip_prefix("choose:") {
+            let choices: Vec<&str> = choices.split('|').collect();
+            Some(choices[rng.usize(..choices.len())].trim().to_string())
+        } else {
+            None
+        };
+        match value {
+            Some(value) => {
+                resolved.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                resolved.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    resolved.push_str(rest);
+    resolved
+}
+
+/// Replaces `$user` with the chatter, `$channel` with the broadcaster, `$args` with the rest of
+/// the message, `$touser` with the first argument, or the chatter when there is none, and
+/// `$count` with `count` for counter commands. Unknown placeholders are left as they are.
+pub fn expand_variables(template: &str, context: &CommandContext, count: Option<i64>) -> String {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = rest
+            .find(|character: char| !character.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let value = match &rest[..end] {
+            "user" => Some(context.username.clone()),
+            "channel" => Some(context.channel.clone()),
+            "args" => Some(context.args.join(" ")),
+            "touser" =>
+                Some(
+                    context.args
+                        .first()
+                        .map_or(context.username.as_str(), |arg| arg.trim_start_matches('@'))
+                        .to_string()
+                ),
+            "count" => count.map(|count| count.to_string()),
+            _ => None,
+        };
+        let Some(value) = value else {
+            expanded.push('$');
+            continue;
+        };
+        expanded.push_str(&value);
+        rest = &rest[end..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Parses "low-high" into ordered bounds, allowing negative numbers such as "-10--1".
+fn parse_range(range: &str) -> Option<(i64, i64)> {
+    let range = range.trim();
+    // skip a leading minus so it isn't taken for the separator
+    let separator = range.get(1..)?.find('-')? + 1;
+    let low: i64 = range[..separator].trim().parse().ok()?;
+    let high: i64 = range[separator + 1..].trim().parse().ok()?;
+    Some((low.min(high), low.max(high)))
+}
+
+/// Spawns a timer per step that pushes its text onto the chat send queue.
+/// Pending steps are cancelled when `tasks` is dropped, e.g. on disconnect.
+pub fn schedule_steps(
+    steps: &[CommandStep],
+    chat_tx: &tokio::sync::mpsc::Sender<String>,
+    tasks: &mut JoinSet<()>
+) {
+    for step in steps {
+        let chat_tx = chat_tx.clone();
+        let delay = Duration::from_millis(step.delay_ms);
+        let text = step.text.clone();
+        tasks.spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = chat_tx.send(text).await;
+        });
+    }
+}
+
+/// Steps of commands run outside a connection's message loop, e.g. from the UI, kept with the
+/// connection they reply to. Dropping the last copy cancels them like `cancel` does.
+#[derive(Clone, Default)]
+pub struct ScheduledSteps {
+    tasks: Arc<Mutex<JoinSet<()>>>,
+}
+
+impl ScheduledSteps {
+    pub fn schedule(&self, steps: &[CommandStep], chat_tx: &tokio::sync::mpsc::Sender<String>) {
+        let mut tasks = self.tasks.lock().unwrap();
+        // nothing else reaps the finished steps
+        while tasks.try_join_next().is_some() {}
+        schedule_steps(steps, chat_tx, &mut tasks);
+    }
+
+    /// Stops the steps that haven't been sent yet, for when the connection ends.
+    pub fn cancel(&self) {
+        self.tasks.lock().unwrap().abort_all();
+    }
+}
+
+fn commands_path() -> PathBuf {
+    crate::backend::config::app_dir().join("commands.json")
+}
+
+fn read_commands<P: AsRef<Path>>(path: P) -> Result<Vec<Command>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let commands: Vec<Command> = serde_json::from_str(&content)?;
+    Ok(commands)
+}
+
+/// Loads commands.json, starting with no commands when it can't be read.
+pub fn load_commands() -> Vec<Command> {
+    try_load_commands().unwrap_or_else(|e| {
+        log::error!("Could not load {}: {}", commands_path().display(), e);
+        Vec::new()
+    })
+}
+
+pub fn try_load_commands() -> Result<Vec<Command>, Box<dyn std::error::Error>> {
+    let path = commands_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    read_commands(path)
+}
+
+pub fn save_commands(commands: &[Command]) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(commands_path(), serde_json::to_string_pretty(commands)?)?;
+    Ok(())
+}
+
+/// Writes a new count into the commands file at `path`, leaving the rest of its commands as
+/// they are.
+fn save_counter(path: &Path, name: &str, count: i64) -> Result<(), Box<dyn std::error::Error>> {
+    let mut commands = if path.exists() { read_commands(path)? } else { Vec::new() };
+    let command = commands.iter_mut().find(|command| command.name.eq_ignore_ascii_case(name));
+    if let Some(command) = command {
+        command.counter = Some(count);
+    }
+    export_commands(&commands, path)
+}
+
+/// Saves `command` into commands.json and returns the new set. `original` is the name of the
+/// command being edited, None adds a new one.
+pub fn save_command(
+    original: Option<&str>,
+    mut command: Command
+) -> Result<Vec<Command>, Box<dyn std::error::Error>> {
+    validate_command(&command)?;
+    let mut commands = try_load_commands()?;
+    let index = match original {
+        Some(original) => {
+            let index = find_command(&commands, original)?;
+            // the editor doesn't show counts, keep the one chat has been changing
+            command.counter = commands[index].counter;
+            Some(index)
+        }
+        None => None,
+    };
+    let taken: HashSet<String> = commands
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| Some(*i) != index)
+        .flat_map(|(_, other)| triggers(other))
+        .collect();
+    if triggers(&command).iter().any(|trigger| taken.contains(trigger)) {
+        return Err(format!("!{} or one of its aliases is already used", command.name).into());
+    }
+    match index {
+        Some(index) => {
+            commands[index] = command;
+        }
+        None => commands.push(command),
+    }
+    save_commands(&commands)?;
+    Ok(commands)
+}
+
+/// Deletes the command `name` from commands.json and returns the remaining ones.
+pub fn remove_command(name: &str) -> Result<Vec<Command>, Box<dyn std::error::Error>> {
+    let mut commands = try_load_commands()?;
+    let index = find_command(&commands, name)?;
+    commands.remove(index);
+    save_commands(&commands)?;
+    Ok(commands)
+}
+
+/// Turns the command `name` on or off in commands.json and returns the new set.
+pub fn set_command_enabled(
+    name: &str,
+    enabled: bool
+) -> Result<Vec<Command>, Box<dyn std::error::Error>> {
+    let mut commands = try_load_commands()?;
+    let index = find_command(&commands, name)?;
+    commands[index].enabled = enabled;
+    save_commands(&commands)?;
+    Ok(commands)
+}
+
+fn find_command(commands: &[Command], name: &str) -> Result<usize, String> {
+    commands
+        .iter()
+        .position(|command| command.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("There is no command named {}", name))
+}
+
+/// How many commands an import took in, and how many it left out as invalid or duplicates.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+/// Writes `commands` to `path` in the same format as commands.json.
+pub fn export_commands<P: AsRef<Path>>(
+    commands: &[Command],
+    path: P
+) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(path, serde_json::to_string_pretty(commands)?)?;
+    Ok(())
+}
+
+/// Reads the commands in `path` into commands.json and returns the new set. With `merge` the
+/// current commands stay and only commands with new triggers are added, otherwise the imported
+/// ones replace them all.
+pub fn import_commands<P: AsRef<Path>>(
+    path: P,
+    merge: bool
+) -> Result<(Vec<Command>, ImportSummary), Box<dyn std::error::Error>> {
+    import_commands_into(path.as_ref(), &commands_path(), merge)
+}
+
+/// `import_commands` with the commands file at `target` in place of commands.json.
+fn import_commands_into(
+    path: &Path,
+    target: &Path,
+    merge: bool
+) -> Result<(Vec<Command>, ImportSummary), Box<dyn std::error::Error>> {
+    let imported = read_commands(path)?;
+    let existing = if merge && target.exists() { read_commands(target)? } else { Vec::new() };
+    let (commands, summary) = merge_commands(existing, imported);
+    export_commands(&commands, target)?;
+    Ok((commands, summary))
+}
+
+/// Adds the valid `imported` commands to `existing`, skipping any whose name or alias is
+/// already taken.
+pub fn merge_commands(
+    existing: Vec<Command>,
+    imported: Vec<Command>
+) -> (Vec<Command>, ImportSummary) {
+    let mut taken: HashSet<String> = existing.iter().flat_map(triggers).collect();
+    let mut commands = existing;
+    let mut summary = ImportSummary::default();
+    for command in imported {
+        if let Err(e) = validate_command(&command) {
+            log::warn!("Skipped importing !{}, {}", command.name, e);
+            summary.skipped += 1;
+            continue;
+        }
+        let command_triggers = triggers(&command);
+        if command_triggers.iter().any(|trigger| taken.contains(trigger)) {
+            log::warn!("Skipped importing !{}, the trigger is already used", command.name);
+            summary.skipped += 1;
+            continue;
+        }
+        taken.extend(command_triggers);
+        commands.push(command);
+        summary.imported += 1;
+    }
+    (commands, summary)
+}
+
+/// The lowercase name and aliases of `command`.
+fn triggers(command: &Command) -> Vec<String> {
+    std::iter
+        ::once(&command.name)
+        .chain(command.aliases.iter())
+        .map(|trigger| trigger.to_lowercase())
+        .collect()
+}
+
+fn validate_command(command: &Command) -> Result<(), String> {
+    let is_valid_trigger = |trigger: &String| {
+        !trigger.is_empty() && !trigger.contains(char::is_whitespace)
+    };
+    if !is_valid_trigger(&command.name) {
+        return Err("the name is empty or has spaces".to_string());
+    }
+    if !command.aliases.iter().all(is_valid_trigger) {
+        return Err("an alias is empty or has spaces".to_string());
+    }
+    let cooldowns = [Some(command.cooldown), command.user_cooldown];
+    if cooldowns.into_iter().flatten().any(|cooldown| cooldown > MAX_IMPORTED_COOLDOWN) {
+        return Err(format!("a cooldown is longer than {} seconds", MAX_IMPORTED_COOLDOWN));
+    }
+    PatternMatcher::new(command).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(delay_ms: u64, text: &str) -> CommandStep {
+        CommandStep { delay_ms, text: text.to_string() }
+    }
+
+    fn command(name: &str, response: &str) -> Command {
+        Command { response: response.to_string(), ..Command::new(name) }
+    }
+
+    fn context(user_level: UserLevel, args: &[&str]) -> CommandContext {
+        CommandContext {
+            username: "Viewer".to_string(),
+            user_id: "1".to_string(),
+            channel: "streamer".to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            user_level,
+            bypass_cooldown: false,
+        }
+    }
+
+    fn response(result: CommandResult) -> String {
+        match result {
+            CommandResult::Success(Some(response)) => response,
+            other => panic!("expected a response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn steps_are_sent_in_delay_order() {
+        let (chat_tx, mut chat_rx) = tokio::sync::mpsc::channel(10);
+        let mut tasks = JoinSet::new();
+        schedule_steps(&[step(40, "second"), step(0, "first")], &chat_tx, &mut tasks);
+        assert_eq!(chat_rx.recv().await.as_deref(), Some("first"));
+        assert_eq!(chat_rx.recv().await.as_deref(), Some("second"));
+    }
+
+    #[tokio::test]
+    async fn dropping_the_tasks_cancels_pending_steps() {
+        let (chat_tx, mut chat_rx) = tokio::sync::mpsc::channel(10);
+        let mut tasks = JoinSet::new();
+        schedule_steps(&[step(60_000, "never")], &chat_tx, &mut tasks);
+        drop(tasks);
+        drop(chat_tx);
+        // the channel closes once the aborted step drops its sender
+        assert_eq!(chat_rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn scheduled_steps_are_sent_until_cancelled() {
+        let (chat_tx, mut chat_rx) = tokio::sync::mpsc::channel(10);
+        let scheduled = ScheduledSteps::default();
+        scheduled.schedule(&[step(0, "sent"), step(60_000, "never")], &chat_tx);
+        assert_eq!(chat_rx.recv().await.as_deref(), Some("sent"));
+        scheduled.cancel();
+        drop(chat_tx);
+        assert_eq!(chat_rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn dropping_the_connection_cancels_scheduled_steps() {
+        let (chat_tx, mut chat_rx) = tokio::sync::mpsc::channel(10);
+        let scheduled = ScheduledSteps::default();
+        let connection_copy = scheduled.clone();
+        scheduled.schedule(&[step(60_000, "never")], &chat_tx);
+        drop(scheduled);
+        drop(connection_copy);
+        drop(chat_tx);
+        assert_eq!(chat_rx.recv().await, None);
+    }
+
+    #[test]
+    fn configured_prefix_starts_commands() {
+        let parser = CommandParser::new("?");
+        assert_eq!(parser.prefix(), "?");
+        let parsed = parser.parse("?help me").unwrap();
+        assert_eq!(parsed.name, "help");
+        assert_eq!(parsed.args, ["me"]);
+        assert!(parser.parse("!help").is_none());
+    }
+
+    #[test]
+    fn unusable_prefixes_fall_back_to_the_default() {
+        for prefix in ["", " ", "! "] {
+            let parser = CommandParser::new(prefix);
+            assert_eq!(parser.prefix(), DEFAULT_PREFIX);
+            assert_eq!(parser.parse("!help").unwrap().name, "help");
+        }
+    }
+
+    #[test]
+    fn transcript_shows_the_prefix_a_command_was_run_with() {
+        let context = context(UserLevel::Viewer, &[]);
+        let success = CommandResult::Success(Some("hello".to_string()));
+        let execution = CommandExecution::new("hi", &context, &success);
+        assert_eq!(execution.transcript_text("?").as_deref(), Some("?hi hello"));
+        let denied = CommandExecution::new("hi", &context, &CommandResult::PermissionDenied);
+        assert_eq!(denied.transcript_text("?"), None);
+    }
+
+    #[test]
+    fn ranges_parse_in_either_order_with_negative_bounds() {
+        assert_eq!(parse_range("1-100"), Some((1, 100)));
+        assert_eq!(parse_range("100-1"), Some((1, 100)));
+        assert_eq!(parse_range(" 3 - 7 "), Some((3, 7)));
+        assert_eq!(parse_range("-10--1"), Some((-10, -1)));
+        assert_eq!(parse_range("-5-5"), Some((-5, 5)));
+        for malformed in ["", "5", "-5", "a-b", "1-", "1-2-3"] {
+            assert_eq!(parse_range(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn random_variables_are_replaced() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        assert_eq!(resolve_random_variables("{random:4-4}", &mut rng), "4");
+        assert_eq!(resolve_random_variables("{choose: only }", &mut rng), "only");
+        for _ in 0..20 {
+            let rolled: i64 = resolve_random_variables("{random:6-1}", &mut rng).parse().unwrap();
+            assert!((1..=6).contains(&rolled));
+            let picked = resolve_random_variables("{choose:heads|tails}", &mut rng);
+            assert!(picked == "heads" || picked == "tails");
+        }
+    }
+
+    #[test]
+    fn malformed_random_variables_are_left_as_they_are() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        for template in ["{random:x}", "{random:1}", "{other} {", "{choose:a", "$user {}"] {
+            assert_eq!(resolve_random_variables(template, &mut rng), template);
+        }
+    }
+
+    #[test]
+    fn seeded_runs_pick_the_same_values() {
+        let run = |seed| {
+            let mut registry = CommandRegistry::new(vec![
+                command("roll", "{random:1-1000} {choose:a|b|c|d|e|f}")
+            ]);
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let context = context(UserLevel::Viewer, &[]);
+            response(CommandExecutor::execute_with_rng(&mut registry, "roll", &context, &mut rng))
+        };
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+    }
+
+    #[test]
+    fn variables_are_expanded_from_the_context() {
+        let context = context(UserLevel::Viewer, &["@Friend", "says", "hi"]);
+        assert_eq!(
+            expand_variables("$user greets $touser in $channel: $args", &context, None),
+            "Viewer greets Friend in streamer: @Friend says hi"
+        );
+    }
+
+    #[test]
+    fn touser_falls_back_to_the_chatter() {
+        let context = context(UserLevel::Viewer, &[]);
+        assert_eq!(expand_variables("hugs $touser", &context, None), "hugs Viewer");
+        assert_eq!(expand_variables("[$args]", &context, None), "[]");
+    }
+
+    #[test]
+    fn variables_end_at_the_first_non_alphanumeric_character() {
+        let context = context(UserLevel::Viewer, &[]);
+        let expanded = expand_variables("$user's turn, $user!", &context, None);
+        assert_eq!(expanded, "Viewer's turn, Viewer!");
+    }
+
+    #[test]
+    fn unknown_variables_and_dollar_signs_are_kept() {
+        let context = context(UserLevel::Viewer, &[]);
+        assert_eq!(expand_variables("$5 for $nobody $", &context, None), "$5 for $nobody $");
+        // $count only means something for counter commands
+        assert_eq!(expand_variables("$count", &context, None), "$count");
+        assert_eq!(expand_variables("$count", &context, Some(3)), "3");
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn counter_changes_follow_the_arguments() {
+        assert_eq!(apply_counter_change(5, &args(&[])), 6);
+        assert_eq!(apply_counter_change(5, &args(&["+"])), 6);
+        assert_eq!(apply_counter_change(5, &args(&["-"])), 4);
+        assert_eq!(apply_counter_change(5, &args(&["+10"])), 15);
+        assert_eq!(apply_counter_change(5, &args(&["-10"])), -5);
+        assert_eq!(apply_counter_change(5, &args(&["set", "42"])), 42);
+        assert_eq!(apply_counter_change(5, &args(&["RESET"])), 0);
+    }
+
+    #[test]
+    fn invalid_counter_changes_keep_the_count() {
+        for invalid in [&["set"][..], &["set", "many"], &["+x"], &["double"]] {
+            assert_eq!(apply_counter_change(5, &args(invalid)), 5, "{:?}", invalid);
+        }
+        assert_eq!(apply_counter_change(i64::MAX, &args(&["+1"])), i64::MAX);
+        assert_eq!(apply_counter_change(i64::MIN, &args(&["-"])), i64::MIN);
+    }
+
+    fn counter_registry(name: &str) -> (PathBuf, CommandRegistry) {
+        let path = std::env
+            ::temp_dir()
+            .join(format!("yambot-counters-{}-{}.json", name, std::process::id()));
+        let deaths = Command { counter: Some(3), ..command("deaths", "Died $count times") };
+        let commands = vec![deaths];
+        export_commands(&commands, &path).unwrap();
+        let mut registry = CommandRegistry::new(commands);
+        registry.counters_path = path.clone();
+        (path, registry)
+    }
+
+    #[test]
+    fn moderators_change_the_count_and_it_is_saved() {
+        let (path, mut registry) = counter_registry("moderator");
+        let context = context(UserLevel::Moderator, &["+2"]);
+        let result = CommandExecutor::execute(&mut registry, "deaths", &context);
+        assert_eq!(response(result), "Died 5 times");
+        assert_eq!(registry.get("deaths").unwrap().counter, Some(5));
+        let saved = read_commands(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(saved[0].counter, Some(5));
+    }
+
+    #[test]
+    fn viewers_only_read_the_count() {
+        let (path, mut registry) = counter_registry("viewer");
+        let context = context(UserLevel::Vip, &["reset"]);
+        let result = CommandExecutor::execute(&mut registry, "deaths", &context);
+        assert_eq!(response(result), "Died 3 times");
+        let saved = read_commands(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(saved[0].counter, Some(3));
+    }
+
+    fn run_as(registry: &mut CommandRegistry, name: &str, username: &str) -> CommandResult {
+        let context = CommandContext {
+            username: username.to_string(),
+            ..context(UserLevel::Viewer, &[])
+        };
+        CommandExecutor::execute(registry, name, &context)
+    }
+
+    fn remaining(result: CommandResult) -> Duration {
+        match result {
+            CommandResult::OnCooldown(remaining) => remaining,
+            other => panic!("expected a cooldown, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn global_cooldown_holds_back_every_chatter() {
+        let hug = Command { cooldown: 30, ..command("hug", "hugs") };
+        let mut registry = CommandRegistry::new(vec![hug]);
+        assert!(matches!(run_as(&mut registry, "hug", "alice"), CommandResult::Success(_)));
+        let remaining = remaining(run_as(&mut registry, "hug", "bob"));
+        assert!(remaining > Duration::from_secs(29) && remaining <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn user_cooldown_only_holds_back_the_same_chatter() {
+        let hug = Command { user_cooldown: Some(60), ..command("hug", "hugs") };
+        let mut registry = CommandRegistry::new(vec![hug]);
+        assert!(matches!(run_as(&mut registry, "hug", "alice"), CommandResult::Success(_)));
+        assert!(matches!(run_as(&mut registry, "hug", "ALICE"), CommandResult::OnCooldown(_)));
+        assert!(matches!(run_as(&mut registry, "hug", "bob"), CommandResult::Success(_)));
+    }
+
+    #[test]
+    fn longer_of_both_cooldowns_is_reported() {
+        let hug = Command { cooldown: 10, user_cooldown: Some(60), ..command("hug", "hugs") };
+        let mut registry = CommandRegistry::new(vec![hug]);
+        run_as(&mut registry, "hug", "alice");
+        assert!(remaining(run_as(&mut registry, "hug", "alice")) > Duration::from_secs(10));
+        let remaining = remaining(run_as(&mut registry, "hug", "bob"));
+        assert!(remaining <= Duration::from_secs(10));
+    }
+
+    #[test]
+    fn expired_cooldowns_let_the_command_run() {
+        let hug = Command { cooldown: 10, user_cooldown: Some(60), ..command("hug", "hugs") };
+        let mut registry = CommandRegistry::new(vec![hug]);
+        run_as(&mut registry, "hug", "alice");
+        let long_ago = Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        registry.last_used.insert("hug".to_string(), long_ago);
+        registry.user_last_used.insert(("hug".to_string(), "alice".to_string()), long_ago);
+        assert!(matches!(run_as(&mut registry, "hug", "alice"), CommandResult::Success(_)));
+    }
+
+    #[test]
+    fn manual_runs_skip_both_cooldowns() {
+        let hug = Command { cooldown: 10, user_cooldown: Some(60), ..command("hug", "hugs") };
+        let mut registry = CommandRegistry::new(vec![hug]);
+        run_as(&mut registry, "hug", "Viewer");
+        let manual = CommandContext { bypass_cooldown: true, ..context(UserLevel::Viewer, &[]) };
+        let result = CommandExecutor::execute(&mut registry, "hug", &manual);
+        assert_eq!(response(result), "hugs");
+    }
+
+    #[test]
+    fn picked_responses_never_repeat_the_previous_one() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        let mut previous = None;
+        for _ in 0..100 {
+            let index = pick_response(3, previous, &mut rng);
+            assert!(index < 3);
+            assert_ne!(Some(index), previous);
+            previous = Some(index);
+        }
+    }
+
+    #[test]
+    fn single_responses_and_stale_indexes_are_still_picked() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        assert_eq!(pick_response(1, Some(0), &mut rng), 0);
+        // a previous index past the end after the list was edited
+        assert!(pick_response(2, Some(5), &mut rng) < 2);
+    }
+
+    #[test]
+    fn random_responses_alternate_between_runs() {
+        let responses = vec!["heads".to_string(), "tails".to_string()];
+        let coin = Command { responses, ..command("coin", "unused") };
+        let mut registry = CommandRegistry::new(vec![coin]);
+        let mut rng = fastrand::Rng::with_seed(1);
+        let context = context(UserLevel::Viewer, &[]);
+        let mut run = || {
+            response(CommandExecutor::execute_with_rng(&mut registry, "coin", &context, &mut rng))
+        };
+        let first = run();
+        let second = run();
+        assert_ne!(first, second);
+        assert_eq!(run(), first);
+    }
+
+    fn pattern_command(name: &str, trigger_kind: TriggerKind, pattern: &str) -> Command {
+        Command { trigger_kind, pattern: pattern.to_string(), ..command(name, "matched") }
+    }
+
+    fn parsed_name(registry: &CommandRegistry, message: &str) -> Option<String> {
+        let parser = CommandParser::with_default_prefix();
+        parser.parse_with_patterns(message, registry).map(|parsed| parsed.name)
+    }
+
+    #[test]
+    fn regex_and_contains_commands_match_anywhere_ignoring_case() {
+        let registry = CommandRegistry::new(vec![
+            pattern_command("greeting", TriggerKind::Regex, r"\bhe+llo\b"),
+            pattern_command("gg", TriggerKind::Contains, "Good Game"),
+        ]);
+        assert_eq!(parsed_name(&registry, "well HEEELLO there").as_deref(), Some("greeting"));
+        assert_eq!(parsed_name(&registry, "that was a good game!").as_deref(), Some("gg"));
+        assert_eq!(parsed_name(&registry, "othello"), None);
+    }
+
+    #[test]
+    fn first_matching_pattern_by_name_wins() {
+        let registry = CommandRegistry::new(vec![
+            pattern_command("zebra", TriggerKind::Contains, "hi"),
+            pattern_command("apple", TriggerKind::Contains, "hi"),
+        ]);
+        assert_eq!(parsed_name(&registry, "hi all").as_deref(), Some("apple"));
+    }
+
+    #[test]
+    fn pattern_cannot_shadow_a_prefix_command() {
+        let registry = CommandRegistry::new(vec![
+            command("lurk", "lurking"),
+            pattern_command("anything", TriggerKind::Regex, ".*"),
+        ]);
+        assert_eq!(parsed_name(&registry, "!lurk now").as_deref(), Some("lurk"));
+        // the prefix marks a command, an unknown one isn't handed to the patterns
+        assert_eq!(parsed_name(&registry, "!unknown").as_deref(), Some("unknown"));
+        // and a pattern command isn't triggered by its name
+        assert_eq!(parsed_name(&registry, "!anything"), None);
+        assert_eq!(parsed_name(&registry, "lurk").as_deref(), Some("anything"));
+    }
+
+    #[test]
+    fn unusable_patterns_never_trigger() {
+        let registry = CommandRegistry::new(vec![
+            pattern_command("empty", TriggerKind::Contains, ""),
+            pattern_command("broken", TriggerKind::Regex, "(unclosed"),
+            pattern_command("long", TriggerKind::Contains, &"a".repeat(MAX_PATTERN_LENGTH + 1)),
+        ]);
+        assert_eq!(parsed_name(&registry, "(unclosed"), None);
+        assert_eq!(parsed_name(&registry, &"a".repeat(MAX_PATTERN_LENGTH + 1)), None);
+        assert!(registry.get("broken").is_some());
+    }
+
+    fn names(commands: &[Command]) -> Vec<&str> {
+        commands.iter().map(|command| command.name.as_str()).collect()
+    }
+
+    #[test]
+    fn merged_commands_skip_taken_triggers_and_invalid_ones() {
+        let existing = vec![Command { aliases: vec!["Bye".to_string()], ..command("hi", "") }];
+        let imported = vec![
+            command("HI", "case doesn't matter"),
+            command("bye", "an alias is taken too"),
+            Command { aliases: vec!["hi".to_string()], ..command("hello", "") },
+            command("has space", ""),
+            Command { cooldown: MAX_IMPORTED_COOLDOWN + 1, ..command("slow", "") },
+            command("lurk", ""),
+            command("Lurk", "a duplicate within the import"),
+        ];
+        let (commands, summary) = merge_commands(existing, imported);
+        assert_eq!(names(&commands), ["hi", "lurk"]);
+        assert_eq!(summary, ImportSummary { imported: 1, skipped: 6 });
+    }
+
+    fn import_files(name: &str) -> (PathBuf, PathBuf) {
+        let file = |kind: &str| {
+            std::env
+                ::temp_dir()
+                .join(format!("yambot-import-{}-{}-{}.json", name, kind, std::process::id()))
+        };
+        let (source, target) = (file("source"), file("target"));
+        let imported = [command("lurk", "imported"), command("hi", "imported")];
+        export_commands(&imported, &source).unwrap();
+        export_commands(&[command("hi", "existing"), command("bye", "existing")], &target).unwrap();
+        (source, target)
+    }
+
+    #[test]
+    fn merging_an_import_keeps_the_current_commands() {
+        let (source, target) = import_files("merge");
+        let result = import_commands_into(&source, &target, true);
+        let saved = read_commands(&target).unwrap();
+        let _ = fs::remove_file(&source);
+        let _ = fs::remove_file(&target);
+        let (commands, summary) = result.unwrap();
+        assert_eq!(names(&commands), ["hi", "bye", "lurk"]);
+        assert_eq!(commands[0].response, "existing");
+        assert_eq!(summary, ImportSummary { imported: 1, skipped: 1 });
+        assert_eq!(names(&saved), names(&commands));
+    }
+
+    #[test]
+    fn overwriting_import_replaces_the_current_commands() {
+        let (source, target) = import_files("overwrite");
+        let result = import_commands_into(&source, &target, false);
+        let saved = read_commands(&target).unwrap();
+        let _ = fs::remove_file(&source);
+        let _ = fs::remove_file(&target);
+        let (commands, summary) = result.unwrap();
+        assert_eq!(names(&commands), ["lurk", "hi"]);
+        assert_eq!(commands[1].response, "imported");
+        assert_eq!(summary, ImportSummary { imported: 2, skipped: 0 });
+        assert_eq!(names(&saved), names(&commands));
+    }
+
+    #[test]
+    fn aliases_resolve_to_their_command() {
+        let hug = Command { aliases: vec!["Cuddle".to_string()], ..command("Hug", "hugs") };
+        let mut registry = CommandRegistry::new(vec![hug]);
+        assert_eq!(registry.resolve("CUDDLE"), Some("hug"));
+        assert_eq!(registry.resolve("hug"), Some("hug"));
+        assert_eq!(registry.resolve("unknown"), None);
+        registry.register(command("cuddle", "own command"));
+        // a command's own name wins over another's alias
+        assert_eq!(registry.resolve("cuddle"), Some("cuddle"));
+    }
+
+    #[test]
+    fn aliases_run_the_command_and_share_its_cooldown() {
+        let aliases = vec!["cuddle".to_string()];
+        let hug = Command { cooldown: 30, aliases, ..command("hug", "hugs") };
+        let mut registry = CommandRegistry::new(vec![hug]);
+        let context = context(UserLevel::Viewer, &[]);
+        assert_eq!(response(CommandExecutor::execute(&mut registry, "cuddle", &context)), "hugs");
+        let result = CommandExecutor::execute(&mut registry, "hug", &context);
+        assert!(matches!(result, CommandResult::OnCooldown(_)));
+    }
+
+    #[test]
+    fn replaced_commands_drop_their_old_aliases() {
+        let hug = Command { aliases: vec!["cuddle".to_string()], ..command("hug", "hugs") };
+        let mut registry = CommandRegistry::new(vec![hug]);
+        registry.replace(vec![command("hug", "hugs")]);
+        assert_eq!(registry.resolve("cuddle"), None);
+    }
+
+    #[test]
+    fn silent_commands_run_without_a_reply() {
+        let hug = Command { silent: true, cooldown: 30, ..command("hug", "hugs $user") };
+        let mut registry = CommandRegistry::new(vec![hug]);
+        let context = context(UserLevel::Viewer, &[]);
+        let result = CommandExecutor::execute(&mut registry, "hug", &context);
+        assert_eq!(result, CommandResult::Success(None));
+        // the run still counts towards the cooldown
+        assert_eq!(registry.on_cooldown(), HashSet::from(["hug".to_string()]));
+    }
+
+    #[test]
+    fn silent_counters_still_change_the_count() {
+        let (path, mut registry) = counter_registry("silent");
+        registry.commands.get_mut("deaths").unwrap().silent = true;
+        let context = context(UserLevel::Moderator, &[]);
+        let result = CommandExecutor::execute(&mut registry, "deaths", &context);
+        let _ = fs::remove_file(&path);
+        assert_eq!(result, CommandResult::Success(None));
+        assert_eq!(registry.get("deaths").unwrap().counter, Some(4));
+    }
+
+    fn names_set(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn watcher_reports_cooldowns_starting_and_ending() {
+        let mut watcher = CooldownWatcher::default();
+        let changes = watcher.update(names_set(&["hug", "lurk"]));
+        let mut started = changes.started.clone();
+        started.sort();
+        assert_eq!(started, ["hug", "lurk"]);
+        assert!(changes.ready.is_empty());
+        let changes = watcher.update(names_set(&["lurk", "dice"]));
+        assert_eq!(changes, CooldownChanges {
+            started: vec!["dice".to_string()],
+            ready: vec!["hug".to_string()],
+        });
+        assert_eq!(watcher.cooling(), &names_set(&["lurk", "dice"]));
+        assert!(watcher.update(names_set(&["lurk", "dice"])).is_empty());
+    }
+
+    #[test]
+    fn only_commands_with_time_left_are_on_cooldown() {
+        let hug = Command { cooldown: 30, ..command("hug", "hugs") };
+        let mut registry = CommandRegistry::new(vec![hug, command("lurk", "lurking")]);
+        let context = context(UserLevel::Viewer, &[]);
+        CommandExecutor::execute(&mut registry, "hug", &context);
+        CommandExecutor::execute(&mut registry, "lurk", &context);
+        assert_eq!(registry.on_cooldown(), names_set(&["hug"]));
+        let long_ago = Instant::now().checked_sub(Duration::from_secs(31)).unwrap();
+        registry.last_used.insert("hug".to_string(), long_ago);
+        assert!(registry.on_cooldown().is_empty());
+    }
+
+    #[test]
+    fn executions_record_each_outcome() {
+        let context = context(UserLevel::Viewer, &[]);
+        let cases = [
+            (CommandResult::Success(Some("hi".to_string())), CommandOutcome::Success, Some("hi")),
+            (CommandResult::Success(None), CommandOutcome::Success, None),
+            (CommandResult::OnCooldown(Duration::from_secs(5)), CommandOutcome::OnCooldown, None),
+            (CommandResult::PermissionDenied, CommandOutcome::PermissionDenied, None),
+            (CommandResult::NotFound, CommandOutcome::NotFound, None),
+        ];
+        for (result, outcome, response) in cases {
+            let execution = CommandExecution::new("hug", &context, &result);
+            assert_eq!(execution.trigger, "hug");
+            assert_eq!(execution.username, "Viewer");
+            assert_eq!(execution.outcome, outcome);
+            assert_eq!(execution.response.as_deref(), response);
+        }
+    }
+
+    #[test]
+    fn only_successful_runs_reach_the_transcript() {
+        let context = context(UserLevel::Viewer, &[]);
+        let silent = CommandExecution::new("hug", &context, &CommandResult::Success(None));
+        assert_eq!(silent.transcript_text("!").as_deref(), Some("!hug "));
+        let denied = CommandExecution::new("hug", &context, &CommandResult::PermissionDenied);
+        assert_eq!(denied.transcript_text("!"), None);
+    }
+}
@@ -1,40 +1,481 @@
 use serde::{ Deserialize, Serialize };
 use std::fs;
-use std::path::Path;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex, RwLock, RwLockReadGuard };
+use std::time::{ Duration, Instant, SystemTime };
 
-use crate::ui::{ ChatbotConfig, Config };
+use crate::ui::{ ChatbotConfig, Config, TTSConfig, UIConfig };
+
+use super::automod::AutomodConfig;
+use super::dashboard::DashboardConfig;
+use super::follows::FollowsConfig;
+use super::raids::RaidsConfig;
+use super::secrets;
+use super::regulars::RegularsConfig;
+use super::shoutout::ShoutoutConfig;
+use super::spam::SpamConfig;
+use super::welcome::WelcomeConfig;
+
+// written when config.toml is missing or can't be read, the other keys fall back to their
+// serde defaults
+const DEFAULT_CONFIG: &str =
+    r#"
+[chatbot]
+channel_name = ""
+auth_token = ""
+
+[sfx]
+volume = 0.5
+enabled = true
+
+[sfx.permited_roles]
+subs = true
+vips = false
+mods = true
+
+[tts]
+volume = 1.0
+enabled = true
+
+[tts.permited_roles]
+subs = true
+vips = false
+mods = true
+"#;
+
+// how long an outside edit has to leave config.toml alone before it's loaded, editors and sync
+// tools often write a file several times in a row
+const EDIT_SETTLE_TIME: Duration = Duration::from_secs(2);
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AppConfig {
+    // version of the app that last wrote this config, used to detect upgrades
+    #[serde(default)]
+    pub last_run_version: String,
+    // turns off TTS, sounds and every reaction to chat while still showing it
+    #[serde(default)]
+    pub safe_mode: bool,
     pub chatbot: ChatbotConfig,
     pub sfx: Config,
-    pub tts: Config,
+    pub tts: TTSConfig,
+    #[serde(default)]
+    pub spam: SpamConfig,
+    #[serde(default)]
+    pub regulars: RegularsConfig,
+    #[serde(default)]
+    pub ui: UIConfig,
+    #[serde(default)]
+    pub dashboard: DashboardConfig,
+    #[serde(default)]
+    pub follows: FollowsConfig,
+    #[serde(default)]
+    pub raids: RaidsConfig,
+    #[serde(default)]
+    pub welcome: WelcomeConfig,
+    #[serde(default)]
+    pub automod: AutomodConfig,
+    #[serde(default)]
+    pub shoutout: ShoutoutConfig,
 }
 
 impl AppConfig {
     fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
         let content = fs::read_to_string(path)?;
-        let config: AppConfig = toml::from_str(&content)?;
+        let mut config: AppConfig = toml::from_str(&content)?;
+        // a token that can't be decrypted has to be entered again, the rest of the config is fine
+        if let Err(e) = decrypt_tokens(&mut config) {
+            log::error!("Could not decrypt the auth token: {}", e);
+            config.chatbot.auth_token.clear();
+        }
         Ok(config)
     }
 
+    pub fn set_tts_volume(&mut self, volume: f64) {
+        self.tts.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn set_sfx_volume(&mut self, volume: f64) {
+        self.sfx.volume = volume.clamp(0.0, 1.0);
+    }
+
     fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
-        let content = toml::to_string(self)?;
-        fs::write(path, content)?;
+        let mut config = self.clone();
+        if config.chatbot.encrypt_tokens {
+            if let Err(e) = encrypt_tokens(&mut config) {
+                log::warn!("Saving the auth token unencrypted: {}", e);
+                config.chatbot.auth_token = self.chatbot.auth_token.clone();
+            }
+        }
+        let content = toml::to_string(&config)?;
+        // written next to the config and renamed over it, so a load never sees half a file
+        let temporary = path.as_ref().with_extension("toml.tmp");
+        fs::write(&temporary, content)?;
+        fs::rename(&temporary, path)?;
         Ok(())
     }
 }
 
+/// Encrypts the auth token for writing to disk. Blank and already encrypted tokens are left as
+/// they are.
+pub fn encrypt_tokens(config: &mut AppConfig) -> Result<(), String> {
+    let token = &mut config.chatbot.auth_token;
+    if !token.is_empty() && !secrets::is_encrypted(token) {
+        *token = secrets::encrypt(token)?;
+    }
+    Ok(())
+}
+
+/// Decrypts an auth token written by `encrypt_tokens`. Plaintext tokens from before encryption
+/// are kept and get encrypted the next time the config is saved.
+pub fn decrypt_tokens(config: &mut AppConfig) -> Result<(), String> {
+    let token = &mut config.chatbot.auth_token;
+    if secrets::is_encrypted(token) {
+        *token = secrets::decrypt(token)?;
+    }
+    Ok(())
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        toml::from_str(DEFAULT_CONFIG).expect("the default config should parse")
+    }
+}
+
+/// Directory the config and the other data files live in: the project root when run through
+/// cargo, otherwise the directory of the executable.
+pub fn app_dir() -> PathBuf {
+    project_root::get_project_root()
+        .ok()
+        .or_else(|| {
+            std::env
+                ::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        })
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+pub fn config_path() -> PathBuf {
+    app_dir().join("config.toml")
+}
+
+/// Reads config.toml, replacing it with the defaults when it's missing or invalid. Only done on
+/// startup, the backend reads its `SharedConfig` after that.
 pub fn load_config() -> AppConfig {
-    let project_root = project_root::get_project_root().unwrap();
-    let config_path = project_root.join("config.toml");
-    let config: AppConfig = AppConfig::from_file(config_path).unwrap();
+    AppConfig::from_file(config_path()).unwrap_or_else(|e| {
+        log::error!("Could not load {}: {}", config_path().display(), e);
+        reset_config()
+    })
+}
 
-    return config;
+/// Checks config.toml on startup, resetting it like `load_config` does. Returns what was wrong
+/// with it so it can be shown to the user.
+pub fn check_config() -> Option<String> {
+    let path = config_path();
+    let e = AppConfig::from_file(&path).err()?;
+    let reason = if path.exists() {
+        format!(
+            "{} is invalid ({}), using the defaults and keeping the old file as {}",
+            path.display(),
+            e,
+            backup_path().display()
+        )
+    } else {
+        format!("{} was missing, created it with the defaults", path.display())
+    };
+    reset_config();
+    Some(reason)
 }
 
-pub fn save_config(config: &AppConfig) {
-    let project_root = project_root::get_project_root().unwrap();
-    let config_path = project_root.join("config.toml");
-    config.to_file(config_path).unwrap();
+/// The config the backend tasks share, updated in place when it's saved or edited. It's read for
+/// every chat message and sound played, which used to mean reading and parsing config.toml each
+/// time; now the file is only read on startup and after an edit made outside the app.
+#[derive(Clone)]
+pub struct SharedConfig {
+    config: Arc<RwLock<AppConfig>>,
+    path: PathBuf,
+    // modification time of the file when the app last read or wrote it, to tell its own writes
+    // apart from edits made outside the app
+    last_seen: Arc<Mutex<Option<SystemTime>>>,
+}
+
+impl SharedConfig {
+    /// Shares `config`, as just read from `path`, saving it there.
+    pub fn new(config: AppConfig, path: PathBuf) -> Self {
+        SharedConfig {
+            config: Arc::new(RwLock::new(config)),
+            last_seen: Arc::new(Mutex::new(modified_at(&path))),
+            path,
+        }
+    }
+
+    /// A copy of the current config, for when several values have to agree with each other.
+    pub fn get(&self) -> AppConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// The current config without copying it, not to be held across an await.
+    pub fn read(&self) -> RwLockReadGuard<'_, AppConfig> {
+        self.config.read().unwrap()
+    }
+
+    /// Replaces the config in memory only, e.g. with an edit already made to the file.
+    pub fn replace(&self, config: AppConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Updates the config in memory and writes it to disk. The new values are used even when
+    /// writing fails.
+    pub fn save(&self, config: &AppConfig) -> Result<(), String> {
+        self.replace(config.clone());
+        let result = config
+            .to_file(&self.path)
+            .map_err(|e| format!("Could not save {}: {}", self.path.display(), e));
+        *self.last_seen.lock().unwrap() = modified_at(&self.path);
+        result
+    }
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Notices edits made to a `SharedConfig`'s file outside the app, e.g. in a text editor.
+pub struct ConfigWatcher {
+    config: SharedConfig,
+    settle_time: Duration,
+    // modification time of an edit waiting to settle, and when it was first seen
+    pending: Option<(SystemTime, Instant)>,
+}
+
+impl ConfigWatcher {
+    pub fn new(config: SharedConfig) -> Self {
+        ConfigWatcher {
+            config,
+            settle_time: EDIT_SETTLE_TIME,
+            pending: None,
+        }
+    }
+
+    /// Called periodically. Once an outside edit has left the file alone for the settle time,
+    /// loads it into the shared config and returns it, or returns why it was rejected, in which
+    /// case the current config stays in use.
+    pub fn poll(&mut self) -> Option<Result<AppConfig, String>> {
+        let modified = modified_at(&self.config.path)?;
+        if Some(modified) == *self.config.last_seen.lock().unwrap() {
+            self.pending = None;
+            return None;
+        }
+        match self.pending {
+            Some((pending, since)) if pending == modified => {
+                if since.elapsed() < self.settle_time {
+                    return None;
+                }
+            }
+            // a new write, start waiting again
+            _ => {
+                self.pending = Some((modified, Instant::now()));
+                return None;
+            }
+        }
+        self.pending = None;
+        *self.config.last_seen.lock().unwrap() = Some(modified);
+        match AppConfig::from_file(&self.config.path) {
+            Ok(config) => {
+                self.config.replace(config.clone());
+                Some(Ok(config))
+            }
+            Err(e) => {
+                Some(Err(format!("Ignored the edit to {}: {}", self.config.path.display(), e)))
+            }
+        }
+    }
+}
+
+fn backup_path() -> PathBuf {
+    backup_path_of(&config_path())
+}
+
+fn backup_path_of(path: &Path) -> PathBuf {
+    path.with_extension("toml.bak")
+}
+
+fn reset_config() -> AppConfig {
+    write_default_config(&config_path())
+}
+
+// keeps a config that failed to load around instead of overwriting the user's edits
+fn write_default_config(path: &Path) -> AppConfig {
+    let config = AppConfig::default();
+    if path.exists() {
+        if let Err(e) = fs::rename(path, backup_path_of(path)) {
+            log::error!("Could not back up {}: {}", path.display(), e);
+            return config;
+        }
+    }
+    if let Err(e) = config.to_file(path) {
+        log::error!("Could not save {}: {}", path.display(), e);
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh directory per test, so tests never touch the real config.toml
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env
+            ::temp_dir()
+            .join(format!("yambot-config-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn missing_config_is_created_with_the_defaults() {
+        let path = test_dir("missing").join("config.toml");
+        assert!(AppConfig::from_file(&path).is_err());
+        let config = write_default_config(&path);
+        assert_eq!(config.sfx.volume, AppConfig::default().sfx.volume);
+        assert!(AppConfig::from_file(&path).is_ok());
+        assert!(!backup_path_of(&path).exists());
+    }
+
+    #[test]
+    fn malformed_config_is_backed_up_and_replaced() {
+        let path = test_dir("malformed").join("config.toml");
+        fs::write(&path, "[chatbot\nchannel_name = ").unwrap();
+        assert!(AppConfig::from_file(&path).is_err());
+        write_default_config(&path);
+        assert!(AppConfig::from_file(&path).is_ok());
+        let backup = fs::read_to_string(backup_path_of(&path)).unwrap();
+        assert_eq!(backup, "[chatbot\nchannel_name = ");
+    }
+
+    #[test]
+    fn saved_config_is_read_from_memory_not_the_file() {
+        let path = test_dir("shared").join("config.toml");
+        let shared = SharedConfig::new(AppConfig::default(), path.clone());
+        let mut config = shared.get();
+        config.set_tts_volume(0.25);
+        shared.save(&config).unwrap();
+        // a read of the file would fail now
+        fs::write(&path, "[chatbot\nchannel_name = ").unwrap();
+        assert_eq!(shared.get().tts.volume, 0.25);
+        assert_eq!(shared.read().tts.volume, 0.25);
+    }
+
+    #[test]
+    fn failed_save_still_updates_the_config_in_memory() {
+        let path = test_dir("unwritable").join("missing").join("config.toml");
+        let shared = SharedConfig::new(AppConfig::default(), path.clone());
+        let mut config = shared.get();
+        config.safe_mode = true;
+        assert!(shared.save(&config).is_err());
+        assert!(shared.read().safe_mode);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn replaced_config_is_seen_by_every_clone() {
+        let shared = SharedConfig::new(
+            AppConfig::default(),
+            test_dir("clones").join("config.toml")
+        );
+        let task_copy = shared.clone();
+        let mut config = AppConfig::default();
+        config.set_sfx_volume(0.1);
+        shared.replace(config);
+        assert_eq!(task_copy.read().sfx.volume, 0.1);
+    }
+
+    // a config file as the app would have loaded it, with a watcher that reloads edits as soon
+    // as the next poll sees them unchanged
+    fn watched_config(name: &str) -> (PathBuf, SharedConfig, ConfigWatcher) {
+        let path = test_dir(name).join("config.toml");
+        let config = write_default_config(&path);
+        let shared = SharedConfig::new(config, path.clone());
+        let watcher = ConfigWatcher {
+            config: shared.clone(),
+            settle_time: Duration::ZERO,
+            pending: None,
+        };
+        (path, shared, watcher)
+    }
+
+    // outside edits get a modification time of their own, even on filesystems with coarse times
+    fn edit_file(path: &Path, content: &str, seconds_later: u64) {
+        fs::write(path, content).unwrap();
+        fs::File
+            ::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(seconds_later))
+            .unwrap();
+    }
+
+    fn with_tts_volume(volume: f64) -> String {
+        let mut config = AppConfig::default();
+        config.set_tts_volume(volume);
+        toml::to_string(&config).unwrap()
+    }
+
+    #[test]
+    fn outside_edit_is_reloaded_once_it_settles() {
+        let (path, shared, mut watcher) = watched_config("edit");
+        assert!(watcher.poll().is_none());
+        edit_file(&path, &with_tts_volume(0.3), 5);
+        assert!(watcher.poll().is_none());
+        let reloaded = watcher.poll().unwrap().unwrap();
+        assert_eq!(reloaded.tts.volume, 0.3);
+        assert_eq!(shared.read().tts.volume, 0.3);
+        assert!(watcher.poll().is_none());
+    }
+
+    #[test]
+    fn invalid_edit_keeps_the_current_config() {
+        let (path, shared, mut watcher) = watched_config("invalid");
+        edit_file(&path, "[tts\nvolume = ", 5);
+        assert!(watcher.poll().is_none());
+        assert!(watcher.poll().unwrap().is_err());
+        assert_eq!(shared.read().tts.volume, AppConfig::default().tts.volume);
+        // not reported again until the file changes
+        assert!(watcher.poll().is_none());
+    }
+
+    #[test]
+    fn own_saves_are_not_reloaded() {
+        let (_, shared, mut watcher) = watched_config("own-save");
+        let mut config = shared.get();
+        config.set_tts_volume(0.4);
+        shared.save(&config).unwrap();
+        assert!(watcher.poll().is_none());
+        assert!(watcher.poll().is_none());
+    }
+
+    #[test]
+    fn rapid_edits_are_loaded_once_after_the_last() {
+        let (path, shared, mut watcher) = watched_config("rapid");
+        edit_file(&path, &with_tts_volume(0.2), 5);
+        assert!(watcher.poll().is_none());
+        edit_file(&path, &with_tts_volume(0.6), 6);
+        assert!(watcher.poll().is_none());
+        assert_eq!(watcher.poll().unwrap().unwrap().tts.volume, 0.6);
+        assert_eq!(shared.read().tts.volume, 0.6);
+    }
+
+    #[test]
+    fn edit_waits_for_the_settle_time() {
+        let (path, _, mut watcher) = watched_config("settle");
+        watcher.settle_time = Duration::from_secs(60);
+        edit_file(&path, &with_tts_volume(0.2), 5);
+        for _ in 0..3 {
+            assert!(watcher.poll().is_none());
+        }
+    }
 }
@@ -0,0 +1,405 @@
+use ring::rand::{ SecureRandom, SystemRandom };
+use serde::{ Deserialize, Serialize };
+use std::net::SocketAddr;
+use std::sync::{ Arc, Mutex };
+use tokio::io::{ AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader };
+use tokio::net::{ TcpListener, TcpStream };
+use tokio::sync::mpsc::Sender;
+
+use crate::ui::FrontendToBackendMessage;
+
+use super::config::SharedConfig;
+use super::tts::queue::TTSQueue;
+
+// requests with a bigger body are rejected before it is read
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+const DASHBOARD_HTML: &str = include_str!("../../assets/dashboard.html");
+
+/// Settings for the remote control dashboard, read once on startup.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DashboardConfig {
+    pub enabled: bool,
+    // localhost only by default, use 0.0.0.0 to reach it from other devices on the network
+    pub address: String,
+    // required as a bearer token by the API, generated the first time the dashboard starts
+    pub token: String,
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:8080".to_string(),
+            token: String::new(),
+        }
+    }
+}
+
+/// A random token for the API, 32 hex characters.
+pub fn generate_token() -> Result<String, String> {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| "Could not generate a dashboard token".to_string())?;
+    Ok(
+        bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    )
+}
+
+/// The headers of a request that `handle_request` looks at.
+#[derive(Debug, Default)]
+pub struct RequestHeaders {
+    pub authorization: Option<String>,
+    pub host: Option<String>,
+    pub origin: Option<String>,
+    pub content_type: Option<String>,
+}
+
+/// What the API handlers can read and control.
+pub struct DashboardContext {
+    pub frontend_tx: Sender<FrontendToBackendMessage>,
+    pub tts_queue: Arc<Mutex<TTSQueue>>,
+    pub config: SharedConfig,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl ApiResponse {
+    fn json(status: u16, body: String) -> Self {
+        Self { status, content_type: "application/json", body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, serde_json::json!({ "error": message }).to_string())
+    }
+}
+
+#[derive(Serialize)]
+struct Status {
+    channel: String,
+    tts_volume: f64,
+    sfx_volume: f64,
+    safe_mode: bool,
+}
+
+/// Messages the dashboard may send. Anything touching files on disk stays in the app itself.
+fn is_remote_allowed(message: &FrontendToBackendMessage) -> bool {
+    matches!(
+        message,
+        FrontendToBackendMessage::ConnectToChat(_) |
+            FrontendToBackendMessage::DisconnectFromChat(_) |
+            FrontendToBackendMessage::SkipTTS |
+            FrontendToBackendMessage::SetVolume(..) |
+            FrontendToBackendMessage::RunCommand { .. } |
+            FrontendToBackendMessage::TestTTSLanguage(_) |
+            FrontendToBackendMessage::SetSafeMode(_)
+    )
+}
+
+/// Whether `host` names the address the dashboard is bound to. Checked so a web page can't reach
+/// the API through a domain that resolves to this machine (DNS rebinding).
+fn is_allowed_host(host: &str, address: &str) -> bool {
+    if host.eq_ignore_ascii_case(address) {
+        return true;
+    }
+    let Ok(address) = address.parse::<SocketAddr>() else {
+        return false;
+    };
+    let Some(name) = host.strip_suffix(&format!(":{}", address.port())) else {
+        return false;
+    };
+    // listening on every interface, any of the machine's names may be used
+    address.ip().is_unspecified() ||
+        (address.ip().is_loopback() && name.eq_ignore_ascii_case("localhost"))
+}
+
+/// Checks the headers browsers set, so other web pages can't use the API on the streamer's
+/// behalf. Returns why the request was refused.
+fn check_browser_headers(
+    method: &str,
+    headers: &RequestHeaders,
+    config: &DashboardConfig
+) -> Result<(), &'static str> {
+    let host = headers.host.as_deref().unwrap_or_default();
+    if !is_allowed_host(host, &config.address) {
+        return Err("unknown host");
+    }
+    // sent by browsers on cross-origin requests and every POST, other clients may leave it out
+    if let Some(origin) = &headers.origin {
+        if !origin.eq_ignore_ascii_case(&format!("http://{}", host)) {
+            return Err("requests from other sites are not allowed");
+        }
+    }
+    // plain forms can post other content types without a preflight, JSON can't
+    if method == "POST" {
+        let content_type = headers.content_type.as_deref().unwrap_or_default();
+        let media_type = content_type.split(';').next().unwrap_or_default().trim();
+        if !media_type.eq_ignore_ascii_case("application/json") {
+            return Err("the body must be application/json");
+        }
+    }
+    Ok(())
+}
+
+/// Handles a single request, independent of how it arrived.
+pub fn handle_request(
+    method: &str,
+    path: &str,
+    headers: &RequestHeaders,
+    body: &str,
+    config: &DashboardConfig,
+    context: &DashboardContext
+) -> ApiResponse {
+    if let Err(reason) = check_browser_headers(method, headers, config) {
+        return ApiResponse::error(403, reason);
+    }
+    let path = path.split('?').next().unwrap_or_default();
+    if method == "GET" && path == "/" {
+        return ApiResponse {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: DASHBOARD_HTML.to_string(),
+        };
+    }
+    let token = headers.authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "));
+    // an empty token never matches, the API stays closed until one is set
+    if config.token.is_empty() || token != Some(config.token.as_str()) {
+        return ApiResponse::error(401, "invalid token");
+    }
+    match (method, path) {
+        ("GET", "/api/status") => {
+            let app_config = context.config.get();
+            let status = Status {
+                channel: app_config.chatbot.channel_name,
+                tts_volume: app_config.tts.volume,
+                sfx_volume: app_config.sfx.volume,
+                safe_mode: app_config.safe_mode,
+            };
+            ApiResponse::json(200, serde_json::to_string(&status).unwrap())
+        }
+        ("GET", "/api/queue") => {
+            let queue = context.tts_queue.lock().unwrap().get_all_with_current();
+            ApiResponse::json(200, serde_json::to_string(&queue).unwrap())
+        }
+        ("POST", "/api/message") => {
+            let message: FrontendToBackendMessage = match serde_json::from_str(body) {
+                Ok(message) => message,
+                Err(e) => {
+                    return ApiResponse::error(400, &e.to_string());
+                }
+            };
+            if !is_remote_allowed(&message) {
+                return ApiResponse::error(403, "message not allowed from the dashboard");
+            }
+            match context.frontend_tx.try_send(message) {
+                Ok(()) => ApiResponse::json(202, "{}".to_string()),
+                Err(_) => ApiResponse::error(503, "backend is busy"),
+            }
+        }
+        _ => ApiResponse::error(404, "not found"),
+    }
+}
+
+/// Serves the dashboard until the listener fails to bind.
+pub async fn serve(config: DashboardConfig, context: DashboardContext) -> std::io::Result<()> {
+    let listener = TcpListener::bind(&config.address).await?;
+    log::info!("Dashboard listening on http://{}", config.address);
+    let config = Arc::new(config);
+    let context = Arc::new(context);
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                log::warn!("Dashboard connection failed: {}", e);
+                continue;
+            }
+        };
+        let config = config.clone();
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &config, &context).await {
+                log::warn!("Dashboard request failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Reads one HTTP/1.1 request off the stream and writes the response, closing the connection.
+async fn handle_connection(
+    stream: TcpStream,
+    config: &DashboardConfig,
+    context: &DashboardContext
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+    let mut content_length = 0;
+    let mut headers = RequestHeaders::default();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().unwrap_or(0);
+        } else if name.eq_ignore_ascii_case("authorization") {
+            headers.authorization = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("host") {
+            headers.host = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("origin") {
+            headers.origin = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("content-type") {
+            headers.content_type = Some(value.to_string());
+        }
+    }
+    let response = if content_length > MAX_BODY_SIZE {
+        ApiResponse::error(413, "request body too large")
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).await?;
+        handle_request(
+            &method,
+            &path,
+            &headers,
+            &String::from_utf8_lossy(&body),
+            config,
+            context
+        )
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason_phrase(response.status),
+        response.content_type,
+        response.body.len()
+    );
+    let mut stream = reader.into_inner();
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        413 => "Payload Too Large",
+        _ => "Service Unavailable",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::config::AppConfig;
+
+    fn config() -> DashboardConfig {
+        DashboardConfig { token: "secret".to_string(), ..DashboardConfig::default() }
+    }
+
+    fn context() -> DashboardContext {
+        let (frontend_tx, _) = tokio::sync::mpsc::channel(1);
+        DashboardContext {
+            frontend_tx,
+            tts_queue: Arc::new(Mutex::new(TTSQueue::new(10))),
+            // never saved by these tests
+            config: SharedConfig::new(
+                AppConfig::default(),
+                std::env::temp_dir().join("yambot-dashboard-config.toml")
+            ),
+        }
+    }
+
+    fn headers() -> RequestHeaders {
+        RequestHeaders {
+            authorization: Some("Bearer secret".to_string()),
+            host: Some("127.0.0.1:8080".to_string()),
+            origin: None,
+            content_type: None,
+        }
+    }
+
+    fn queue_status(headers: &RequestHeaders, config: &DashboardConfig) -> u16 {
+        handle_request("GET", "/api/queue", headers, "", config, &context()).status
+    }
+
+    #[test]
+    fn api_requires_the_token() {
+        assert_eq!(queue_status(&headers(), &config()), 200);
+        let wrong = RequestHeaders { authorization: Some("Bearer nope".to_string()), ..headers() };
+        assert_eq!(queue_status(&wrong, &config()), 401);
+        let missing = RequestHeaders { authorization: None, ..headers() };
+        assert_eq!(queue_status(&missing, &config()), 401);
+    }
+
+    #[test]
+    fn empty_token_keeps_the_api_closed() {
+        let config = DashboardConfig::default();
+        let headers = RequestHeaders { authorization: Some("Bearer ".to_string()), ..headers() };
+        assert_eq!(queue_status(&headers, &config), 401);
+    }
+
+    #[test]
+    fn foreign_hosts_and_origins_are_rejected() {
+        let localhost = RequestHeaders { host: Some("localhost:8080".to_string()), ..headers() };
+        assert_eq!(queue_status(&localhost, &config()), 200);
+        let rebound = RequestHeaders { host: Some("evil.example:8080".to_string()), ..headers() };
+        assert_eq!(queue_status(&rebound, &config()), 403);
+        let cross_site = RequestHeaders {
+            origin: Some("http://evil.example".to_string()),
+            ..headers()
+        };
+        assert_eq!(queue_status(&cross_site, &config()), 403);
+        let same_site = RequestHeaders {
+            origin: Some("http://127.0.0.1:8080".to_string()),
+            ..headers()
+        };
+        assert_eq!(queue_status(&same_site, &config()), 200);
+    }
+
+    #[test]
+    fn posts_must_be_json() {
+        let body = r#""SkipTTS""#;
+        let form = RequestHeaders {
+            content_type: Some("text/plain".to_string()),
+            ..headers()
+        };
+        let response = handle_request("POST", "/api/message", &form, body, &config(), &context());
+        assert_eq!(response.status, 403);
+        let json = RequestHeaders {
+            content_type: Some("application/json; charset=utf-8".to_string()),
+            ..headers()
+        };
+        let response = handle_request("POST", "/api/message", &json, body, &config(), &context());
+        assert_ne!(response.status, 403);
+    }
+
+    #[test]
+    fn generated_tokens_are_random() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, generate_token().unwrap());
+    }
+}
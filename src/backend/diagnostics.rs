@@ -0,0 +1,61 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// State of the current chat connections, kept for the diagnostics report.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStatus {
+    pub channels: Vec<String>,
+    pub scopes: Option<Vec<String>>,
+    pub last_error: Option<String>,
+}
+
+/// Everything needed to look into a bug report, meant to be pasted as is.
+#[derive(Debug, Clone)]
+pub struct DiagnosticsReport {
+    pub version: &'static str,
+    pub connected: bool,
+    pub connection: ConnectionStatus,
+    pub tts_queue_length: usize,
+    pub tts_queue_capacity: usize,
+    pub tts_queue_bytes: usize,
+    // 0 when the queue has no memory budget
+    pub tts_queue_budget: usize,
+    pub audio_device: Result<String, String>,
+    pub config_path: PathBuf,
+}
+
+impl fmt::Display for DiagnosticsReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Yambot {}", self.version)?;
+        let channels = self.connection.channels.join(", ");
+        let connection = match (channels.is_empty(), self.connected) {
+            (false, true) => format!("connected to {}", channels),
+            (false, false) => format!("connection to {} closed", channels),
+            (true, _) => "disconnected".to_string(),
+        };
+        writeln!(f, "Connection: {}", connection)?;
+        let scopes = match &self.connection.scopes {
+            Some(scopes) if scopes.is_empty() => "none".to_string(),
+            Some(scopes) => scopes.join(", "),
+            None => "not validated".to_string(),
+        };
+        writeln!(f, "Token scopes: {}", scopes)?;
+        writeln!(f, "Last error: {}", self.connection.last_error.as_deref().unwrap_or("none"))?;
+        writeln!(f, "TTS queue: {}/{}", self.tts_queue_length, self.tts_queue_capacity)?;
+        if self.tts_queue_budget > 0 {
+            writeln!(
+                f,
+                "TTS queue memory: {} KB of {} KB",
+                self.tts_queue_bytes / 1024,
+                self.tts_queue_budget / 1024
+            )?;
+        } else {
+            writeln!(f, "TTS queue memory: {} KB", self.tts_queue_bytes / 1024)?;
+        }
+        match &self.audio_device {
+            Ok(name) => writeln!(f, "Audio device: {}", name)?,
+            Err(e) => writeln!(f, "Audio device: unavailable ({})", e)?,
+        }
+        write!(f, "Config file: {}", self.config_path.display())
+    }
+}
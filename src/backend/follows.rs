@@ -0,0 +1,69 @@
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+use std::time::{ Duration, Instant };
+
+use super::helix::HelixFollow;
+
+/// Sound played for new followers. Chat runs over IRC, which has no follow events, so the
+/// follower list is polled through the Twitch API instead (needs `moderator:read:followers`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FollowsConfig {
+    pub enabled: bool,
+    pub sound: String,
+    // seconds after a follow sound before another one can play, so follow bots can't spam it
+    pub cooldown_secs: u64,
+    pub poll_interval_secs: u64,
+}
+
+impl Default for FollowsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sound: String::new(),
+            cooldown_secs: 30,
+            poll_interval_secs: 15,
+        }
+    }
+}
+
+/// Picks out follows newer than the ones already seen and rate-limits the follow sound.
+#[derive(Default)]
+pub struct FollowTracker {
+    // `None` until the first poll, whose follows all happened before the bot connected
+    last_followed_at: Option<DateTime<Utc>>,
+    last_played: Option<Instant>,
+}
+
+impl FollowTracker {
+    /// Returns the logins of follows not seen in earlier polls, oldest first.
+    pub fn new_follows(&mut self, follows: &[HelixFollow]) -> Vec<String> {
+        let newest = follows.iter().map(|follow| follow.followed_at).max();
+        let Some(last_followed_at) = self.last_followed_at else {
+            self.last_followed_at = Some(newest.unwrap_or_else(Utc::now));
+            return Vec::new();
+        };
+        let mut new_follows: Vec<&HelixFollow> = follows
+            .iter()
+            .filter(|follow| follow.followed_at > last_followed_at)
+            .collect();
+        new_follows.sort_by_key(|follow| follow.followed_at);
+        if let Some(newest) = newest {
+            self.last_followed_at = Some(newest.max(last_followed_at));
+        }
+        new_follows
+            .into_iter()
+            .map(|follow| follow.user_login.clone())
+            .collect()
+    }
+
+    /// Returns false while the previous follow sound is still on cooldown.
+    pub fn should_play(&mut self, cooldown: Duration) -> bool {
+        if let Some(last_played) = self.last_played {
+            if last_played.elapsed() < cooldown {
+                return false;
+            }
+        }
+        self.last_played = Some(Instant::now());
+        true
+    }
+}
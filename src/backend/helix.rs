@@ -0,0 +1,401 @@
+use chrono::{ DateTime, Utc };
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{ Duration, Instant };
+
+const HELIX_URL: &str = "https://api.twitch.tv/helix";
+const VALIDATE_URL: &str = "https://id.twitch.tv/oauth2/validate";
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(3);
+const USER_CACHE_TTL: Duration = Duration::from_secs(600);
+const UNKNOWN_VALUE: &str = "unknown";
+// how long a looked up stream start is reused, so a spammed !uptime doesn't hit the API each time
+const STREAM_CACHE_TTL: Duration = Duration::from_secs(30);
+
+// built-in chat command replying with how long the stream has been live
+pub const UPTIME_COMMAND: &str = "uptime";
+
+type HelixError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Deserialize)]
+struct ValidateResponse {
+    client_id: String,
+    login: String,
+    user_id: String,
+    #[serde(default)]
+    scopes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct HelixResponse<T> {
+    data: Vec<T>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HelixUser {
+    pub id: String,
+    pub login: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct HelixStream {
+    started_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct HelixChannel {
+    game_name: String,
+}
+
+#[derive(Deserialize)]
+struct HelixFollower {
+    followed_at: DateTime<Utc>,
+}
+
+/// A single entry of the channel's follower list.
+#[derive(Deserialize, Debug, Clone)]
+pub struct HelixFollow {
+    pub user_login: String,
+    pub followed_at: DateTime<Utc>,
+}
+
+/// Accent color of a chat announcement, `Primary` being the channel's own color.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AnnouncementColor {
+    #[default]
+    Primary,
+    Blue,
+    Green,
+    Orange,
+    Purple,
+}
+
+impl AnnouncementColor {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "primary" => Some(AnnouncementColor::Primary),
+            "blue" => Some(AnnouncementColor::Blue),
+            "green" => Some(AnnouncementColor::Green),
+            "orange" => Some(AnnouncementColor::Orange),
+            "purple" => Some(AnnouncementColor::Purple),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            AnnouncementColor::Primary => "primary",
+            AnnouncementColor::Blue => "blue",
+            AnnouncementColor::Green => "green",
+            AnnouncementColor::Orange => "orange",
+            AnnouncementColor::Purple => "purple",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct UserData {
+    created_at: Option<DateTime<Utc>>,
+    followed_at: Option<DateTime<Utc>>,
+}
+
+pub struct HelixClient {
+    http: reqwest::Client,
+    token: String,
+    client_id: String,
+    pub login: String,
+    pub user_id: String,
+    pub scopes: Vec<String>,
+    pub broadcaster_id: String,
+    user_cache: Mutex<HashMap<String, (Instant, UserData)>>,
+    // when the stream started, `None` inside when it was offline at the lookup
+    stream_cache: Mutex<Option<(Instant, Option<DateTime<Utc>>)>>,
+}
+
+impl HelixClient {
+    /// Validates the token and resolves the broadcaster's user id.
+    pub async fn connect(token: &str, channel_name: &str) -> Result<Self, HelixError> {
+        let http = reqwest::Client::new();
+        let token = token.trim_start_matches("oauth:").to_string();
+        let validated: ValidateResponse = http
+            .get(VALIDATE_URL)
+            .header("Authorization", format!("OAuth {}", token))
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+        let mut client = Self {
+            http,
+            token,
+            client_id: validated.client_id,
+            login: validated.login,
+            user_id: validated.user_id,
+            scopes: validated.scopes,
+            broadcaster_id: String::new(),
+            user_cache: Mutex::new(HashMap::new()),
+            stream_cache: Mutex::new(None),
+        };
+        client.broadcaster_id = client
+            .get_user(channel_name).await?
+            .ok_or_else(|| format!("Channel {} does not exist", channel_name))?.id;
+        Ok(client)
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)]
+    ) -> Result<Vec<T>, HelixError> {
+        let response: HelixResponse<T> = self.http
+            .get(format!("{}{}", HELIX_URL, path))
+            .query(query)
+            .bearer_auth(&self.token)
+            .header("Client-Id", &self.client_id)
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+        Ok(response.data)
+    }
+
+    async fn post(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        body: &serde_json::Value
+    ) -> Result<(), HelixError> {
+        self.http
+            .post(format!("{}{}", HELIX_URL, path))
+            .query(query)
+            .bearer_auth(&self.token)
+            .header("Client-Id", &self.client_id)
+            .json(body)
+            .send().await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Fails up front when the token lacks `scope`, rather than with a bare 403 from Twitch.
+    fn require_scope(&self, scope: &str) -> Result<(), HelixError> {
+        if self.scopes.iter().any(|granted| granted == scope) {
+            Ok(())
+        } else {
+            Err(format!("the auth token is missing the {} scope", scope).into())
+        }
+    }
+
+    pub async fn get_user(&self, login: &str) -> Result<Option<HelixUser>, HelixError> {
+        Ok(self.get::<HelixUser>("/users", &[("login", login)]).await?.into_iter().next())
+    }
+
+    /// How long the channel has been live, `None` when it's offline.
+    pub async fn get_stream_uptime(&self) -> Result<Option<chrono::Duration>, HelixError> {
+        let cached = *self.stream_cache.lock().unwrap();
+        let started_at = match cached {
+            Some((fetched_at, started_at)) if fetched_at.elapsed() < STREAM_CACHE_TTL => started_at,
+            _ => {
+                let streams = self.get::<HelixStream>(
+                    "/streams",
+                    &[("user_id", &self.broadcaster_id)]
+                ).await?;
+                let started_at = streams.into_iter().next().map(|stream| stream.started_at);
+                *self.stream_cache.lock().unwrap() = Some((Instant::now(), started_at));
+                started_at
+            }
+        };
+        Ok(started_at.map(|started_at| Utc::now() - started_at))
+    }
+
+    /// The game `user_id` last streamed, `None` when they never set one.
+    pub async fn get_channel_game(&self, user_id: &str) -> Result<Option<String>, HelixError> {
+        let channel = self.get::<HelixChannel>("/channels", &[("broadcaster_id", user_id)]).await?;
+        Ok(
+            channel
+                .into_iter()
+                .next()
+                .map(|channel| channel.game_name)
+                .filter(|game| !game.is_empty())
+        )
+    }
+
+    /// Gives `to_user_id` a Twitch shoutout, needs `moderator:manage:shoutouts`. Twitch limits
+    /// these to one every 2 minutes and one per user an hour, answering 429 otherwise.
+    pub async fn shoutout(&self, to_user_id: &str) -> Result<(), HelixError> {
+        self.require_scope("moderator:manage:shoutouts")?;
+        self.post(
+            "/chat/shoutouts",
+            &[
+                ("from_broadcaster_id", &self.broadcaster_id),
+                ("to_broadcaster_id", to_user_id),
+                ("moderator_id", &self.user_id),
+            ],
+            &serde_json::json!({})
+        ).await
+    }
+
+    async fn get_followed_at(&self, user_id: &str) -> Result<Option<DateTime<Utc>>, HelixError> {
+        let followers = self.get::<HelixFollower>(
+            "/channels/followers",
+            &[
+                ("broadcaster_id", &self.broadcaster_id),
+                ("user_id", user_id),
+            ]
+        ).await?;
+        Ok(followers.into_iter().next().map(|follower| follower.followed_at))
+    }
+
+    /// The channel's most recent follows, newest first.
+    pub async fn recent_follows(&self) -> Result<Vec<HelixFollow>, HelixError> {
+        self.get::<HelixFollow>(
+            "/channels/followers",
+            &[
+                ("broadcaster_id", &self.broadcaster_id),
+                ("first", "20"),
+            ]
+        ).await
+    }
+
+    /// Deletes a chat message, needs `moderator:manage:chat_messages` and the bot to be a mod.
+    pub async fn delete_message(&self, message_id: &str) -> Result<(), HelixError> {
+        self.require_scope("moderator:manage:chat_messages")?;
+        self.http
+            .delete(format!("{}/moderation/chat", HELIX_URL))
+            .query(
+                &[
+                    ("broadcaster_id", self.broadcaster_id.as_str()),
+                    ("moderator_id", self.user_id.as_str()),
+                    ("message_id", message_id),
+                ]
+            )
+            .bearer_auth(&self.token)
+            .header("Client-Id", &self.client_id)
+            .send().await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Times a user out, needs `moderator:manage:banned_users` and the bot to be a mod.
+    pub async fn timeout_user(
+        &self,
+        user_id: &str,
+        duration_secs: u32,
+        reason: &str
+    ) -> Result<(), HelixError> {
+        self.require_scope("moderator:manage:banned_users")?;
+        let body = serde_json::json!({
+            "data": { "user_id": user_id, "duration": duration_secs, "reason": reason },
+        });
+        self.post(
+            "/moderation/bans",
+            &[
+                ("broadcaster_id", &self.broadcaster_id),
+                ("moderator_id", &self.user_id),
+            ],
+            &body
+        ).await
+    }
+
+    /// Whispers `text` from the bot's account, needs `user:manage:whispers` and a verified phone
+    /// number on that account.
+    pub async fn send_whisper(&self, to_user_id: &str, text: &str) -> Result<(), HelixError> {
+        self.require_scope("user:manage:whispers")?;
+        self.post(
+            "/whispers",
+            &[
+                ("from_user_id", &self.user_id),
+                ("to_user_id", to_user_id),
+            ],
+            &serde_json::json!({ "message": text })
+        ).await
+    }
+
+    /// Posts a highlighted announcement in chat, needs `moderator:manage:announcements` and the
+    /// bot to be a mod.
+    pub async fn send_announcement(
+        &self,
+        text: &str,
+        color: AnnouncementColor
+    ) -> Result<(), HelixError> {
+        self.require_scope("moderator:manage:announcements")?;
+        self.post(
+            "/chat/announcements",
+            &[
+                ("broadcaster_id", &self.broadcaster_id),
+                ("moderator_id", &self.user_id),
+            ],
+            &serde_json::json!({ "message": text, "color": color.as_str() })
+        ).await
+    }
+
+    async fn user_data(&self, login: &str) -> UserData {
+        let login = login.to_lowercase();
+        if let Some((fetched_at, data)) = self.user_cache.lock().unwrap().get(&login) {
+            if fetched_at.elapsed() < USER_CACHE_TTL {
+                return data.clone();
+            }
+        }
+        let mut data = UserData::default();
+        if let Ok(Some(user)) = self.get_user(&login).await {
+            data.created_at = Some(user.created_at);
+            data.followed_at = self.get_followed_at(&user.id).await.ok().flatten();
+        }
+        self.user_cache.lock().unwrap().insert(login, (Instant::now(), data.clone()));
+        data
+    }
+}
+
+pub fn has_user_variables(template: &str) -> bool {
+    template.contains("{followage}") || template.contains("{accountage}")
+}
+
+/// Replaces `{followage}` and `{accountage}` with data looked up for `username`,
+/// falling back to "unknown" when Helix is unavailable or too slow.
+pub async fn resolve_user_variables(
+    helix: Option<&HelixClient>,
+    template: &str,
+    username: &str
+) -> String {
+    if !has_user_variables(template) {
+        return template.to_string();
+    }
+    let data = match helix {
+        Some(helix) =>
+            tokio::time::timeout(LOOKUP_TIMEOUT, helix.user_data(username)).await.unwrap_or_default(),
+        None => UserData::default(),
+    };
+    let now = Utc::now();
+    let format_since = |since: Option<DateTime<Utc>>| {
+        since.map_or_else(|| UNKNOWN_VALUE.to_string(), |since| humanize_duration(now - since))
+    };
+    template
+        .replace("{followage}", &format_since(data.followed_at))
+        .replace("{accountage}", &format_since(data.created_at))
+}
+
+/// Formats a stream's uptime as e.g. "2h 13m", leaving out the hours below one.
+pub fn format_uptime(uptime: chrono::Duration) -> String {
+    let minutes = uptime.num_minutes().max(0);
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+fn humanize_duration(duration: chrono::Duration) -> String {
+    let days = duration.num_days().max(0);
+    let parts = [
+        (days / 365, "year"),
+        ((days % 365) / 30, "month"),
+        ((days % 365) % 30, "day"),
+    ];
+    let formatted: Vec<String> = parts
+        .iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{} {}{}", value, unit, if *value == 1 { "" } else { "s" }))
+        .collect();
+    if formatted.is_empty() {
+        "less than a day".to_string()
+    } else {
+        formatted.join(", ")
+    }
+}
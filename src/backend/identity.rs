@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+use std::time::{ Duration, Instant };
+
+// how long a sent message is expected to come back from chat, when it comes back at all
+const SENT_ECHO_WINDOW: Duration = Duration::from_secs(30);
+
+/// The account the bot is logged in as. The bot never reacts to what it sent itself, but when it
+/// runs on the broadcaster's own account the broadcaster still has to be able to use commands,
+/// so only messages matching something the bot just sent are skipped.
+pub struct BotIdentity {
+    login: String,
+    user_id: Option<String>,
+    shared_account: bool,
+    recently_sent: VecDeque<(Instant, String)>,
+}
+
+impl BotIdentity {
+    /// `user_id` is unknown when the token could not be validated, the login is compared instead.
+    pub fn new(login: String, user_id: Option<String>, shared_account: bool) -> Self {
+        Self {
+            login,
+            user_id,
+            shared_account,
+            recently_sent: VecDeque::new(),
+        }
+    }
+
+    pub fn login(&self) -> &str {
+        &self.login
+    }
+
+    pub fn is_shared_account(&self) -> bool {
+        self.shared_account
+    }
+
+    pub fn record_sent(&mut self, text: &str) {
+        self.recently_sent.push_back((Instant::now(), text.trim().to_string()));
+    }
+
+    /// Returns true when the message was sent by the bot itself.
+    pub fn is_own_message(&mut self, user_id: &str, login: &str, text: &str) -> bool {
+        let from_bot_account = match &self.user_id {
+            Some(bot_user_id) => bot_user_id == user_id,
+            None => self.login.eq_ignore_ascii_case(login),
+        };
+        if !from_bot_account {
+            return false;
+        }
+        if !self.shared_account {
+            return true;
+        }
+        self.recently_sent.retain(|(sent_at, _)| sent_at.elapsed() < SENT_ECHO_WINDOW);
+        match self.recently_sent.iter().position(|(_, sent)| sent == text.trim()) {
+            Some(index) => {
+                self.recently_sent.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
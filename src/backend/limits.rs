@@ -0,0 +1,55 @@
+use serde::{ Deserialize, Serialize };
+
+/// What happens to chat messages longer than the configured maximum.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum LongMessageAction {
+    #[default]
+    Truncate,
+    Drop,
+}
+
+/// Returns the text the bot should process, or `None` when the message is dropped.
+/// A `max_length` of 0 disables the limit.
+pub fn limit_length(text: &str, max_length: usize, action: LongMessageAction) -> Option<String> {
+    if max_length == 0 || text.chars().count() <= max_length {
+        return Some(text.to_string());
+    }
+    match action {
+        LongMessageAction::Truncate => Some(text.chars().take(max_length).collect()),
+        LongMessageAction::Drop => None,
+    }
+}
+
+/// Like `limit_length`, but truncation cuts after the last whole word that fits and ends the
+/// text with an ellipsis, so TTS doesn't read half a word.
+pub fn limit_length_at_word(
+    text: &str,
+    max_length: usize,
+    action: LongMessageAction
+) -> Option<String> {
+    if max_length == 0 || text.chars().count() <= max_length {
+        return Some(text.to_string());
+    }
+    if action == LongMessageAction::Drop {
+        return None;
+    }
+    // room for the ellipsis
+    let budget = max_length - 1;
+    let mut truncated = String::new();
+    for word in text.split_whitespace() {
+        let separator = if truncated.is_empty() { 0 } else { 1 };
+        if truncated.chars().count() + separator + word.chars().count() > budget {
+            break;
+        }
+        if separator == 1 {
+            truncated.push(' ');
+        }
+        truncated.push_str(word);
+    }
+    if truncated.is_empty() {
+        // a single word longer than the limit is cut where it has to be
+        truncated = text.chars().take(budget).collect();
+    }
+    truncated.push('…');
+    Some(truncated)
+}
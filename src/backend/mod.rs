@@ -1 +1,25 @@
+pub mod audio;
+pub mod automod;
+pub mod commands;
 pub mod config;
+pub mod dashboard;
+pub mod diagnostics;
+pub mod follows;
+pub mod helix;
+pub mod identity;
+pub mod limits;
+pub mod raids;
+pub mod rate;
+pub mod reconnect;
+pub mod regulars;
+pub mod safe_mode;
+pub mod secrets;
+pub mod shoutout;
+pub mod sounds;
+pub mod spam;
+pub mod transcript;
+pub mod tts;
+pub mod version;
+pub mod volume;
+pub mod welcome;
+pub mod window;
@@ -0,0 +1,26 @@
+use serde::{ Deserialize, Serialize };
+
+/// What the bot does when the channel gets raided.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RaidsConfig {
+    pub enabled: bool,
+    // posted in chat, `{raider}` and `{viewers}` are filled in, nothing is posted when empty
+    pub message: String,
+    // played when raided, nothing is played when empty
+    pub sound: String,
+}
+
+impl Default for RaidsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            message: "Thanks for the raid {raider}! Welcome in, all {viewers} of you".to_string(),
+            sound: String::new(),
+        }
+    }
+}
+
+/// Fills in the raider's name and the number of viewers they brought.
+pub fn raid_message(template: &str, raider: &str, viewers: u64) -> String {
+    template.replace("{raider}", raider).replace("{viewers}", &viewers.to_string())
+}
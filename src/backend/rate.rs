@@ -0,0 +1,34 @@
+use std::collections::VecDeque;
+use std::time::{ Duration, Instant };
+
+/// Counts how many messages arrived within a sliding time window.
+pub struct MessageRateTracker {
+    timestamps: VecDeque<Instant>,
+    window: Duration,
+}
+
+impl MessageRateTracker {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            timestamps: VecDeque::new(),
+            window,
+        }
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Records a message and returns the number of messages in the current window.
+    pub fn record(&mut self) -> usize {
+        let now = Instant::now();
+        self.timestamps.push_back(now);
+        while let Some(oldest) = self.timestamps.front() {
+            if now.duration_since(*oldest) < self.window {
+                break;
+            }
+            self.timestamps.pop_front();
+        }
+        self.timestamps.len()
+    }
+}
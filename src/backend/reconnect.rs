@@ -0,0 +1,150 @@
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+// a connection that held this long counts as working, the next drop starts over at BASE_DELAY
+pub const STABLE_CONNECTION: Duration = Duration::from_secs(60);
+// Twitch pings every connection about every five minutes, a connection silent for longer than
+// this is dead even if the socket hasn't noticed yet
+pub const SILENCE_TIMEOUT: Duration = Duration::from_secs(6 * 60);
+
+/// Why a chat connection ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Disconnect {
+    // Twitch rejected the auth token, connecting again with it won't help
+    LoginFailed(String),
+    // the channel could not be joined, e.g. an invalid channel name
+    JoinFailed(String),
+    // Twitch is about to restart the server the bot is connected to
+    ReconnectRequested,
+    // nothing arrived for `SILENCE_TIMEOUT`
+    TimedOut,
+    // the client stopped delivering messages
+    Closed,
+}
+
+impl Disconnect {
+    /// Whether connecting again may work.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, Disconnect::LoginFailed(_) | Disconnect::JoinFailed(_))
+    }
+
+    pub fn reason(&self) -> String {
+        match self {
+            Disconnect::LoginFailed(notice) => format!("login failed, {}", notice),
+            Disconnect::JoinFailed(e) => format!("could not join, {}", e),
+            Disconnect::ReconnectRequested => "Twitch asked the bot to reconnect".to_string(),
+            Disconnect::TimedOut => {
+                format!("nothing received for {}s", SILENCE_TIMEOUT.as_secs())
+            }
+            Disconnect::Closed => "the connection closed".to_string(),
+        }
+    }
+}
+
+/// Whether a NOTICE from Twitch means the auth token was rejected.
+pub fn is_login_failure(notice: &str) -> bool {
+    ["Login authentication failed", "Improperly formatted auth", "Login unsuccessful"]
+        .iter()
+        .any(|failure| notice.contains(failure))
+}
+
+/// Waits for the next message from chat, giving up once nothing has arrived for `timeout`.
+pub async fn next_message<T>(
+    incoming: &mut UnboundedReceiver<T>,
+    timeout: Duration
+) -> Result<T, Disconnect> {
+    match tokio::time::timeout(timeout, incoming.recv()).await {
+        Ok(Some(message)) => Ok(message),
+        Ok(None) => Err(Disconnect::Closed),
+        Err(_) => Err(Disconnect::TimedOut),
+    }
+}
+
+/// The wait before reconnect attempt `attempt`, counting from 1: 1s, 2s, 4s and so on, up to a
+/// minute.
+pub fn delay(attempt: u32) -> Duration {
+    BASE_DELAY.checked_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .map_or(MAX_DELAY, |delay| delay.min(MAX_DELAY))
+}
+
+/// Whether reconnect attempt `attempt` may still be made, 0 `max_attempts` meaning no limit.
+pub fn should_retry(attempt: u32, max_attempts: u32) -> bool {
+    max_attempts == 0 || attempt <= max_attempts
+}
+
+/// The number of the reconnect attempt to make after `disconnect` ended a connection that was
+/// up for `connected_for`, `attempt` being the previous one, or None to stay disconnected.
+/// Twitch asking for a reconnect isn't a failure, so it is followed even with auto reconnect
+/// off or the attempts used up.
+pub fn next_attempt(
+    disconnect: &Disconnect,
+    connected_for: Duration,
+    attempt: u32,
+    auto_reconnect: bool,
+    max_attempts: u32
+) -> Option<u32> {
+    let attempt = if connected_for >= STABLE_CONNECTION { 1 } else { attempt.saturating_add(1) };
+    if *disconnect == Disconnect::ReconnectRequested {
+        return Some(attempt);
+    }
+    let retry = disconnect.is_retryable() && auto_reconnect && should_retry(attempt, max_attempts);
+    retry.then_some(attempt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_doubles_up_to_a_minute() {
+        assert_eq!(delay(1), Duration::from_secs(1));
+        assert_eq!(delay(2), Duration::from_secs(2));
+        assert_eq!(delay(4), Duration::from_secs(8));
+        assert_eq!(delay(7), MAX_DELAY);
+        assert_eq!(delay(u32::MAX), MAX_DELAY);
+    }
+
+    #[test]
+    fn login_failures_are_not_retried() {
+        assert!(is_login_failure("Login authentication failed"));
+        assert!(!is_login_failure("This room is now in slow mode."));
+        assert!(!Disconnect::LoginFailed("Login authentication failed".to_string()).is_retryable());
+        assert!(Disconnect::ReconnectRequested.is_retryable());
+        assert!(Disconnect::TimedOut.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn silent_connection_times_out() {
+        let (incoming_tx, mut incoming) = tokio::sync::mpsc::unbounded_channel::<u32>();
+        incoming_tx.send(1).unwrap();
+        let timeout = Duration::from_millis(20);
+        assert_eq!(next_message(&mut incoming, timeout).await, Ok(1));
+        assert_eq!(next_message(&mut incoming, timeout).await, Err(Disconnect::TimedOut));
+        drop(incoming_tx);
+        assert_eq!(next_message(&mut incoming, timeout).await, Err(Disconnect::Closed));
+    }
+
+    #[test]
+    fn reconnect_requests_are_always_followed() {
+        let requested = Disconnect::ReconnectRequested;
+        assert_eq!(next_attempt(&requested, Duration::ZERO, 0, false, 3), Some(1));
+        // attempts keep counting for the backoff but never run out
+        assert_eq!(next_attempt(&requested, Duration::ZERO, 3, true, 3), Some(4));
+        assert_eq!(next_attempt(&requested, STABLE_CONNECTION, 3, true, 3), Some(1));
+    }
+
+    #[test]
+    fn dropped_connections_follow_the_reconnect_settings() {
+        let timed_out = Disconnect::TimedOut;
+        assert_eq!(next_attempt(&timed_out, Duration::ZERO, 0, true, 3), Some(1));
+        assert_eq!(next_attempt(&timed_out, Duration::ZERO, 0, false, 3), None);
+        assert_eq!(next_attempt(&timed_out, Duration::ZERO, 3, true, 3), None);
+        assert_eq!(next_attempt(&timed_out, Duration::ZERO, 3, true, 0), Some(4));
+        // a connection that held starts the count over
+        assert_eq!(next_attempt(&timed_out, STABLE_CONNECTION, 3, true, 3), Some(1));
+        let login_failed = Disconnect::LoginFailed("Login unsuccessful".to_string());
+        assert_eq!(next_attempt(&login_failed, Duration::ZERO, 0, true, 0), None);
+    }
+}
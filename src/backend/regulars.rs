@@ -0,0 +1,57 @@
+use serde::{ Deserialize, Serialize };
+use std::collections::HashSet;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Regular {
+    pub username: String,
+    // SFX file to play, empty for none
+    #[serde(default)]
+    pub sound: String,
+    // spoken through TTS, `{user}` is replaced with the username; empty for none
+    #[serde(default)]
+    pub phrase: String,
+    #[serde(default = "default_language")]
+    pub language: String,
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RegularsConfig {
+    pub enabled: bool,
+    pub users: Vec<Regular>,
+}
+
+/// Greets each configured regular once per session, on their first message.
+#[derive(Default)]
+pub struct RegularGreeter {
+    greeted: HashSet<String>,
+}
+
+impl RegularGreeter {
+    pub fn greeting_for<'a>(
+        &mut self,
+        username: &str,
+        config: &'a RegularsConfig
+    ) -> Option<&'a Regular> {
+        if !config.enabled {
+            return None;
+        }
+        let regular = config.users
+            .iter()
+            .find(|regular| regular.username.eq_ignore_ascii_case(username))?;
+        if self.greeted.insert(username.to_lowercase()) {
+            Some(regular)
+        } else {
+            None
+        }
+    }
+}
+
+impl Regular {
+    pub fn phrase_text(&self) -> String {
+        self.phrase.replace("{user}", &self.username)
+    }
+}
@@ -0,0 +1,19 @@
+use super::commands::UserLevel;
+
+// chat command that turns safe mode on or off
+pub const SAFE_MODE_COMMAND: &str = "safemode";
+// lowest level allowed to toggle safe mode from chat
+pub const REQUIRED_LEVEL: UserLevel = UserLevel::Broadcaster;
+
+/// Parses the argument of the safe mode command, `None` when it isn't "on" or "off".
+pub fn parse_state(arg: &str) -> Option<bool> {
+    match arg.to_lowercase().as_str() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn label(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
@@ -0,0 +1,127 @@
+use base64::{ engine::general_purpose::STANDARD, Engine };
+use ring::aead::{ Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN };
+use ring::rand::{ SecureRandom, SystemRandom };
+use std::fs;
+use std::path::PathBuf;
+
+use super::config::app_dir;
+
+// marks a value as encrypted, anything else is treated as plaintext
+const ENCRYPTED_PREFIX: &str = "enc:";
+// random key generated on first use, never written to config.toml
+const KEY_FILE: &str = "token.key";
+const KEY_LEN: usize = 32;
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Encrypts `value` with the machine-local key, returning `enc:` followed by the base64 of the
+/// nonce and the ciphertext.
+pub fn encrypt(value: &str) -> Result<String, String> {
+    seal(&load_key()?, value)
+}
+
+/// Decrypts a value written by `encrypt`.
+pub fn decrypt(value: &str) -> Result<String, String> {
+    open(&load_key()?, value)
+}
+
+fn seal(key: &LessSafeKey, value: &str) -> Result<String, String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| "Could not generate a nonce".to_string())?;
+    let mut sealed = value.as_bytes().to_vec();
+    key
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut sealed)
+        .map_err(|_| "Could not encrypt the value".to_string())?;
+    let mut bytes = nonce.to_vec();
+    bytes.extend(sealed);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(bytes)))
+}
+
+fn open(key: &LessSafeKey, value: &str) -> Result<String, String> {
+    let encoded = value
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .ok_or_else(|| "The value isn't encrypted".to_string())?;
+    let mut bytes = STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Invalid encrypted value: {}", e))?;
+    if bytes.len() < NONCE_LEN {
+        return Err("Invalid encrypted value: too short".to_string());
+    }
+    let mut sealed = bytes.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&bytes).map_err(|_| "Invalid nonce".to_string())?;
+    let opened = key
+        .open_in_place(nonce, Aad::empty(), &mut sealed)
+        .map_err(|_| format!("Could not decrypt the value, {} may have changed", KEY_FILE))?;
+    String::from_utf8(opened.to_vec()).map_err(|e| format!("Invalid decrypted value: {}", e))
+}
+
+fn key_path() -> PathBuf {
+    app_dir().join(KEY_FILE)
+}
+
+// reads the key file, creating it with a random key the first time
+fn load_key() -> Result<LessSafeKey, String> {
+    let path = key_path();
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut bytes = vec![0u8; KEY_LEN];
+            SystemRandom::new()
+                .fill(&mut bytes)
+                .map_err(|_| "Could not generate a key".to_string())?;
+            fs::write(&path, &bytes).map_err(|e|
+                format!("Could not write {}: {}", path.display(), e)
+            )?;
+            bytes
+        }
+        Err(e) => {
+            return Err(format!("Could not read {}: {}", path.display(), e));
+        }
+    };
+    key_from_bytes(&bytes).ok_or_else(|| format!("{} is not a valid key", path.display()))
+}
+
+fn key_from_bytes(bytes: &[u8]) -> Option<LessSafeKey> {
+    UnboundKey::new(&CHACHA20_POLY1305, bytes).ok().map(LessSafeKey::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> LessSafeKey {
+        key_from_bytes(&[byte; KEY_LEN]).unwrap()
+    }
+
+    #[test]
+    fn encrypted_values_decrypt_to_the_original() {
+        let sealed = seal(&key(1), "oauth:abc123").unwrap();
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.contains("abc123"));
+        assert_eq!(open(&key(1), &sealed).unwrap(), "oauth:abc123");
+        // a fresh nonce every time
+        assert_ne!(seal(&key(1), "oauth:abc123").unwrap(), sealed);
+    }
+
+    #[test]
+    fn tampered_values_are_rejected() {
+        let sealed = seal(&key(1), "oauth:abc123").unwrap();
+        let mut bytes = STANDARD.decode(sealed.strip_prefix(ENCRYPTED_PREFIX).unwrap()).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let tampered = format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(bytes));
+        assert!(open(&key(1), &tampered).is_err());
+        assert!(open(&key(1), "enc:AAAA").is_err());
+        assert!(open(&key(1), "oauth:abc123").is_err());
+    }
+
+    #[test]
+    fn values_do_not_decrypt_with_another_key() {
+        let sealed = seal(&key(1), "oauth:abc123").unwrap();
+        assert!(open(&key(2), &sealed).is_err());
+    }
+}
@@ -0,0 +1,35 @@
+use serde::{ Deserialize, Serialize };
+
+use super::commands::UserLevel;
+
+// chat command that shouts out another streamer
+pub const SHOUTOUT_COMMAND: &str = "so";
+// lowest level allowed to give a shoutout
+pub const REQUIRED_LEVEL: UserLevel = UserLevel::Moderator;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShoutoutConfig {
+    // posted in chat with `{user}` and `{game}`, the game they last streamed, filled in
+    pub message: String,
+    // also give a Twitch shoutout, which Twitch allows every 2 minutes and once an hour per user
+    pub use_twitch_shoutout: bool,
+}
+
+impl Default for ShoutoutConfig {
+    fn default() -> Self {
+        Self {
+            message: "Go check out {user} at twitch.tv/{user}, last seen playing {game}".to_string(),
+            use_twitch_shoutout: true,
+        }
+    }
+}
+
+pub fn shoutout_message(template: &str, user: &str, game: &str) -> String {
+    template.replace("{user}", user).replace("{game}", game)
+}
+
+/// The login a shoutout is for, from the command's first argument with or without the `@`.
+pub fn parse_target(args: &[String]) -> Option<String> {
+    let target = args.first()?.trim_start_matches('@');
+    if target.is_empty() { None } else { Some(target.to_lowercase()) }
+}
@@ -0,0 +1,501 @@
+use rodio::{ Decoder, Source };
+use serde::{ Deserialize, Serialize };
+use std::collections::HashMap;
+use std::fs::{ self, File };
+use std::io::BufReader;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, LazyLock, Mutex };
+use std::time::SystemTime;
+
+use crate::ui::Config;
+
+use super::config::app_dir;
+
+// used when the SFX config doesn't name a directory
+pub const DEFAULT_SOUNDS_DIRECTORY: &str = "./assets/sounds/";
+// chat command that plays a random sound, picked according to `sound_weights`
+pub const DEFAULT_RANDOM_SOUND_COMMAND: &str = "random";
+// weight of sounds missing from `sound_weights`
+const DEFAULT_WEIGHT: f64 = 1.0;
+// per-sound settings, in the project root next to config.toml
+const OVERRIDES_FILE: &str = "sounds.toml";
+// formats rodio can decode with its default features, in the order extensionless names try them
+const SUPPORTED_EXTENSIONS: &[&str] = &["wav", "mp3", "ogg", "flac"];
+
+// peak amplitude normalized sounds are scaled to
+const TARGET_PEAK: f32 = 0.9;
+// keeps near-silent files from being blown up to full volume
+const MAX_GAIN: f32 = 4.0;
+
+static GAIN_CACHE: LazyLock<Mutex<GainCache>> = LazyLock::new(|| Mutex::new(GainCache::default()));
+// sounds.toml is looked up for every sound played, it's only parsed again after it changes
+static OVERRIDES: LazyLock<Mutex<OverridesCache>> = LazyLock::new(|| {
+    Mutex::new(OverridesCache::default())
+});
+
+/// The directory sounds are played from, as set in the SFX config.
+pub fn sounds_directory(config: &Config) -> PathBuf {
+    let directory = &config.sounds_dir;
+    if directory.trim().is_empty() {
+        PathBuf::from(DEFAULT_SOUNDS_DIRECTORY)
+    } else {
+        PathBuf::from(directory.trim())
+    }
+}
+
+/// Creates the sounds directory when it doesn't exist yet.
+pub fn ensure_sounds_directory(directory: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(directory)
+}
+
+/// Checks that every file in the sounds directory can be opened and decoded, without playing it.
+/// Returns the name of each file that failed along with the reason.
+pub fn validate_sounds(directory: &Path) -> Vec<(String, String)> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return vec![(directory.display().to_string(), e.to_string())];
+        }
+    };
+    let mut failures = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !path.is_file() || name.starts_with('.') || !has_supported_extension(&name) {
+            continue;
+        }
+        let result = File::open(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| Decoder::new(BufReader::new(file)).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            failures.push((name, e));
+        }
+    }
+    failures.sort();
+    failures
+}
+
+/// Settings of a single sound that take precedence over the SFX config.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SoundOverride {
+    // used instead of the SFX volume, role volumes still apply on top
+    #[serde(default)]
+    pub volume: Option<f64>,
+    // seconds before the sound can play again
+    #[serde(default)]
+    pub cooldown_secs: Option<u64>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Default for SoundOverride {
+    fn default() -> Self {
+        Self {
+            volume: None,
+            cooldown_secs: None,
+            enabled: true,
+        }
+    }
+}
+
+impl SoundOverride {
+    pub fn volume(&self, sfx_volume: f64) -> f64 {
+        self.volume.map_or(sfx_volume, |volume| volume.clamp(0.0, 1.0))
+    }
+}
+
+#[derive(Default)]
+struct OverridesCache {
+    // modification time of the file that was read, `None` when it was missing
+    modified: Option<SystemTime>,
+    overrides: Option<Arc<HashMap<String, SoundOverride>>>,
+}
+
+/// Reads sounds.toml, a table of sound file names to their overrides, e.g.
+/// `["airhorn.mp3"]` with `volume = 0.3`. A missing or broken file means no overrides.
+pub fn load_sound_overrides() -> Arc<HashMap<String, SoundOverride>> {
+    let path = app_dir().join(OVERRIDES_FILE);
+    let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+    let mut cache = OVERRIDES.lock().unwrap();
+    if let Some(overrides) = cache.overrides.as_ref().filter(|_| cache.modified == modified) {
+        return overrides.clone();
+    }
+    let overrides = Arc::new(read_sound_overrides(&path));
+    cache.modified = modified;
+    cache.overrides = Some(overrides.clone());
+    overrides
+}
+
+fn read_sound_overrides(path: &Path) -> HashMap<String, SoundOverride> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    toml::from_str(&content).unwrap_or_else(|e| {
+        log::warn!("Ignoring {}: {}", path.display(), e);
+        HashMap::new()
+    })
+}
+
+/// The override of `sound`, the defaults when it has none.
+pub fn sound_override(sound: &str) -> SoundOverride {
+    load_sound_overrides().get(sound).cloned().unwrap_or_default()
+}
+
+/// Names of the files in the sounds directory, sorted.
+pub fn list_sounds(directory: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    let mut sounds: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.') && has_supported_extension(name))
+        .collect();
+    sounds.sort();
+    sounds
+}
+
+/// Whether `name` ends in one of the extensions sounds can have, ignoring case.
+pub fn has_supported_extension(name: &str) -> bool {
+    name.rsplit_once('.').is_some_and(|(_, extension)| {
+        SUPPORTED_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+    })
+}
+
+/// The file in `sounds_directory` that `name` refers to. Names with an extension are taken as
+/// they are, others get each supported extension tried in turn, so "airhorn" finds
+/// "airhorn.ogg" or "airhorn.flac".
+pub fn resolve_sound_file(name: &str, sounds_directory: &Path) -> Option<String> {
+    if has_supported_extension(name) {
+        return sounds_directory.join(name).is_file().then(|| name.to_string());
+    }
+    SUPPORTED_EXTENSIONS.iter()
+        .map(|extension| format!("{}.{}", name, extension))
+        .find(|file_name| sounds_directory.join(file_name).is_file())
+}
+
+/// A sound with what the SFX panel sorts it by.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundInfo {
+    pub name: String,
+    // seconds since the epoch the file was last modified, 0 when unknown
+    pub added: u64,
+    // times played this session
+    pub plays: usize,
+}
+
+/// How the SFX panel orders the sounds.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum SoundSort {
+    #[default]
+    Name,
+    MostPlayed,
+    RecentlyAdded,
+}
+
+impl SoundSort {
+    pub const ALL: [SoundSort; 3] = [
+        SoundSort::Name,
+        SoundSort::MostPlayed,
+        SoundSort::RecentlyAdded,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SoundSort::Name => "Name",
+            SoundSort::MostPlayed => "Most played",
+            SoundSort::RecentlyAdded => "Recently added",
+        }
+    }
+}
+
+/// `list_sounds` with the file times and the play counts in `plays`.
+pub fn list_sound_info(directory: &Path, plays: &HashMap<String, usize>) -> Vec<SoundInfo> {
+    list_sounds(directory)
+        .into_iter()
+        .map(|name| {
+            let added = fs
+                ::metadata(directory.join(&name))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map_or(0, |since_epoch| since_epoch.as_secs());
+            SoundInfo {
+                plays: plays.get(&name).copied().unwrap_or(0),
+                name,
+                added,
+            }
+        })
+        .collect()
+}
+
+/// The sounds whose name contains `query`, ignoring case, in `sort` order. Ties are broken by
+/// name so the list doesn't jump around.
+pub fn sort_and_filter<'a>(
+    sounds: &'a [SoundInfo],
+    query: &str,
+    sort: SoundSort
+) -> Vec<&'a SoundInfo> {
+    let query = query.trim().to_lowercase();
+    let mut matching: Vec<&SoundInfo> = sounds
+        .iter()
+        .filter(|sound| sound.name.to_lowercase().contains(&query))
+        .collect();
+    matching.sort_by(|a, b| {
+        let order = match sort {
+            SoundSort::Name => std::cmp::Ordering::Equal,
+            SoundSort::MostPlayed => b.plays.cmp(&a.plays),
+            SoundSort::RecentlyAdded => b.added.cmp(&a.added),
+        };
+        order.then_with(|| a.name.cmp(&b.name))
+    });
+    matching
+}
+
+/// Turns a file name into one that is safe to play from chat: lowercase, with anything other
+/// than letters, digits, dashes and underscores replaced by underscores.
+pub fn sanitize_sound_name(file_name: &str) -> Option<String> {
+    let path = Path::new(file_name);
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let stem: String = path
+        .file_stem()?
+        .to_string_lossy()
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() || character == '-' {
+                character.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let stem = stem.trim_matches('_');
+    if stem.is_empty() {
+        return None;
+    }
+    Some(format!("{}.{}", stem, extension))
+}
+
+/// Copies a sound file into `sounds_directory` under a sanitized name, after checking that it
+/// decodes. Returns the name it was saved as.
+pub fn import_sound(source: &Path, sounds_directory: &Path) -> Result<String, String> {
+    let file_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("{} is not a file", source.display()))?;
+    let name = sanitize_sound_name(&file_name).ok_or_else(||
+        format!("{} has no usable name", file_name)
+    )?;
+    if !has_supported_extension(&name) {
+        return Err(
+            format!(
+                "{} is not a supported format, use one of: {}",
+                file_name,
+                SUPPORTED_EXTENSIONS.join(", ")
+            )
+        );
+    }
+    let file = File::open(source).map_err(|e| format!("Could not open {}: {}", file_name, e))?;
+    Decoder::new(BufReader::new(file)).map_err(|e|
+        format!("{} could not be decoded: {}", file_name, e)
+    )?;
+    let destination = sounds_directory.join(&name);
+    if destination.exists() {
+        return Err(format!("A sound named {} already exists", name));
+    }
+    fs::copy(source, &destination).map_err(|e| format!("Could not copy {}: {}", file_name, e))?;
+    Ok(name)
+}
+
+/// The sounds the random sound command can pick from, leaving out the `ignored` ones and those
+/// disabled in `sounds.toml`.
+pub fn random_sound_candidates(sounds: Vec<String>, ignored: &[String]) -> Vec<String> {
+    let overrides = load_sound_overrides();
+    sounds
+        .into_iter()
+        .filter(|sound| !ignored.iter().any(|ignored| ignored.eq_ignore_ascii_case(sound)))
+        .filter(|sound| overrides.get(sound).is_none_or(|sound_override| sound_override.enabled))
+        .collect()
+}
+
+/// Picks one of `sounds` with a probability proportional to its weight in `weights`, sounds
+/// without one weighing `DEFAULT_WEIGHT`. `roll` is a random number in `0.0..1.0`.
+pub fn weighted_choice<'a>(
+    sounds: &'a [String],
+    weights: &HashMap<String, f64>,
+    roll: f64
+) -> Option<&'a str> {
+    let weight_of = |sound: &String| {
+        let weight = weights.get(sound).copied().unwrap_or(DEFAULT_WEIGHT);
+        if weight.is_finite() { weight.max(0.0) } else { 0.0 }
+    };
+    let total: f64 = sounds.iter().map(weight_of).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut target = roll * total;
+    for sound in sounds {
+        let weight = weight_of(sound);
+        if target < weight {
+            return Some(sound);
+        }
+        target -= weight;
+    }
+    // rounding can leave the target just past the last weighted sound
+    sounds.iter().rev().find(|sound| weight_of(sound) > 0.0).map(|sound| sound.as_str())
+}
+
+/// Gain that brings the loudest sample to `TARGET_PEAK`.
+pub fn peak_gain<I: IntoIterator<Item = f32>>(samples: I) -> f32 {
+    let peak = samples.into_iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    if peak <= f32::EPSILON {
+        return 1.0;
+    }
+    (TARGET_PEAK / peak).min(MAX_GAIN)
+}
+
+/// Normalization gains of analyzed sounds, keyed by path and invalidated when the file's
+/// modification time changes.
+#[derive(Default)]
+pub struct GainCache {
+    gains: HashMap<PathBuf, (SystemTime, f32)>,
+}
+
+impl GainCache {
+    pub fn gain(&mut self, path: &Path) -> f32 {
+        let Ok(modified) = fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+            return 1.0;
+        };
+        if let Some((analyzed_at, gain)) = self.gains.get(path) {
+            if *analyzed_at == modified {
+                return *gain;
+            }
+        }
+        let gain = match File::open(path).map(|file| Decoder::new(BufReader::new(file))) {
+            Ok(Ok(decoder)) => peak_gain(decoder.convert_samples::<f32>()),
+            _ => 1.0,
+        };
+        self.gains.insert(path.to_path_buf(), (modified, gain));
+        gain
+    }
+}
+
+/// Gain to apply to the sound at `path` so sounds play at a similar loudness, analyzed on first
+/// play.
+pub fn normalization_gain(path: &Path) -> f32 {
+    GAIN_CACHE.lock().unwrap().gain(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh directory per test, so tests never touch the real sounds directory
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env
+            ::temp_dir()
+            .join(format!("yambot-sounds-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names(sounds: &[&str]) -> Vec<String> {
+        sounds
+            .iter()
+            .map(|sound| sound.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn weighted_choice_follows_the_weights() {
+        let sounds = names(&["a.mp3", "b.mp3", "c.mp3"]);
+        let weights = HashMap::from([
+            ("a.mp3".to_string(), 3.0),
+            ("b.mp3".to_string(), 0.0),
+        ]);
+        // a takes the first three quarters of the rolls, c weighs the default 1.0
+        assert_eq!(weighted_choice(&sounds, &weights, 0.0), Some("a.mp3"));
+        assert_eq!(weighted_choice(&sounds, &weights, 0.74), Some("a.mp3"));
+        assert_eq!(weighted_choice(&sounds, &weights, 0.75), Some("c.mp3"));
+        assert_eq!(weighted_choice(&sounds, &weights, 0.999_999), Some("c.mp3"));
+    }
+
+    #[test]
+    fn weighted_choice_skips_unusable_weights() {
+        let sounds = names(&["a.mp3", "b.mp3"]);
+        let weights = HashMap::from([
+            ("a.mp3".to_string(), f64::NAN),
+            ("b.mp3".to_string(), -1.0),
+        ]);
+        assert_eq!(weighted_choice(&sounds, &weights, 0.5), None);
+        assert_eq!(weighted_choice(&[], &HashMap::new(), 0.5), None);
+    }
+
+    #[test]
+    fn peak_gain_brings_the_peak_to_the_target() {
+        assert_eq!(peak_gain([0.1, -0.45, 0.2]), TARGET_PEAK / 0.45);
+        assert_eq!(peak_gain([0.9]), 1.0);
+        // near silence is capped instead of blown up
+        assert_eq!(peak_gain([0.01, -0.01]), MAX_GAIN);
+        assert_eq!(peak_gain([0.0; 4]), 1.0);
+        assert_eq!(peak_gain(std::iter::empty()), 1.0);
+    }
+
+    fn sound(name: &str, added: u64, plays: usize) -> SoundInfo {
+        SoundInfo { name: name.to_string(), added, plays }
+    }
+
+    fn sorted<'a>(sounds: &'a [SoundInfo], query: &str, sort: SoundSort) -> Vec<&'a str> {
+        sort_and_filter(sounds, query, sort)
+            .iter()
+            .map(|sound| sound.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn sounds_are_filtered_and_sorted_with_ties_by_name() {
+        let sounds = [
+            sound("drum.wav", 30, 1),
+            sound("Airhorn.mp3", 10, 5),
+            sound("bell.ogg", 30, 5),
+        ];
+        assert_eq!(sorted(&sounds, "", SoundSort::Name), ["Airhorn.mp3", "bell.ogg", "drum.wav"]);
+        assert_eq!(
+            sorted(&sounds, "", SoundSort::MostPlayed),
+            ["Airhorn.mp3", "bell.ogg", "drum.wav"]
+        );
+        assert_eq!(
+            sorted(&sounds, "", SoundSort::RecentlyAdded),
+            ["bell.ogg", "drum.wav", "Airhorn.mp3"]
+        );
+        assert_eq!(sorted(&sounds, " AIR ", SoundSort::Name), ["Airhorn.mp3"]);
+        assert!(sorted(&sounds, "missing", SoundSort::Name).is_empty());
+    }
+
+    #[test]
+    fn sanitized_names_are_safe_to_type_in_chat() {
+        assert_eq!(sanitize_sound_name("Air Horn!.MP3").as_deref(), Some("air_horn.mp3"));
+        assert_eq!(sanitize_sound_name("boom-2.wav").as_deref(), Some("boom-2.wav"));
+        assert_eq!(sanitize_sound_name("!!!.wav"), None);
+        assert_eq!(sanitize_sound_name("no_extension"), None);
+    }
+
+    #[test]
+    fn importing_a_non_audio_file_leaves_nothing_behind() {
+        let source_dir = test_dir("import-source");
+        let sounds_dir = test_dir("import-sounds");
+        let fake = source_dir.join("notes.mp3");
+        fs::write(&fake, "not a sound").unwrap();
+        assert!(import_sound(&fake, &sounds_dir).is_err());
+        let text = source_dir.join("notes.txt");
+        fs::write(&text, "not a sound").unwrap();
+        assert!(import_sound(&text, &sounds_dir).is_err());
+        assert_eq!(fs::read_dir(&sounds_dir).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{ Deserialize, Serialize };
+use std::collections::HashMap;
+use std::time::{ Duration, Instant };
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpamConfig {
+    pub enabled: bool,
+    // messages shorter than this (after normalization) are never tracked
+    pub min_length: usize,
+    // how many different users may send the same phrase within the window before it is suppressed
+    pub max_repeats: usize,
+    pub window_secs: u64,
+    pub suppression_secs: u64,
+}
+
+impl Default for SpamConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_length: 40,
+            max_repeats: 3,
+            window_secs: 30,
+            suppression_secs: 300,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SpamCheck {
+    Allowed,
+    // `newly` is true only for the message that tipped the phrase over the threshold
+    Suppressed {
+        newly: bool,
+    },
+}
+
+#[derive(Default)]
+struct PhraseEntry {
+    senders: HashMap<String, Instant>,
+    suppressed_until: Option<Instant>,
+}
+
+/// Detects the same long message being pasted by several users in a short time (copypasta raids).
+#[derive(Default)]
+pub struct SpamDetector {
+    phrases: HashMap<String, PhraseEntry>,
+}
+
+impl SpamDetector {
+    pub fn check(&mut self, username: &str, text: &str, config: &SpamConfig) -> SpamCheck {
+        if !config.enabled {
+            return SpamCheck::Allowed;
+        }
+        let phrase = normalize(text);
+        if phrase.chars().count() < config.min_length {
+            return SpamCheck::Allowed;
+        }
+
+        let now = Instant::now();
+        let window = Duration::from_secs(config.window_secs);
+        self.phrases.retain(|_, entry| {
+            entry.senders.retain(|_, sent_at| now.duration_since(*sent_at) < window);
+            let suppressed = entry.suppressed_until.is_some_and(|until| until > now);
+            if !suppressed {
+                entry.suppressed_until = None;
+            }
+            suppressed || !entry.senders.is_empty()
+        });
+
+        let entry = self.phrases.entry(phrase).or_default();
+        if entry.suppressed_until.is_some() {
+            return SpamCheck::Suppressed { newly: false };
+        }
+        entry.senders.insert(username.to_lowercase(), now);
+        if entry.senders.len() > config.max_repeats {
+            entry.suppressed_until = Some(now + Duration::from_secs(config.suppression_secs));
+            return SpamCheck::Suppressed { newly: true };
+        }
+        SpamCheck::Allowed
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
@@ -0,0 +1,100 @@
+use serde::{ Deserialize, Serialize };
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum TranscriptFormat {
+    Json,
+    Csv,
+}
+
+impl TranscriptFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Json => "json",
+            TranscriptFormat::Csv => "csv",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub enum TranscriptEventKind {
+    Chat,
+    Command,
+    Tts,
+    Sound,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TranscriptEvent {
+    pub timestamp: String,
+    pub kind: TranscriptEventKind,
+    pub username: String,
+    pub text: String,
+}
+
+/// Everything that happened during the current session, in order.
+#[derive(Default)]
+pub struct Transcript {
+    events: Vec<TranscriptEvent>,
+}
+
+impl Transcript {
+    pub fn record(&mut self, kind: TranscriptEventKind, username: &str, text: &str) {
+        self.events.push(TranscriptEvent {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            kind,
+            username: username.to_string(),
+            text: text.to_string(),
+        });
+    }
+
+    /// How many times each sound was played this session.
+    pub fn sound_plays(&self) -> HashMap<String, usize> {
+        let mut plays = HashMap::new();
+        for event in &self.events {
+            if let TranscriptEventKind::Sound = event.kind {
+                *plays.entry(event.text.clone()).or_insert(0) += 1;
+            }
+        }
+        plays
+    }
+
+    pub fn export<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: TranscriptFormat
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let content = match format {
+            TranscriptFormat::Json => serde_json::to_string_pretty(&self.events)?,
+            TranscriptFormat::Csv => self.to_csv(),
+        };
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("timestamp,kind,username,text\n");
+        for event in &self.events {
+            csv.push_str(
+                &format!(
+                    "{},{:?},{},{}\n",
+                    escape_csv_field(&event.timestamp),
+                    event.kind,
+                    escape_csv_field(&event.username),
+                    escape_csv_field(&event.text)
+                )
+            );
+        }
+        csv
+    }
+}
+
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
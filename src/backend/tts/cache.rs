@@ -0,0 +1,85 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{ HashMap, VecDeque };
+use std::hash::{ Hash, Hasher };
+
+pub fn cache_key(text: &str, language: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    language.hash(&mut hasher);
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Synthesized audio kept in memory by text and language, evicting the least recently used
+/// entries once it grows past `max_size` bytes.
+#[derive(Default)]
+pub struct AudioCache {
+    entries: HashMap<u64, Vec<u8>>,
+    // least recently used first
+    order: VecDeque<u64>,
+    size: usize,
+    max_size: usize,
+}
+
+impl AudioCache {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            ..Self::default()
+        }
+    }
+
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        self.evict();
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<Vec<u8>> {
+        let audio_data = self.entries.get(&key)?.clone();
+        self.touch(key);
+        Some(audio_data)
+    }
+
+    pub fn insert(&mut self, key: u64, audio_data: Vec<u8>) {
+        if audio_data.len() > self.max_size {
+            return;
+        }
+        self.remove(key);
+        self.size += audio_data.len();
+        self.entries.insert(key, audio_data);
+        self.order.push_back(key);
+        self.evict();
+    }
+
+    pub fn remove(&mut self, key: u64) {
+        if let Some(audio_data) = self.entries.remove(&key) {
+            self.size -= audio_data.len();
+            self.order.retain(|existing| *existing != key);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.size = 0;
+    }
+
+    fn touch(&mut self, key: u64) {
+        self.order.retain(|existing| *existing != key);
+        self.order.push_back(key);
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            let Some(key) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(audio_data) = self.entries.remove(&key) {
+                self.size -= audio_data.len();
+            }
+        }
+    }
+}
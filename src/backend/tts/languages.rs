@@ -0,0 +1,205 @@
+use crate::ui::TTSConfig;
+
+const TOGGLE_USAGE: &str = "Usage: !tts lang <code> on|off";
+
+/// Languages supported by the Google Translate TTS endpoint, as (code, name) pairs.
+pub const LANGUAGES: &[(&str, &str)] = &[
+    ("af", "Afrikaans"),
+    ("ar", "Arabic"),
+    ("bg", "Bulgarian"),
+    ("bn", "Bengali"),
+    ("bs", "Bosnian"),
+    ("ca", "Catalan"),
+    ("cs", "Czech"),
+    ("cy", "Welsh"),
+    ("da", "Danish"),
+    ("de", "German"),
+    ("el", "Greek"),
+    ("en", "English"),
+    ("es", "Spanish"),
+    ("et", "Estonian"),
+    ("fi", "Finnish"),
+    ("fr", "French"),
+    ("gu", "Gujarati"),
+    ("hi", "Hindi"),
+    ("hr", "Croatian"),
+    ("hu", "Hungarian"),
+    ("id", "Indonesian"),
+    ("is", "Icelandic"),
+    ("it", "Italian"),
+    ("iw", "Hebrew"),
+    ("ja", "Japanese"),
+    ("jw", "Javanese"),
+    ("km", "Khmer"),
+    ("kn", "Kannada"),
+    ("ko", "Korean"),
+    ("la", "Latin"),
+    ("lv", "Latvian"),
+    ("ml", "Malayalam"),
+    ("mr", "Marathi"),
+    ("ms", "Malay"),
+    ("my", "Myanmar (Burmese)"),
+    ("ne", "Nepali"),
+    ("nl", "Dutch"),
+    ("no", "Norwegian"),
+    ("pl", "Polish"),
+    ("pt", "Portuguese"),
+    ("ro", "Romanian"),
+    ("ru", "Russian"),
+    ("si", "Sinhala"),
+    ("sk", "Slovak"),
+    ("sq", "Albanian"),
+    ("sr", "Serbian"),
+    ("su", "Sundanese"),
+    ("sv", "Swedish"),
+    ("sw", "Swahili"),
+    ("ta", "Tamil"),
+    ("te", "Telugu"),
+    ("th", "Thai"),
+    ("tl", "Filipino"),
+    ("tr", "Turkish"),
+    ("uk", "Ukrainian"),
+    ("ur", "Urdu"),
+    ("vi", "Vietnamese"),
+    ("zh-CN", "Chinese (Simplified)"),
+    ("zh-TW", "Chinese (Traditional)"),
+];
+
+const DEFAULT_SAMPLE_PHRASE: &str = "This is a test of the text to speech voice.";
+
+/// Sentences used to test a language, as (code, phrase) pairs. Languages without one
+/// fall back to `DEFAULT_SAMPLE_PHRASE`.
+const SAMPLE_PHRASES: &[(&str, &str)] = &[
+    ("cs", "Toto je test hlasu pro převod textu na řeč."),
+    ("de", "Dies ist ein Test der Sprachausgabe."),
+    ("en", DEFAULT_SAMPLE_PHRASE),
+    ("es", "Esta es una prueba de la voz de texto a voz."),
+    ("fr", "Ceci est un test de la synthèse vocale."),
+    ("it", "Questo è un test della sintesi vocale."),
+    ("ja", "これは音声合成のテストです。"),
+    ("ko", "이것은 음성 합성 테스트입니다."),
+    ("nl", "Dit is een test van de tekst-naar-spraakstem."),
+    ("pl", "To jest test syntezatora mowy."),
+    ("pt", "Este é um teste da voz de texto para fala."),
+    ("ru", "Это проверка синтеза речи."),
+    ("sv", "Det här är ett test av talsyntesen."),
+    ("tr", "Bu, metin okuma sesinin bir testidir."),
+    ("uk", "Це перевірка синтезу мовлення."),
+    ("zh-CN", "这是语音合成的测试。"),
+];
+
+pub fn sample_phrase(code: &str) -> &'static str {
+    SAMPLE_PHRASES.iter()
+        .find(|(language_code, _)| language_code.eq_ignore_ascii_case(code))
+        .map_or(DEFAULT_SAMPLE_PHRASE, |(_, phrase)| *phrase)
+}
+
+pub fn is_supported(code: &str) -> bool {
+    LANGUAGES.iter().any(|(language_code, _)| *language_code == code)
+}
+
+/// The canonical spelling of `code`, matched case-insensitively.
+pub fn canonical_code(code: &str) -> Option<&'static str> {
+    LANGUAGES.iter()
+        .find(|(language_code, _)| language_code.eq_ignore_ascii_case(code))
+        .map(|(language_code, _)| *language_code)
+}
+
+pub fn is_enabled(config: &TTSConfig, code: &str) -> bool {
+    !config.disabled_languages.iter().any(|disabled| disabled.eq_ignore_ascii_case(code))
+}
+
+pub fn set_enabled(config: &mut TTSConfig, code: &str, enabled: bool) {
+    config.disabled_languages.retain(|disabled| !disabled.eq_ignore_ascii_case(code));
+    if !enabled {
+        config.disabled_languages.push(code.to_string());
+    }
+}
+
+/// Disables every language except `codes`.
+pub fn enable_only(config: &mut TTSConfig, codes: &[&str]) {
+    config.disabled_languages = LANGUAGES.iter()
+        .map(|(code, _)| *code)
+        .filter(|code| !codes.contains(code))
+        .map(str::to_string)
+        .collect();
+}
+
+/// The languages whose name or code contains `search`, ignoring case.
+pub fn search(search: &str) -> Vec<(&'static str, &'static str)> {
+    let search = search.trim().to_lowercase();
+    LANGUAGES.iter()
+        .filter(|(code, name)| {
+            code.to_lowercase().contains(&search) || name.to_lowercase().contains(&search)
+        })
+        .copied()
+        .collect()
+}
+
+/// Parses the arguments of `!tts lang <code> on|off`, returning the language code and its new
+/// state, or the reply to send when they are invalid.
+pub fn parse_toggle(args: &[String]) -> Result<(&'static str, bool), String> {
+    let [subcommand, code, state] = args else {
+        return Err(TOGGLE_USAGE.to_string());
+    };
+    if subcommand != "lang" {
+        return Err(TOGGLE_USAGE.to_string());
+    }
+    let Some(code) = canonical_code(code) else {
+        return Err(format!("Unknown TTS language: {}", code));
+    };
+    match state.to_lowercase().as_str() {
+        "on" => Ok((code, true)),
+        "off" => Ok((code, false)),
+        _ => Err(TOGGLE_USAGE.to_string()),
+    }
+}
+
+/// Guesses the language of `text` from the script most of its letters are written in.
+/// Latin text, text mixing scripts and anything else ambiguous falls back to `default`.
+pub fn detect_language(text: &str, default: &str) -> String {
+    let mut counts: Vec<(&'static str, usize)> = Vec::new();
+    let mut letters = 0;
+    let mut has_kana = false;
+    for character in text.chars().filter(|character| character.is_alphabetic()) {
+        letters += 1;
+        let code = match character {
+            '\u{0400}'..='\u{04FF}' => "ru",
+            '\u{0370}'..='\u{03FF}' => "el",
+            '\u{0590}'..='\u{05FF}' => "iw",
+            '\u{0600}'..='\u{06FF}' => "ar",
+            '\u{0900}'..='\u{097F}' => "hi",
+            '\u{0E00}'..='\u{0E7F}' => "th",
+            '\u{3040}'..='\u{30FF}' => {
+                has_kana = true;
+                "ja"
+            }
+            '\u{AC00}'..='\u{D7AF}' | '\u{1100}'..='\u{11FF}' => "ko",
+            '\u{4E00}'..='\u{9FFF}' => "zh-CN",
+            _ => {
+                continue;
+            }
+        };
+        match counts.iter_mut().find(|(counted, _)| *counted == code) {
+            Some((_, count)) => {
+                *count += 1;
+            }
+            None => counts.push((code, 1)),
+        }
+    }
+    // Japanese mixes kanji in with kana, which would otherwise count as Chinese
+    if has_kana {
+        let han = counts
+            .iter()
+            .find(|(code, _)| *code == "zh-CN")
+            .map_or(0, |(_, count)| *count);
+        counts.retain(|(code, _)| *code != "zh-CN");
+        if let Some((_, count)) = counts.iter_mut().find(|(code, _)| *code == "ja") {
+            *count += han;
+        }
+    }
+    match counts.into_iter().max_by_key(|(_, count)| *count) {
+        Some((code, count)) if count * 2 > letters && is_supported(code) => code.to_string(),
+        _ => default.to_string(),
+    }
+}
@@ -0,0 +1,959 @@
+use serde::{ Deserialize, Serialize };
+use std::collections::{ HashMap, VecDeque };
+use std::fs;
+use std::sync::{ Arc, Mutex };
+use std::time::{ Duration, Instant };
+
+use crate::ui::TTSConfig;
+
+use self::cache::AudioCache;
+use self::provider::{ TTSProviderKind, TtsProvider };
+
+pub mod cache;
+pub mod languages;
+pub mod moderation;
+pub mod provider;
+pub mod queue;
+
+const MAX_TEXT_LENGTH: usize = 200;
+const USER_AGENT: &str = concat!("yambot/", env!("CARGO_PKG_VERSION"));
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// longest wait between two synthesis attempts, however many retries are configured
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Where a TTS request came from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum TTSSource {
+    #[default]
+    Chat,
+    Redemption,
+    Test,
+    Api,
+}
+
+impl TTSSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TTSSource::Chat => "chat",
+            TTSSource::Redemption => "redemption",
+            TTSSource::Test => "test",
+            TTSSource::Api => "api",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TTSRequest {
+    pub id: String,
+    pub username: String,
+    pub language: String,
+    pub text: String,
+    #[serde(default)]
+    pub source: TTSSource,
+}
+
+#[derive(Debug, Clone)]
+pub struct TTSAudioChunk {
+    pub text: String,
+    pub audio_data: Vec<u8>,
+}
+
+/// How often a failed synthesis request is tried again, waiting twice as long after each attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// The wait before retry number `attempt`, counting from 0.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .checked_mul(2u32.saturating_pow(attempt))
+            .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+    }
+}
+
+/// Server errors, rate limits and dropped connections may go away on their own, other failures
+/// such as a 4xx status will fail the same way again.
+fn is_retryable(error: &(dyn std::error::Error + 'static)) -> bool {
+    let Some(error) = error.downcast_ref::<reqwest::Error>() else {
+        return false;
+    };
+    match error.status() {
+        Some(status) =>
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+        None => error.is_timeout() || error.is_connect() || error.is_request() || error.is_body(),
+    }
+}
+
+pub struct TTSService {
+    // shared by every provider, so connections are reused between requests
+    client: reqwest::Client,
+    // an Arc rather than a Box so a request can keep using it while the provider is switched
+    provider: Mutex<(TTSProviderKind, Arc<dyn TtsProvider>)>,
+    // tried in order when the provider above fails
+    fallbacks: Mutex<Vec<(TTSProviderKind, Arc<dyn TtsProvider>)>>,
+    request_timeout: Mutex<Duration>,
+    retry_policy: Mutex<RetryPolicy>,
+    cache: Mutex<AudioCache>,
+}
+
+impl TTSService {
+    /// `cache_size` is the most synthesized audio kept around for repeated phrases, in bytes.
+    pub fn new(provider: TTSProviderKind, request_timeout: Duration, cache_size: usize) -> Self {
+        let client = reqwest::Client
+            ::builder()
+            .user_agent(USER_AGENT)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .build()
+            .unwrap();
+        Self {
+            provider: Mutex::new((provider, provider.provider(client.clone()))),
+            fallbacks: Mutex::new(Vec::new()),
+            client,
+            request_timeout: Mutex::new(request_timeout),
+            retry_policy: Mutex::new(RetryPolicy::default()),
+            cache: Mutex::new(AudioCache::new(cache_size)),
+        }
+    }
+
+    /// Switches to another provider, dropping the audio cached from the previous one.
+    pub fn set_provider(&self, kind: TTSProviderKind) {
+        let mut provider = self.provider.lock().unwrap();
+        if provider.0 == kind {
+            return;
+        }
+        *provider = (kind, kind.provider(self.client.clone()));
+        self.clear_cache();
+    }
+
+    /// Sets the providers tried in order when the main one fails. The main provider and
+    /// duplicates are left out.
+    pub fn set_fallback_providers(&self, kinds: &[TTSProviderKind]) {
+        let primary = self.provider.lock().unwrap().0;
+        let mut fallbacks = Vec::new();
+        for &kind in kinds {
+            if kind != primary && !fallbacks.iter().any(|(fallback, _)| *fallback == kind) {
+                fallbacks.push((kind, kind.provider(self.client.clone())));
+            }
+        }
+        *self.fallbacks.lock().unwrap() = fallbacks;
+    }
+
+    /// Longest a single synthesis request may take before it fails.
+    pub fn set_request_timeout(&self, request_timeout: Duration) {
+        *self.request_timeout.lock().unwrap() = request_timeout;
+    }
+
+    pub fn set_retry_policy(&self, retry_policy: RetryPolicy) {
+        *self.retry_policy.lock().unwrap() = retry_policy;
+    }
+
+    pub fn set_cache_size(&self, cache_size: usize) {
+        self.cache.lock().unwrap().set_max_size(cache_size);
+    }
+
+    pub fn cache_size(&self) -> usize {
+        self.cache.lock().unwrap().size()
+    }
+
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// Synthesizes at most `max_chunks` chunks of the request, dropping the rest of the text.
+    pub async fn generate_tts(
+        &self,
+        request: &TTSRequest,
+        max_chunks: usize
+    ) -> Result<Vec<TTSAudioChunk>, Box<dyn std::error::Error + Send + Sync>> {
+        let chunks = Self::split_text(&request.text);
+        if chunks.len() > max_chunks {
+            log::warn!(
+                "TTS message from {} is {} chunks long, dropping the last {}",
+                request.username,
+                chunks.len(),
+                chunks.len() - max_chunks
+            );
+        }
+        let mut audio_chunks = Vec::new();
+        for chunk in chunks.into_iter().take(max_chunks) {
+            let audio_data = self.fetch_tts_audio(&chunk, &request.language).await?;
+            audio_chunks.push(TTSAudioChunk { text: chunk, audio_data });
+        }
+        Ok(audio_chunks)
+    }
+
+    /// Synthesizes a single chunk of `request` again, e.g. after its audio failed to decode.
+    pub async fn regenerate_chunk(
+        &self,
+        request: &TTSRequest,
+        chunk: &TTSAudioChunk
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        // the cached copy is the one that failed to decode
+        self.cache.lock().unwrap().remove(cache::cache_key(&chunk.text, &request.language));
+        self.fetch_tts_audio(&chunk.text, &request.language).await
+    }
+
+    async fn fetch_tts_audio(
+        &self,
+        text: &str,
+        language: &str
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        let key = cache::cache_key(text, language);
+        if let Some(audio_data) = self.cache.lock().unwrap().get(key) {
+            return Ok(audio_data);
+        }
+        let providers: Vec<(TTSProviderKind, Arc<dyn TtsProvider>)> = std::iter
+            ::once(self.provider.lock().unwrap().clone())
+            .chain(self.fallbacks.lock().unwrap().iter().cloned())
+            .collect();
+        let mut result = self.synthesize_with_retries(providers[0].1.as_ref(), text, language).await;
+        for (kind, provider) in &providers[1..] {
+            let Err(e) = &result else {
+                break;
+            };
+            log::warn!("TTS synthesis failed: {}, falling back to {}", e, kind.label());
+            result = self.synthesize_with_retries(provider.as_ref(), text, language).await;
+        }
+        let audio_data = result?;
+        self.cache.lock().unwrap().insert(key, audio_data.clone());
+        Ok(audio_data)
+    }
+
+    /// Synthesizes with a single provider, retrying according to the retry policy.
+    async fn synthesize_with_retries(
+        &self,
+        provider: &dyn TtsProvider,
+        text: &str,
+        language: &str
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        let request_timeout = *self.request_timeout.lock().unwrap();
+        let retry_policy = *self.retry_policy.lock().unwrap();
+        let mut attempt = 0;
+        loop {
+            let error: Box<dyn std::error::Error + Send + Sync> = match
+                tokio::time::timeout(request_timeout, provider.synthesize(text, language)).await
+            {
+                Ok(Ok(audio_data)) => {
+                    return Ok(audio_data);
+                }
+                Ok(Err(e)) if !is_retryable(e.as_ref()) => {
+                    return Err(e);
+                }
+                Ok(Err(e)) => e,
+                Err(_) => format!("TTS request timed out after {}s", request_timeout.as_secs()).into(),
+            };
+            if attempt >= retry_policy.max_retries {
+                return Err(error);
+            }
+            let delay = retry_policy.delay(attempt);
+            log::warn!("TTS request failed: {}, retrying in {}ms", error, delay.as_millis());
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Splits text into chunks of at most `MAX_TEXT_LENGTH` characters, keeping whole sentences
+    /// together where they fit and falling back to word boundaries for longer ones.
+    fn split_text(text: &str) -> Vec<String> {
+        let mut chunks = Vec::new();
+        let mut current_chunk = String::new();
+        for sentence in Self::split_sentences(text) {
+            let pieces = if sentence.chars().count() > MAX_TEXT_LENGTH {
+                Self::split_words(&sentence)
+            } else {
+                vec![sentence]
+            };
+            for piece in pieces {
+                let current_length = current_chunk.chars().count();
+                if current_length > 0 && current_length + piece.chars().count() + 1 > MAX_TEXT_LENGTH {
+                    chunks.push(std::mem::take(&mut current_chunk));
+                }
+                if !current_chunk.is_empty() {
+                    current_chunk.push(' ');
+                }
+                current_chunk.push_str(&piece);
+            }
+        }
+        if !current_chunk.is_empty() {
+            chunks.push(current_chunk);
+        }
+        chunks
+    }
+
+    /// Splits text after sentence-ending punctuation, normalizing the whitespace in between.
+    fn split_sentences(text: &str) -> Vec<String> {
+        let mut sentences = Vec::new();
+        let mut current_sentence = String::new();
+        for word in text.split_whitespace() {
+            if !current_sentence.is_empty() {
+                current_sentence.push(' ');
+            }
+            // "。" usually ends a sentence with no space after it
+            for character in word.chars() {
+                current_sentence.push(character);
+                if character == '。' {
+                    sentences.push(std::mem::take(&mut current_sentence));
+                }
+            }
+            if word.ends_with(['.', '!', '?']) && !current_sentence.is_empty() {
+                sentences.push(std::mem::take(&mut current_sentence));
+            }
+        }
+        if !current_sentence.is_empty() {
+            sentences.push(current_sentence);
+        }
+        sentences
+    }
+
+    /// Splits a sentence that is too long on its own at word boundaries, cutting words that
+    /// are still longer than the limit, e.g. text in languages written without spaces.
+    fn split_words(sentence: &str) -> Vec<String> {
+        let mut pieces = Vec::new();
+        let mut current_piece = String::new();
+        for word in sentence.split_whitespace() {
+            let characters: Vec<char> = word.chars().collect();
+            for part in characters.chunks(MAX_TEXT_LENGTH) {
+                let current_length = current_piece.chars().count();
+                if current_length > 0 && current_length + part.len() + 1 > MAX_TEXT_LENGTH {
+                    pieces.push(std::mem::take(&mut current_piece));
+                }
+                if !current_piece.is_empty() {
+                    current_piece.push(' ');
+                }
+                current_piece.extend(part);
+            }
+        }
+        if !current_piece.is_empty() {
+            pieces.push(current_piece);
+        }
+        pieces
+    }
+}
+
+// the remembered languages change on every TTS message, so they're written at most this often
+const LAST_LANGUAGES_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Remembers the language each user last used for TTS, for the same-language prefix. Kept in
+/// a file between sessions, forgetting the least recently seen users past the capacity.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LastLanguages {
+    // (username, language), least recently used first
+    by_user: VecDeque<(String, String)>,
+    // changed since the last write
+    #[serde(skip)]
+    unsaved: bool,
+    #[serde(skip)]
+    saved_at: Option<Instant>,
+}
+
+impl LastLanguages {
+    /// Loads the remembered languages, starting with none when the file can't be read.
+    pub fn load() -> Self {
+        let path = last_languages_path();
+        if !path.exists() {
+            return Self::default();
+        }
+        Self::read(&path).unwrap_or_else(|e| {
+            log::warn!("Could not load {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    /// Writes the remembered languages if they changed and the last write was long enough ago.
+    pub fn save(&mut self) {
+        let due = self.saved_at.is_none_or(|at| at.elapsed() >= LAST_LANGUAGES_SAVE_INTERVAL);
+        if due {
+            self.flush();
+        }
+    }
+
+    /// Writes the remembered languages if they changed, e.g. before disconnecting.
+    pub fn flush(&mut self) {
+        if !self.unsaved {
+            return;
+        }
+        let path = last_languages_path();
+        if let Err(e) = self.write(&path) {
+            log::warn!("Could not save {}: {}", path.display(), e);
+        }
+        // a failed write is retried with the next change rather than on every message
+        self.unsaved = false;
+        self.saved_at = Some(Instant::now());
+    }
+
+    fn read(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn write(&self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn record(&mut self, username: &str, language: &str, capacity: usize) {
+        let username = username.to_lowercase();
+        self.by_user.retain(|(user, _)| *user != username);
+        self.by_user.push_back((username, language.to_string()));
+        while self.by_user.len() > capacity {
+            self.by_user.pop_front();
+        }
+        self.unsaved = true;
+    }
+
+    pub fn get_or<'a>(&'a self, username: &str, default: &'a str) -> &'a str {
+        self.by_user
+            .iter()
+            .find(|(user, _)| user.eq_ignore_ascii_case(username))
+            .map_or(default, |(_, language)| language.as_str())
+    }
+}
+
+fn last_languages_path() -> std::path::PathBuf {
+    crate::backend::config::app_dir().join("tts_languages.json")
+}
+
+/// Tracks the player going from idle to busy and back, so the "incoming" sound only plays
+/// when TTS starts after a quiet period instead of before every queued message.
+#[derive(Default)]
+pub struct PlaybackTransitions {
+    busy: bool,
+}
+
+impl PlaybackTransitions {
+    /// Returns true when this item starts playback after the queue was empty.
+    pub fn item_started(&mut self) -> bool {
+        !std::mem::replace(&mut self.busy, true)
+    }
+
+    /// Returns true when the queue just drained.
+    pub fn queue_empty(&mut self) -> bool {
+        std::mem::replace(&mut self.busy, false)
+    }
+}
+
+/// Decides whether the configured error sound should play for a failed request,
+/// so a burst of failures doesn't play it over and over.
+#[derive(Default)]
+pub struct ErrorSoundGate {
+    last_played: Option<Instant>,
+}
+
+impl ErrorSoundGate {
+    pub fn should_play(&mut self, config: &TTSConfig) -> bool {
+        if config.error_sound.is_empty() {
+            return false;
+        }
+        if let Some(last_played) = self.last_played {
+            if last_played.elapsed() < Duration::from_secs(config.error_sound_cooldown) {
+                return false;
+            }
+        }
+        self.last_played = Some(Instant::now());
+        true
+    }
+}
+
+/// Limits how often each user can request TTS, so a single chatter can't flood the queue.
+#[derive(Default)]
+pub struct UserCooldowns {
+    cooldown: Duration,
+    last_request: HashMap<String, Instant>,
+}
+
+impl UserCooldowns {
+    pub fn new(secs: u64) -> Self {
+        Self {
+            cooldown: Duration::from_secs(secs),
+            last_request: HashMap::new(),
+        }
+    }
+
+    pub fn set_user_cooldown(&mut self, secs: u64) {
+        self.cooldown = Duration::from_secs(secs);
+    }
+
+    /// Returns false while the user is still on cooldown, otherwise starts a new one.
+    pub fn try_start(&mut self, username: &str) -> bool {
+        let cooldown = self.cooldown;
+        self.last_request.retain(|_, requested_at| requested_at.elapsed() < cooldown);
+        if cooldown.is_zero() {
+            return true;
+        }
+        let username = username.to_lowercase();
+        if self.last_request.contains_key(&username) {
+            return false;
+        }
+        self.last_request.insert(username, Instant::now());
+        true
+    }
+}
+
+/// Pauses TTS while chat is busier than `auto_pause_threshold` and resumes it once the rate
+/// drops under `auto_resume_threshold`, so it doesn't flap around a single threshold.
+#[derive(Default)]
+pub struct AutoPause {
+    paused: bool,
+}
+
+impl AutoPause {
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns the new paused state when it changes.
+    pub fn update(&mut self, message_count: usize, config: &TTSConfig) -> Option<bool> {
+        let paused = if !config.auto_pause {
+            false
+        } else if self.paused {
+            message_count >= config.auto_resume_threshold
+        } else {
+            message_count >= config.auto_pause_threshold
+        };
+        if paused == self.paused {
+            return None;
+        }
+        self.paused = paused;
+        Some(paused)
+    }
+}
+
+/// Silence inserted between the chunks of a multi-chunk message in the given language.
+pub fn chunk_gap(config: &TTSConfig, language: &str) -> Duration {
+    let gap_ms = config.chunk_gap_overrides.get(language).copied().unwrap_or(config.chunk_gap_ms);
+    Duration::from_millis(gap_ms)
+}
+
+/// Prepends `template`, with `{user}` replaced by the username, as a sentence of its own.
+pub fn announce_username(template: &str, username: &str, text: &str) -> String {
+    let announcement = template.replace("{user}", username);
+    let announcement = announcement.trim();
+    if announcement.is_empty() {
+        return text.to_string();
+    }
+    if announcement.ends_with(['.', '!', '?', ':']) {
+        format!("{} {}", announcement, text)
+    } else {
+        format!("{}. {}", announcement, text)
+    }
+}
+
+/// Cleans up chat text before it is synthesized.
+pub fn sanitize(text: &str, config: &TTSConfig) -> String {
+    let text = if config.normalize_whitespace {
+        normalize_whitespace(text)
+    } else {
+        text.to_string()
+    };
+    let text = if config.strip_urls { strip_urls(&text) } else { text };
+    let text = if config.strip_mentions {
+        strip_mentions(&text, config.keep_mention_names)
+    } else {
+        text
+    };
+    let text = if config.collapse_repeats { collapse_repeats(&text) } else { text };
+    limit_words(&text, config.max_words, &config.truncation_suffix)
+}
+
+fn strip_urls(text: &str) -> String {
+    text.split_whitespace()
+        .filter(|word| {
+            let word = word.to_lowercase();
+            !(word.contains("://") || word.starts_with("www."))
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Collapses a word repeated more than twice in a row, e.g. emote spam, into a single one.
+fn collapse_repeats(text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut collapsed = Vec::new();
+    let mut start = 0;
+    while start < words.len() {
+        let run = words[start..]
+            .iter()
+            .take_while(|word| **word == words[start])
+            .count();
+        let kept = if run > 2 { 1 } else { run };
+        collapsed.extend(std::iter::repeat_n(words[start], kept));
+        start += run;
+    }
+    collapsed.join(" ")
+}
+
+/// Keeps the first `max_words` words and appends `suffix` when anything was cut.
+/// A `max_words` of 0 disables the limit.
+fn limit_words(text: &str, max_words: usize, suffix: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if max_words == 0 || words.len() <= max_words {
+        return text.to_string();
+    }
+    let mut limited = words[..max_words].join(" ");
+    if !suffix.is_empty() {
+        limited.push(' ');
+        limited.push_str(suffix);
+    }
+    limited
+}
+
+/// Strips control and zero-width characters, which read as odd pauses or let a message look
+/// empty, then collapses runs of whitespace into single spaces.
+fn normalize_whitespace(text: &str) -> String {
+    let visible: String = text
+        .chars()
+        .map(|character| if character.is_whitespace() { ' ' } else { character })
+        .filter(|character| !character.is_control() && !is_zero_width(*character))
+        .collect();
+    visible.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn is_zero_width(character: char) -> bool {
+    matches!(
+        character,
+        '\u{00AD}' | '\u{200B}'..='\u{200F}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}'
+    )
+}
+
+fn strip_mentions(text: &str, keep_names: bool) -> String {
+    text.split_whitespace()
+        .filter_map(|word| match word.strip_prefix('@') {
+            Some(name) if !name.is_empty() => keep_names.then_some(name),
+            _ => Some(word),
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{ AtomicU32, Ordering };
+
+    use self::provider::SynthesisResult;
+    use crate::backend::config::AppConfig;
+
+    /// Times out on its first `failures` requests, then succeeds.
+    struct FlakyProvider {
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    impl TtsProvider for FlakyProvider {
+        fn synthesize<'a>(
+            &'a self,
+            _text: &'a str,
+            _language: &'a str
+        ) -> Pin<Box<dyn Future<Output = SynthesisResult> + Send + 'a>> {
+            let call = self.calls.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async move {
+                if call < self.failures {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                }
+                Ok(vec![1, 2, 3])
+            })
+        }
+    }
+
+    fn flaky_service(max_retries: u32) -> TTSService {
+        let service = TTSService::new(TTSProviderKind::default(), Duration::from_millis(20), 0);
+        service.set_retry_policy(RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+        });
+        service
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_maximum() {
+        let policy = RetryPolicy { max_retries: 3, base_delay: Duration::from_millis(500) };
+        assert_eq!(policy.delay(0), Duration::from_millis(500));
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(10), MAX_RETRY_DELAY);
+        assert_eq!(policy.delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn synthesis_is_retried_until_it_succeeds() {
+        let provider = FlakyProvider { failures: 2, calls: AtomicU32::new(0) };
+        let result = flaky_service(2).synthesize_with_retries(&provider, "hi", "en").await;
+        assert_eq!(result.unwrap(), [1, 2, 3]);
+        assert_eq!(provider.calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn synthesis_gives_up_after_the_retry_limit() {
+        let provider = FlakyProvider { failures: 5, calls: AtomicU32::new(0) };
+        let result = flaky_service(2).synthesize_with_retries(&provider, "hi", "en").await;
+        assert!(result.is_err());
+        assert_eq!(provider.calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn last_languages_survive_a_save_and_load() {
+        let path = std::env
+            ::temp_dir()
+            .join(format!("yambot-languages-{}.json", std::process::id()));
+        let mut languages = LastLanguages::default();
+        languages.record("Alice", "de", 10);
+        languages.record("bob", "fr", 10);
+        languages.write(&path).unwrap();
+        let loaded = LastLanguages::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded.get_or("alice", "en"), "de");
+        assert_eq!(loaded.get_or("BOB", "en"), "fr");
+        assert_eq!(loaded.get_or("carol", "en"), "en");
+    }
+
+    fn assert_chunks_fit(chunks: &[String]) {
+        assert!(!chunks.is_empty());
+        for chunk in chunks {
+            assert!(chunk.chars().count() <= MAX_TEXT_LENGTH, "{} is too long", chunk);
+        }
+    }
+
+    #[test]
+    fn long_polish_sentence_is_split_at_words_by_characters() {
+        let words = ["Zażółć", "gęślą", "jaźń", "źdźbło", "żółwia", "pchnąć", "łódź", "jeża"];
+        let mut sentence = String::new();
+        for word in words.iter().cycle() {
+            if sentence.chars().count() >= 500 {
+                break;
+            }
+            sentence.push_str(word);
+            sentence.push(' ');
+        }
+        let sentence = format!("{}.", sentence.trim_end());
+        let chunks = TTSService::split_text(&sentence);
+        assert_chunks_fit(&chunks);
+        assert_eq!(chunks.len(), 3);
+        // only whole words, nothing lost
+        assert_eq!(chunks.join(" "), sentence);
+    }
+
+    #[test]
+    fn japanese_text_is_split_after_each_full_stop() {
+        let text = "今日はとても良い天気ですね。".repeat(30);
+        let chunks = TTSService::split_text(&text);
+        assert_chunks_fit(&chunks);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.ends_with('。'), "{} ends mid sentence", chunk);
+        }
+    }
+
+    #[test]
+    fn chunks_end_at_sentence_punctuation_when_they_can() {
+        let text = "This sentence is exactly as long as the next one! Is it though? Yes it is. "
+            .repeat(6);
+        let chunks = TTSService::split_text(&text);
+        assert_chunks_fit(&chunks);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.ends_with(['.', '!', '?']), "{} ends mid sentence", chunk);
+        }
+    }
+
+    #[test]
+    fn text_without_spaces_is_cut_at_the_limit() {
+        let text = "a".repeat(MAX_TEXT_LENGTH * 2 + 10);
+        let chunks = TTSService::split_text(&text);
+        assert_chunks_fit(&chunks);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.concat(), text);
+    }
+
+    fn tts_config() -> TTSConfig {
+        AppConfig::default().tts
+    }
+
+    #[test]
+    fn mentions_are_dropped_or_read_as_names() {
+        assert_eq!(strip_mentions("hi @Streamer and @mod!", false), "hi and");
+        assert_eq!(strip_mentions("hi @Streamer and @mod!", true), "hi Streamer and mod!");
+        // a lone @ isn't a mention
+        assert_eq!(strip_mentions("meet me @ 5", false), "meet me @ 5");
+    }
+
+    #[test]
+    fn sanitize_only_strips_mentions_when_enabled() {
+        let mut config = tts_config();
+        config.strip_mentions = true;
+        config.keep_mention_names = false;
+        assert_eq!(sanitize("@streamer hello", &config), "hello");
+        config.strip_mentions = false;
+        assert_eq!(sanitize("@streamer hello", &config), "@streamer hello");
+    }
+
+    #[test]
+    fn error_sound_plays_once_per_cooldown() {
+        let mut config = tts_config();
+        config.error_sound = "error.mp3".to_string();
+        config.error_sound_cooldown = 60;
+        let mut gate = ErrorSoundGate::default();
+        assert!(gate.should_play(&config));
+        assert!(!gate.should_play(&config));
+        config.error_sound_cooldown = 0;
+        assert!(gate.should_play(&config));
+    }
+
+    #[test]
+    fn error_sound_is_off_without_a_file() {
+        let mut config = tts_config();
+        config.error_sound = String::new();
+        assert!(!ErrorSoundGate::default().should_play(&config));
+    }
+
+    #[test]
+    fn auto_pause_resumes_below_the_lower_threshold() {
+        let mut config = tts_config();
+        config.auto_pause = true;
+        config.auto_pause_threshold = 20;
+        config.auto_resume_threshold = 10;
+        let mut auto_pause = AutoPause::default();
+        assert_eq!(auto_pause.update(19, &config), None);
+        assert_eq!(auto_pause.update(20, &config), Some(true));
+        // between the thresholds it stays paused
+        assert_eq!(auto_pause.update(15, &config), None);
+        assert!(auto_pause.is_paused());
+        assert_eq!(auto_pause.update(9, &config), Some(false));
+        assert_eq!(auto_pause.update(15, &config), None);
+        assert!(!auto_pause.is_paused());
+    }
+
+    #[test]
+    fn turning_auto_pause_off_resumes_tts() {
+        let mut config = tts_config();
+        config.auto_pause = true;
+        config.auto_pause_threshold = 20;
+        config.auto_resume_threshold = 10;
+        let mut auto_pause = AutoPause::default();
+        auto_pause.update(50, &config);
+        config.auto_pause = false;
+        assert_eq!(auto_pause.update(50, &config), Some(false));
+    }
+
+    #[test]
+    fn chunk_gap_uses_the_language_override() {
+        let mut config = tts_config();
+        config.chunk_gap_ms = 300;
+        config.chunk_gap_overrides = HashMap::from([("ja".to_string(), 100)]);
+        assert_eq!(chunk_gap(&config, "ja"), Duration::from_millis(100));
+        assert_eq!(chunk_gap(&config, "en"), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn transitions_fire_only_at_the_edges() {
+        let mut transitions = PlaybackTransitions::default();
+        assert!(!transitions.queue_empty());
+        assert!(transitions.item_started());
+        assert!(!transitions.item_started());
+        assert!(transitions.queue_empty());
+        assert!(!transitions.queue_empty());
+        assert!(transitions.item_started());
+    }
+
+    #[test]
+    fn users_wait_out_their_own_cooldown() {
+        let mut cooldowns = UserCooldowns::new(60);
+        assert!(cooldowns.try_start("Alice"));
+        assert!(!cooldowns.try_start("alice"));
+        assert!(cooldowns.try_start("bob"));
+        cooldowns.set_user_cooldown(0);
+        assert!(cooldowns.try_start("alice"));
+        assert!(cooldowns.try_start("alice"));
+    }
+
+    #[test]
+    fn expired_user_cooldowns_are_forgotten() {
+        let mut cooldowns = UserCooldowns::new(60);
+        let long_ago = Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        cooldowns.last_request.insert("alice".to_string(), long_ago);
+        assert!(cooldowns.try_start("bob"));
+        assert!(!cooldowns.last_request.contains_key("alice"));
+        assert!(cooldowns.try_start("alice"));
+    }
+
+    #[test]
+    fn invisible_characters_and_whitespace_runs_are_cleaned_up() {
+        let text = "  hello\u{200B}\t\tworld\n\u{FEFF}again\u{0007} ";
+        assert_eq!(normalize_whitespace(text), "hello world again");
+        // text that is only invisible characters ends up empty
+        assert_eq!(normalize_whitespace("\u{200B}\u{2060} \u{00AD}"), "");
+    }
+
+    #[test]
+    fn sanitize_only_normalizes_whitespace_when_enabled() {
+        let mut config = tts_config();
+        config.strip_mentions = false;
+        config.normalize_whitespace = false;
+        assert_eq!(sanitize("a\u{200B}b", &config), "a\u{200B}b");
+        config.normalize_whitespace = true;
+        assert_eq!(sanitize("a\u{200B}b", &config), "ab");
+    }
+
+    #[test]
+    fn long_messages_keep_their_first_words() {
+        assert_eq!(limit_words("one two three four", 2, "and so on"), "one two and so on");
+        assert_eq!(limit_words("one two three four", 2, ""), "one two");
+        assert_eq!(limit_words("one  two", 2, "and so on"), "one  two");
+        assert_eq!(limit_words("one two three", 0, "and so on"), "one two three");
+    }
+
+    #[test]
+    fn sanitize_limits_words_last() {
+        let mut config = tts_config();
+        config.strip_mentions = true;
+        config.keep_mention_names = false;
+        config.max_words = 2;
+        config.truncation_suffix = "etc".to_string();
+        // the dropped mention doesn't count towards the limit
+        assert_eq!(sanitize("@streamer one two three", &config), "one two etc");
+    }
+
+    #[test]
+    fn announcement_is_read_as_its_own_sentence() {
+        assert_eq!(announce_username("{user} says", "Alice", "hi"), "Alice says. hi");
+        assert_eq!(announce_username("{user} says:", "Alice", "hi"), "Alice says: hi");
+        assert_eq!(announce_username("From {user}! ", "Alice", "hi"), "From Alice! hi");
+    }
+
+    #[test]
+    fn blank_announcement_leaves_the_text() {
+        assert_eq!(announce_username("  ", "Alice", "hi"), "hi");
+        assert_eq!(announce_username("{user}", "", "hi"), "hi");
+    }
+
+    #[test]
+    fn links_are_stripped() {
+        let text = "look HTTPS://example.com and www.example.org or ftp://host now";
+        assert_eq!(strip_urls(text), "look and or now");
+        assert_eq!(strip_urls("example.com stays"), "example.com stays");
+    }
+
+    #[test]
+    fn words_repeated_more_than_twice_are_collapsed() {
+        assert_eq!(collapse_repeats("LUL LUL LUL LUL nice"), "LUL nice");
+        assert_eq!(collapse_repeats("no no way"), "no no way");
+        // only runs in a row count, and case matters
+        assert_eq!(collapse_repeats("hi Hi hi hi"), "hi Hi hi hi");
+        assert_eq!(collapse_repeats("a b a b a b"), "a b a b a b");
+    }
+
+    #[test]
+    fn sanitize_only_strips_links_and_repeats_when_enabled() {
+        let mut config = tts_config();
+        config.strip_urls = false;
+        config.collapse_repeats = false;
+        let text = "wow wow wow https://clips.twitch.tv/abc";
+        assert_eq!(sanitize(text, &config), text);
+        config.strip_urls = true;
+        config.collapse_repeats = true;
+        assert_eq!(sanitize(text, &config), "wow");
+    }
+}
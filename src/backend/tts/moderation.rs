@@ -0,0 +1,189 @@
+use serde::{ Deserialize, Serialize };
+use std::fs;
+use std::path::Path;
+
+// spoken in place of a censored phrase
+pub const BLEEP: &str = "bleep";
+
+/// How a banned phrase has to appear in a message to match.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum PhraseMatch {
+    // anywhere, including inside longer words
+    #[default]
+    Substring,
+    WholeWord,
+}
+
+/// What happens to a message containing a banned phrase.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum BannedPhraseAction {
+    // skip the whole message
+    #[default]
+    Block,
+    // read the message with each banned phrase replaced by `BLEEP`
+    Censor,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ModerationList {
+    BannedPhrases,
+    IgnoredUsers,
+}
+
+/// Phrases TTS refuses to read and users whose messages it skips, stored together in one file.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TTSModeration {
+    #[serde(default)]
+    pub banned_phrases: Vec<String>,
+    #[serde(default)]
+    pub ignored_users: Vec<String>,
+}
+
+impl TTSModeration {
+    pub fn list(&self, list: ModerationList) -> &Vec<String> {
+        match list {
+            ModerationList::BannedPhrases => &self.banned_phrases,
+            ModerationList::IgnoredUsers => &self.ignored_users,
+        }
+    }
+
+    fn list_mut(&mut self, list: ModerationList) -> &mut Vec<String> {
+        match list {
+            ModerationList::BannedPhrases => &mut self.banned_phrases,
+            ModerationList::IgnoredUsers => &mut self.ignored_users,
+        }
+    }
+
+    /// Returns false when the entry is empty or already on the list.
+    pub fn add(&mut self, list: ModerationList, entry: &str) -> bool {
+        let entry = entry.trim().to_lowercase();
+        let entries = self.list_mut(list);
+        if entry.is_empty() || entries.contains(&entry) {
+            return false;
+        }
+        entries.push(entry);
+        true
+    }
+
+    pub fn remove(&mut self, list: ModerationList, entry: &str) -> bool {
+        let entries = self.list_mut(list);
+        let length = entries.len();
+        entries.retain(|existing| existing != entry);
+        entries.len() != length
+    }
+
+    pub fn is_ignored(&self, username: &str) -> bool {
+        self.ignored_users.iter().any(|user| user.eq_ignore_ascii_case(username))
+    }
+
+    /// Returns the text to synthesize, or `None` when the message is blocked.
+    pub fn filter_text(
+        &self,
+        text: &str,
+        matching: PhraseMatch,
+        action: BannedPhraseAction
+    ) -> Option<String> {
+        let ranges = self.banned_ranges(text, matching);
+        if ranges.is_empty() {
+            return Some(text.to_string());
+        }
+        if action == BannedPhraseAction::Block {
+            return None;
+        }
+        let characters: Vec<char> = text.chars().collect();
+        let mut filtered = String::new();
+        let mut position = 0;
+        for (start, end) in ranges {
+            filtered.extend(&characters[position..start]);
+            filtered.push_str(BLEEP);
+            position = end;
+        }
+        filtered.extend(&characters[position..]);
+        Some(filtered)
+    }
+
+    /// Character ranges of `text` covered by banned phrases, ignoring case, with overlapping
+    /// matches merged and sorted by position.
+    fn banned_ranges(&self, text: &str, matching: PhraseMatch) -> Vec<(usize, usize)> {
+        let text = fold_case(text);
+        let is_word_boundary = |character: Option<&char>| {
+            character.is_none_or(|character| !character.is_alphanumeric())
+        };
+        let mut ranges = Vec::new();
+        for phrase in &self.banned_phrases {
+            let phrase = fold_case(phrase.trim());
+            if phrase.is_empty() || phrase.len() > text.len() {
+                continue;
+            }
+            for start in 0..=text.len() - phrase.len() {
+                let end = start + phrase.len();
+                if text[start..end] != phrase[..] {
+                    continue;
+                }
+                if
+                    matching == PhraseMatch::WholeWord &&
+                    !(
+                        is_word_boundary(start.checked_sub(1).and_then(|index| text.get(index))) &&
+                        is_word_boundary(text.get(end))
+                    )
+                {
+                    continue;
+                }
+                ranges.push((start, end));
+            }
+        }
+        ranges.sort();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => {
+                    last.1 = last.1.max(end);
+                }
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    pub fn import<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let moderation: TTSModeration = serde_json::from_str(&content)?;
+        Ok(moderation)
+    }
+
+    pub fn export<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Lowercases text one character at a time, so character positions line up with the original.
+fn fold_case(text: &str) -> Vec<char> {
+    text.chars()
+        .map(|character| character.to_lowercase().next().unwrap_or(character))
+        .collect()
+}
+
+fn moderation_path() -> std::path::PathBuf {
+    crate::backend::config::app_dir().join("tts_moderation.json")
+}
+
+/// Loads the saved lists, starting with empty ones when the file can't be read.
+pub fn load_moderation() -> TTSModeration {
+    let path = moderation_path();
+    if !path.exists() {
+        return TTSModeration::default();
+    }
+    TTSModeration::import(&path).unwrap_or_else(|e| {
+        log::error!("Could not load {}: {}", path.display(), e);
+        TTSModeration::default()
+    })
+}
+
+/// Saves the lists, which stay in use for this session even when writing fails.
+pub fn save_moderation(moderation: &TTSModeration) {
+    let path = moderation_path();
+    if let Err(e) = moderation.export(&path) {
+        log::error!("Could not save {}: {}", path.display(), e);
+    }
+}
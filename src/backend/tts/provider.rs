@@ -0,0 +1,132 @@
+use serde::{ Deserialize, Serialize };
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+const GOOGLE_TTS_URL: &str = "https://translate.google.com/translate_tts";
+const STREAMELEMENTS_TTS_URL: &str = "https://api.streamelements.com/kappa/v2/speech";
+const STREAMELEMENTS_DEFAULT_VOICE: &str = "Brian";
+
+/// StreamElements voices for the language codes they can speak, as (code, voice) pairs.
+const STREAMELEMENTS_VOICES: &[(&str, &str)] = &[
+    ("ar", "Zeina"),
+    ("cy", "Gwyneth"),
+    ("da", "Mads"),
+    ("de", "Hans"),
+    ("en", "Brian"),
+    ("es", "Enrique"),
+    ("fr", "Mathieu"),
+    ("hi", "Aditi"),
+    ("is", "Karl"),
+    ("it", "Giorgio"),
+    ("ja", "Mizuki"),
+    ("ko", "Seoyeon"),
+    ("nl", "Ruben"),
+    ("no", "Liv"),
+    ("pl", "Jacek"),
+    ("pt", "Ricardo"),
+    ("ro", "Carmen"),
+    ("ru", "Maxim"),
+    ("sv", "Astrid"),
+    ("tr", "Filiz"),
+    ("zh-CN", "Zhiyu"),
+];
+
+pub type SynthesisResult = Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>;
+
+/// A service that turns text into audio. Language codes are the ones in `languages::LANGUAGES`,
+/// each provider maps them to whatever its endpoint expects.
+pub trait TtsProvider: Send + Sync {
+    fn synthesize<'a>(
+        &'a self,
+        text: &'a str,
+        language: &'a str
+    ) -> Pin<Box<dyn Future<Output = SynthesisResult> + Send + 'a>>;
+}
+
+/// Which TTS provider the service synthesizes with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum TTSProviderKind {
+    #[default]
+    Google,
+    StreamElements,
+}
+
+impl TTSProviderKind {
+    pub const ALL: [TTSProviderKind; 2] = [TTSProviderKind::Google, TTSProviderKind::StreamElements];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TTSProviderKind::Google => "Google Translate",
+            TTSProviderKind::StreamElements => "StreamElements",
+        }
+    }
+
+    pub fn provider(&self, client: reqwest::Client) -> Arc<dyn TtsProvider> {
+        match self {
+            TTSProviderKind::Google => Arc::new(GoogleProvider { client }),
+            TTSProviderKind::StreamElements => Arc::new(StreamElementsProvider { client }),
+        }
+    }
+}
+
+/// The unofficial Google Translate endpoint, which takes the language code as is.
+pub struct GoogleProvider {
+    client: reqwest::Client,
+}
+
+impl TtsProvider for GoogleProvider {
+    fn synthesize<'a>(
+        &'a self,
+        text: &'a str,
+        language: &'a str
+    ) -> Pin<Box<dyn Future<Output = SynthesisResult> + Send + 'a>> {
+        Box::pin(async move {
+            let url = reqwest::Url::parse_with_params(
+                GOOGLE_TTS_URL,
+                &[
+                    ("ie", "UTF-8"),
+                    ("client", "tw-ob"),
+                    ("tl", language),
+                    ("q", text),
+                ]
+            )?;
+            let response = self.client.get(url).send().await?.error_for_status()?;
+            Ok(response.bytes().await?.to_vec())
+        })
+    }
+}
+
+/// StreamElements speech endpoint, which picks a voice instead of a language.
+pub struct StreamElementsProvider {
+    client: reqwest::Client,
+}
+
+impl StreamElementsProvider {
+    /// Languages without a voice of their own fall back to the default English one.
+    pub fn voice(language: &str) -> &'static str {
+        STREAMELEMENTS_VOICES.iter()
+            .find(|(code, _)| *code == language)
+            .map_or(STREAMELEMENTS_DEFAULT_VOICE, |(_, voice)| voice)
+    }
+}
+
+impl TtsProvider for StreamElementsProvider {
+    fn synthesize<'a>(
+        &'a self,
+        text: &'a str,
+        language: &'a str
+    ) -> Pin<Box<dyn Future<Output = SynthesisResult> + Send + 'a>> {
+        Box::pin(async move {
+            let url = reqwest::Url::parse_with_params(
+                STREAMELEMENTS_TTS_URL,
+                &[
+                    ("voice", Self::voice(language)),
+                    ("text", text),
+                ]
+            )?;
+            let response = self.client.get(url).send().await?.error_for_status()?;
+            Ok(response.bytes().await?.to_vec())
+        })
+    }
+}
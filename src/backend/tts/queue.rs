@@ -0,0 +1,487 @@
+use serde::{ Deserialize, Serialize };
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::mpsc;
+use std::time::{ Duration, Instant };
+
+use crate::ui::TTSQueueItemUI;
+
+use super::{ TTSAudioChunk, TTSRequest };
+
+/// Elevated items play before every normal one, items in the same tier play in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum TTSPriority {
+    #[default]
+    Normal,
+    Elevated,
+}
+
+#[derive(Debug, Clone)]
+pub struct TTSQueueItem {
+    pub request: TTSRequest,
+    pub audio_chunks: Vec<TTSAudioChunk>,
+    pub priority: TTSPriority,
+}
+
+impl TTSQueueItem {
+    pub fn audio_bytes(&self) -> usize {
+        self.audio_chunks
+            .iter()
+            .map(|chunk| chunk.audio_data.len())
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueueRejection {
+    Full,
+    // the item's audio would put the queue over its memory budget
+    OverMemoryBudget,
+}
+
+/// What happens to a new message when the queue is already at its maximum length.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum QueueFullPolicy {
+    #[default]
+    Reject,
+    DropOldest,
+}
+
+#[derive(Debug)]
+pub enum QueueAddResult {
+    Added,
+    // the new item was added in place of this one
+    DroppedOldest(TTSQueueItem),
+    Rejected(QueueRejection),
+}
+
+pub struct TTSQueue {
+    items: VecDeque<TTSQueueItem>,
+    // popped by the player and not finished yet
+    currently_playing: Option<TTSRequest>,
+    skip: AtomicBool,
+    max_length: usize,
+    full_policy: QueueFullPolicy,
+    // bytes of audio the queue may hold, 0 for no limit
+    memory_budget: usize,
+    memory_usage: usize,
+    // pending requests are sent to a writer thread after every change when persisting
+    persister: Option<mpsc::Sender<PersistJob>>,
+}
+
+enum PersistJob {
+    Write(String),
+    Remove,
+}
+
+/// Writes the persisted queue on its own thread, so the queue's lock isn't held during file
+/// writes. Only the newest of several waiting snapshots is written.
+fn spawn_persister(path: PathBuf) -> mpsc::Sender<PersistJob> {
+    let (persist_tx, persist_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        while let Ok(job) = persist_rx.recv() {
+            let job = persist_rx.try_iter().last().unwrap_or(job);
+            let result = match job {
+                PersistJob::Write(content) => fs::write(&path, content),
+                PersistJob::Remove => fs::remove_file(&path),
+            };
+            if let Err(e) = result {
+                log::warn!("Could not save the TTS queue to {}: {}", path.display(), e);
+            }
+        }
+    });
+    persist_tx
+}
+
+impl TTSQueue {
+    pub fn new(max_length: usize) -> Self {
+        Self {
+            items: VecDeque::new(),
+            currently_playing: None,
+            skip: AtomicBool::new(false),
+            max_length,
+            full_policy: QueueFullPolicy::default(),
+            memory_budget: 0,
+            memory_usage: 0,
+            persister: None,
+        }
+    }
+
+    /// Keeps `path` up to date with the pending requests from now on, `None` stops persisting.
+    pub fn set_persist_path(&mut self, persist_path: Option<PathBuf>) {
+        if let Some(persister) = self.persister.take() {
+            if persist_path.is_none() {
+                let _ = persister.send(PersistJob::Remove);
+            }
+        }
+        self.persister = persist_path.map(spawn_persister);
+        self.persist();
+    }
+
+    /// The requests that haven't finished playing, the one playing first, as written to the
+    /// persist path. Audio is left out, it is synthesized again when the requests are restored.
+    fn pending_requests_json(&self) -> serde_json::Result<String> {
+        let requests: Vec<&TTSRequest> = self.currently_playing
+            .iter()
+            .chain(self.items.iter().map(|item| &item.request))
+            .collect();
+        serde_json::to_string(&requests)
+    }
+
+    /// Reads the requests written to the persist path. A missing file means nothing was pending.
+    pub fn load_from_disk(path: &Path) -> std::io::Result<Vec<TTSRequest>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn persist(&self) {
+        let Some(persister) = &self.persister else {
+            return;
+        };
+        match self.pending_requests_json() {
+            Ok(content) => {
+                let _ = persister.send(PersistJob::Write(content));
+            }
+            Err(e) => log::warn!("Could not save the TTS queue: {}", e),
+        }
+    }
+
+    pub fn set_memory_budget(&mut self, memory_budget: usize) {
+        self.memory_budget = memory_budget;
+    }
+
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget
+    }
+
+    /// Bytes of audio currently held by queued items.
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    pub fn set_max_length(&mut self, max_length: usize) {
+        self.max_length = max_length;
+    }
+
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    pub fn set_full_policy(&mut self, full_policy: QueueFullPolicy) {
+        self.full_policy = full_policy;
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= self.max_length
+    }
+
+    /// Adds an item behind the others of its priority. When the queue is full it either rejects
+    /// the item or drops the oldest one of the lowest priority, depending on the policy, never
+    /// dropping an item of higher priority than the new one. Items whose audio doesn't fit in
+    /// the memory budget, counting what would be dropped, are always rejected.
+    pub fn add(&mut self, item: TTSQueueItem) -> QueueAddResult {
+        let bytes = item.audio_bytes();
+        let evict = if self.is_full() {
+            if self.full_policy == QueueFullPolicy::Reject {
+                return QueueAddResult::Rejected(QueueRejection::Full);
+            }
+            match self.oldest_droppable(item.priority) {
+                Some(index) => Some(index),
+                None => {
+                    return QueueAddResult::Rejected(QueueRejection::Full);
+                }
+            }
+        } else {
+            None
+        };
+        let freed = evict.map_or(0, |index| self.items[index].audio_bytes());
+        if self.memory_budget > 0 && self.memory_usage - freed + bytes > self.memory_budget {
+            return QueueAddResult::Rejected(QueueRejection::OverMemoryBudget);
+        }
+        let dropped = evict.and_then(|index| self.items.remove(index));
+        self.memory_usage = self.memory_usage - freed + bytes;
+        let index = self.items
+            .iter()
+            .position(|queued| queued.priority < item.priority)
+            .unwrap_or(self.items.len());
+        self.items.insert(index, item);
+        self.persist();
+        match dropped {
+            Some(dropped) => QueueAddResult::DroppedOldest(dropped),
+            None => QueueAddResult::Added,
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<TTSQueueItem> {
+        let item = self.items.pop_front()?;
+        self.memory_usage -= item.audio_bytes();
+        self.persist();
+        Some(item)
+    }
+
+    // index of the oldest item of the lowest priority, if that's no higher than `priority`
+    fn oldest_droppable(&self, priority: TTSPriority) -> Option<usize> {
+        let lowest = self.items.iter().map(|item| item.priority).min()?;
+        if lowest > priority {
+            return None;
+        }
+        self.items.iter().position(|item| item.priority == lowest)
+    }
+
+    pub fn set_currently_playing(&mut self, request: Option<TTSRequest>) {
+        self.currently_playing = request;
+        self.clear_skip();
+        self.persist();
+    }
+
+    pub fn get_currently_playing(&self) -> Option<&TTSRequest> {
+        self.currently_playing.as_ref()
+    }
+
+    /// Asks the player to stop the current message. Returns false when nothing is playing.
+    pub fn skip(&self) -> bool {
+        if self.currently_playing.is_none() {
+            return false;
+        }
+        self.skip.store(true, Ordering::Relaxed);
+        true
+    }
+
+    pub fn get_skip_flag(&self) -> bool {
+        self.skip.load(Ordering::Relaxed)
+    }
+
+    pub fn clear_skip(&self) {
+        self.skip.store(false, Ordering::Relaxed);
+    }
+
+    /// Drops the queued message `id` before it's played. Returns false when it isn't queued.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let Some(index) = self.items.iter().position(|item| item.request.id == id) else {
+            return false;
+        };
+        if let Some(item) = self.items.remove(index) {
+            self.memory_usage -= item.audio_bytes();
+        }
+        self.persist();
+        true
+    }
+
+    /// Drops every queued message, leaving the one that is playing. Returns how many there were.
+    pub fn clear(&mut self) -> usize {
+        let count = self.items.len();
+        self.items.clear();
+        self.memory_usage = 0;
+        self.persist();
+        count
+    }
+
+    pub fn to_ui(&self) -> Vec<TTSQueueItemUI> {
+        self.items
+            .iter()
+            .map(|item| Self::request_to_ui(&item.request, false))
+            .collect()
+    }
+
+    /// Like `to_ui`, with the message that is playing first.
+    pub fn get_all_with_current(&self) -> Vec<TTSQueueItemUI> {
+        self.currently_playing
+            .iter()
+            .map(|request| Self::request_to_ui(request, true))
+            .chain(self.to_ui())
+            .collect()
+    }
+
+    fn request_to_ui(request: &TTSRequest, playing: bool) -> TTSQueueItemUI {
+        TTSQueueItemUI {
+            id: request.id.clone(),
+            username: request.username.clone(),
+            language: request.language.clone(),
+            text: request.text.clone(),
+            source: request.source,
+            playing,
+        }
+    }
+}
+
+pub fn persisted_queue_path() -> PathBuf {
+    crate::backend::config::app_dir().join("tts_queue.json")
+}
+
+/// Rate-limits the chat reply sent when a TTS request is rejected because the queue is full.
+pub struct QueueFullNotice {
+    cooldown: Duration,
+    last_sent: Option<Instant>,
+}
+
+impl QueueFullNotice {
+    pub fn new(cooldown: Duration) -> Self {
+        Self {
+            cooldown,
+            last_sent: None,
+        }
+    }
+
+    pub fn set_cooldown(&mut self, cooldown: Duration) {
+        self.cooldown = cooldown;
+    }
+
+    /// Returns the reply to send, or `None` while the previous reply is still on cooldown.
+    pub fn reply(&mut self, queue_length: usize, max_length: usize) -> Option<String> {
+        if let Some(last_sent) = self.last_sent {
+            if last_sent.elapsed() < self.cooldown {
+                return None;
+            }
+        }
+        self.last_sent = Some(Instant::now());
+        Some(format!("TTS queue is full ({}/{}), try again later", queue_length, max_length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, priority: TTSPriority, bytes: usize) -> TTSQueueItem {
+        TTSQueueItem {
+            request: TTSRequest {
+                id: id.to_string(),
+                username: "viewer".to_string(),
+                language: "en".to_string(),
+                text: "hello".to_string(),
+                source: Default::default(),
+            },
+            audio_chunks: vec![TTSAudioChunk {
+                text: "hello".to_string(),
+                audio_data: vec![0; bytes],
+            }],
+            priority,
+        }
+    }
+
+    fn ids(queue: &TTSQueue) -> Vec<String> {
+        queue.items
+            .iter()
+            .map(|item| item.request.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn full_queue_rejects_new_items() {
+        let mut queue = TTSQueue::new(2);
+        assert!(matches!(queue.add(item("a", TTSPriority::Normal, 1)), QueueAddResult::Added));
+        assert!(matches!(queue.add(item("b", TTSPriority::Normal, 1)), QueueAddResult::Added));
+        let result = queue.add(item("c", TTSPriority::Elevated, 1));
+        assert!(matches!(result, QueueAddResult::Rejected(QueueRejection::Full)));
+        assert_eq!(ids(&queue), ["a", "b"]);
+        assert_eq!(queue.memory_usage(), 2);
+    }
+
+    #[test]
+    fn elevated_items_play_first_in_order() {
+        let mut queue = TTSQueue::new(10);
+        queue.add(item("a", TTSPriority::Normal, 1));
+        queue.add(item("b", TTSPriority::Elevated, 1));
+        queue.add(item("c", TTSPriority::Normal, 1));
+        queue.add(item("d", TTSPriority::Elevated, 1));
+        assert_eq!(ids(&queue), ["b", "d", "a", "c"]);
+        assert_eq!(queue.pop().map(|item| item.request.id), Some("b".to_string()));
+        assert_eq!(queue.memory_usage(), 3);
+    }
+
+    #[test]
+    fn queue_full_reply_is_rate_limited() {
+        let mut notice = QueueFullNotice::new(Duration::from_secs(60));
+        assert_eq!(
+            notice.reply(5, 5).as_deref(),
+            Some("TTS queue is full (5/5), try again later")
+        );
+        assert_eq!(notice.reply(5, 5), None);
+        notice.set_cooldown(Duration::ZERO);
+        assert!(notice.reply(5, 5).is_some());
+    }
+
+    fn drop_oldest_queue(max_length: usize) -> TTSQueue {
+        let mut queue = TTSQueue::new(max_length);
+        queue.set_full_policy(QueueFullPolicy::DropOldest);
+        queue
+    }
+
+    #[test]
+    fn drop_oldest_drops_the_oldest_of_the_lowest_priority() {
+        let mut queue = drop_oldest_queue(3);
+        queue.add(item("a", TTSPriority::Elevated, 1));
+        queue.add(item("b", TTSPriority::Normal, 1));
+        queue.add(item("c", TTSPriority::Normal, 1));
+        let result = queue.add(item("d", TTSPriority::Normal, 1));
+        assert!(
+            matches!(result, QueueAddResult::DroppedOldest(dropped) if dropped.request.id == "b")
+        );
+        assert_eq!(ids(&queue), ["a", "c", "d"]);
+        assert_eq!(queue.memory_usage(), 3);
+    }
+
+    #[test]
+    fn drop_oldest_never_drops_higher_priority_items() {
+        let mut queue = drop_oldest_queue(2);
+        queue.add(item("a", TTSPriority::Elevated, 1));
+        queue.add(item("b", TTSPriority::Elevated, 1));
+        let result = queue.add(item("c", TTSPriority::Normal, 1));
+        assert!(matches!(result, QueueAddResult::Rejected(QueueRejection::Full)));
+        assert_eq!(ids(&queue), ["a", "b"]);
+    }
+
+    #[test]
+    fn memory_budget_counts_the_dropped_item() {
+        let mut queue = drop_oldest_queue(2);
+        queue.set_memory_budget(10);
+        queue.add(item("a", TTSPriority::Normal, 5));
+        queue.add(item("b", TTSPriority::Normal, 5));
+        // fits once "a" is dropped
+        let result = queue.add(item("c", TTSPriority::Normal, 5));
+        assert!(matches!(result, QueueAddResult::DroppedOldest(_)));
+        assert_eq!(ids(&queue), ["b", "c"]);
+        // too big even with "b" dropped, and nothing is dropped for it
+        let result = queue.add(item("d", TTSPriority::Normal, 6));
+        assert!(matches!(result, QueueAddResult::Rejected(QueueRejection::OverMemoryBudget)));
+        assert_eq!(ids(&queue), ["b", "c"]);
+        assert_eq!(queue.memory_usage(), 10);
+    }
+
+    #[test]
+    fn skip_flags_only_the_playing_message() {
+        let mut queue = TTSQueue::new(10);
+        queue.add(item("a", TTSPriority::Normal, 1));
+        queue.add(item("b", TTSPriority::Normal, 1));
+        // nothing has been popped for playback yet
+        assert!(!queue.skip());
+        assert!(!queue.get_skip_flag());
+        let playing = queue.pop().unwrap();
+        queue.set_currently_playing(Some(playing.request));
+        assert_eq!(queue.get_currently_playing().map(|request| request.id.as_str()), Some("a"));
+        assert!(queue.skip());
+        assert!(queue.get_skip_flag());
+        // the next message starts without the previous skip
+        let next = queue.pop().unwrap();
+        queue.set_currently_playing(Some(next.request));
+        assert!(!queue.get_skip_flag());
+        let ui = queue.get_all_with_current();
+        assert_eq!(ui.len(), 1);
+        assert!(ui[0].playing);
+        queue.set_currently_playing(None);
+        assert!(!queue.skip());
+    }
+}
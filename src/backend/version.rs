@@ -0,0 +1,29 @@
+use super::config::AppConfig;
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// shown once after upgrading, keep it to the highlights
+const WHATS_NEW: &[&str] = &[
+    "TTS queue with a configurable maximum length",
+    "Mention stripping, auto-pause and an error sound for TTS",
+    "Chat commands with cooldowns, permissions and {followage}/{accountage}",
+    "Spam suppression and greetings for regulars",
+    "Transcript export to JSON or CSV",
+    "Keyboard shortcuts for switching sections",
+];
+
+/// Compares the stored `last_run_version` with the running version, migrating the config
+/// and returning a one-time notice when the app was upgraded since the last run.
+pub fn check_upgrade(config: &mut AppConfig) -> Option<String> {
+    if config.last_run_version == CURRENT_VERSION {
+        return None;
+    }
+    // settings added since the last run are filled in by their serde defaults, so saving the
+    // config afterwards is all the migration needed so far
+    config.last_run_version = CURRENT_VERSION.to_string();
+    let mut notice = format!("Yambot was updated to {}. What's new:\n", CURRENT_VERSION);
+    for feature in WHATS_NEW {
+        notice.push_str(&format!("- {}\n", feature));
+    }
+    Some(notice)
+}
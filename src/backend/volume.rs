@@ -0,0 +1,99 @@
+use serde::{ Deserialize, Serialize };
+
+use super::commands::UserLevel;
+use super::config::AppConfig;
+
+// lowest level allowed to change the volume from chat
+pub const REQUIRED_LEVEL: UserLevel = UserLevel::Moderator;
+// loudest a role can make its sounds relative to the base volume
+pub const MAX_ROLE_MULTIPLIER: f64 = 2.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum VolumeTarget {
+    Tts,
+    Sfx,
+}
+
+impl VolumeTarget {
+    pub fn from_command(name: &str) -> Option<Self> {
+        match name {
+            "ttsvol" => Some(VolumeTarget::Tts),
+            "sfxvol" => Some(VolumeTarget::Sfx),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            VolumeTarget::Tts => "TTS",
+            VolumeTarget::Sfx => "SFX",
+        }
+    }
+
+    pub fn command(&self) -> &'static str {
+        match self {
+            VolumeTarget::Tts => "ttsvol",
+            VolumeTarget::Sfx => "sfxvol",
+        }
+    }
+
+    pub fn get(&self, config: &AppConfig) -> f64 {
+        match self {
+            VolumeTarget::Tts => config.tts.volume,
+            VolumeTarget::Sfx => config.sfx.volume,
+        }
+    }
+
+    pub fn set(&self, config: &mut AppConfig, volume: f64) {
+        match self {
+            VolumeTarget::Tts => config.set_tts_volume(volume),
+            VolumeTarget::Sfx => config.set_sfx_volume(volume),
+        }
+    }
+}
+
+/// Parses a volume argument such as "0.5", clamped to the 0-1 range.
+pub fn parse_volume(arg: &str) -> Option<f64> {
+    let volume: f64 = arg.parse().ok()?;
+    if !volume.is_finite() {
+        return None;
+    }
+    Some(volume.clamp(0.0, 1.0))
+}
+
+/// How loud sounds play relative to the SFX volume, by the role of the user who triggered them.
+/// Viewers and the broadcaster always play at the SFX volume.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RoleVolumes {
+    pub subs: f64,
+    pub vips: f64,
+    pub mods: f64,
+}
+
+impl Default for RoleVolumes {
+    fn default() -> Self {
+        Self {
+            subs: 1.0,
+            vips: 1.0,
+            mods: 1.0,
+        }
+    }
+}
+
+impl RoleVolumes {
+    pub fn multiplier(&self, level: UserLevel) -> f64 {
+        let multiplier = match level {
+            UserLevel::Subscriber => self.subs,
+            UserLevel::Vip => self.vips,
+            UserLevel::Moderator => self.mods,
+            UserLevel::Viewer | UserLevel::Broadcaster => 1.0,
+        };
+        if multiplier.is_finite() { multiplier.clamp(0.0, MAX_ROLE_MULTIPLIER) } else { 1.0 }
+    }
+
+    /// The volume a sound triggered at `level` plays at, `None` for sounds no user triggered.
+    pub fn effective_volume(&self, base: f64, level: Option<UserLevel>) -> f64 {
+        let multiplier = level.map_or(1.0, |level| self.multiplier(level));
+        (base * multiplier).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,66 @@
+use serde::{ Deserialize, Serialize };
+use std::collections::HashSet;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WelcomeConfig {
+    // reply to everyone's first message of the session
+    pub greet_first_time: bool,
+    // `{user}` is replaced with the chatter's name
+    pub message: String,
+    // never greeted, e.g. other bots; the channel owner is always left out
+    pub ignored_users: Vec<String>,
+}
+
+impl Default for WelcomeConfig {
+    fn default() -> Self {
+        Self {
+            greet_first_time: false,
+            message: "Welcome @{user}!".to_string(),
+            ignored_users: ["nightbot", "streamelements", "streamlabs", "moobot"]
+                .iter()
+                .map(|user| user.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl WelcomeConfig {
+    pub fn is_ignored(&self, username: &str) -> bool {
+        self.ignored_users.iter().any(|user| user.eq_ignore_ascii_case(username))
+    }
+}
+
+pub fn welcome_message(template: &str, username: &str) -> String {
+    template.replace("{user}", username)
+}
+
+/// Remembers who has chatted this session, so only first messages get a welcome.
+#[derive(Default)]
+pub struct FirstTimeGreeter {
+    seen: HashSet<String>,
+}
+
+impl FirstTimeGreeter {
+    /// The welcome for `username`'s message, if it's their first one. A first message that is a
+    /// command still counts as seen, without a welcome.
+    pub fn welcome_for(
+        &mut self,
+        username: &str,
+        channel: &str,
+        is_command: bool,
+        config: &WelcomeConfig
+    ) -> Option<String> {
+        if !self.seen.insert(username.to_lowercase()) {
+            return None;
+        }
+        if
+            !config.greet_first_time ||
+            is_command ||
+            username.eq_ignore_ascii_case(channel) ||
+            config.is_ignored(username)
+        {
+            return None;
+        }
+        Some(welcome_message(&config.message, username))
+    }
+}
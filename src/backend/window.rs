@@ -0,0 +1,69 @@
+use serde::{ Deserialize, Serialize };
+use std::fs;
+use std::path::PathBuf;
+use std::time::{ Duration, Instant };
+
+use super::config::app_dir;
+
+// how long the window has to stay put before its size and position are saved
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Size and position of the main window, restored on the next launch.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WindowGeometry {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+fn geometry_path() -> PathBuf {
+    app_dir().join("window.json")
+}
+
+pub fn load_geometry() -> Option<WindowGeometry> {
+    let content = fs::read_to_string(geometry_path()).ok()?;
+    let geometry: WindowGeometry = serde_json::from_str(&content).ok()?;
+    let values = [geometry.x, geometry.y, geometry.width, geometry.height];
+    (values.iter().all(|value| value.is_finite()) && geometry.width > 0.0 && geometry.height > 0.0)
+        .then_some(geometry)
+}
+
+fn save_geometry(geometry: &WindowGeometry) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(geometry_path(), serde_json::to_string(geometry)?)?;
+    Ok(())
+}
+
+/// Saves the window geometry once it has stopped changing, so dragging or resizing the window
+/// doesn't write on every frame.
+#[derive(Default)]
+pub struct GeometrySaver {
+    saved: Option<WindowGeometry>,
+    // latest geometry not saved yet, and when it last changed
+    pending: Option<(WindowGeometry, Instant)>,
+}
+
+impl GeometrySaver {
+    /// Called every frame with the current geometry.
+    pub fn update(&mut self, geometry: WindowGeometry) {
+        if self.saved == Some(geometry) {
+            self.pending = None;
+            return;
+        }
+        match self.pending {
+            Some((pending, changed_at)) if pending == geometry => {
+                if changed_at.elapsed() < SAVE_DELAY {
+                    return;
+                }
+                if let Err(e) = save_geometry(&geometry) {
+                    log::warn!("Could not save the window size: {}", e);
+                }
+                self.saved = Some(geometry);
+                self.pending = None;
+            }
+            _ => {
+                self.pending = Some((geometry, Instant::now()));
+            }
+        }
+    }
+}
@@ -1,24 +1,114 @@
-use backend::config::AppConfig;
+use backend::audio::{RepeatAction, SfxRoute, SfxTurn, SinkPool, SoundDeduper};
+use backend::automod::{Automod, AutomodAction, AutomodConfig};
+use backend::commands::{
+    Command, CommandContext, CommandExecution, CommandExecutor, CommandParser, CommandRegistry,
+    CommandResult, CooldownWatcher, ResponseTarget, ScheduledSteps, UserLevel,
+};
+use backend::config::{AppConfig, ConfigWatcher, SharedConfig};
+use backend::dashboard::DashboardContext;
+use backend::follows::FollowTracker;
+use backend::diagnostics::{ConnectionStatus, DiagnosticsReport};
+use backend::helix::HelixClient;
+use backend::identity::BotIdentity;
+use backend::limits::LongMessageAction;
+use backend::rate::MessageRateTracker;
+use backend::reconnect::Disconnect;
+use backend::regulars::{Regular, RegularGreeter};
+use backend::shoutout::ShoutoutConfig;
+use backend::sounds::SoundInfo;
+use backend::spam::{SpamCheck, SpamDetector};
+use backend::transcript::{Transcript, TranscriptEventKind};
+use backend::tts::moderation::TTSModeration;
+use backend::tts::queue::{
+    persisted_queue_path, QueueAddResult, QueueFullNotice, QueueFullPolicy, QueueRejection,
+    TTSPriority, TTSQueue, TTSQueueItem,
+};
+use backend::tts::{
+    AutoPause, ErrorSoundGate, LastLanguages, PlaybackTransitions, RetryPolicy, TTSAudioChunk,
+    TTSRequest, TTSService, TTSSource, UserCooldowns,
+};
+use backend::volume::VolumeTarget;
+use backend::welcome::FirstTimeGreeter;
 use eframe::egui::{self};
-use rodio::{Decoder, OutputStream};
+use rodio::cpal::traits::HostTrait;
+use rodio::{Decoder, DeviceTrait, OutputStream};
 use rodio::{OutputStreamHandle, Sink};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
-use std::sync::Arc;
+use std::io::{BufReader, Cursor};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
 use twitch_irc::login::StaticLoginCredentials;
 use twitch_irc::message::PrivmsgMessage;
 use twitch_irc::TwitchIRCClient;
 use twitch_irc::{ClientConfig, SecureTCPTransport};
-use ui::{BackendToFrontendMessage, FrontendToBackendMessage};
+use ui::{BackendToFrontendMessage, FrontendToBackendMessage, PermitedRoles};
 
 pub mod backend;
 pub mod ui;
-use log::{error, info};
+use log::{error, info, warn};
 
 const WINDOW_WIDTH: f32 = 800.0;
 const WINDOW_HEIGHT: f32 = 600.0;
+const MIN_WINDOW_WIDTH: f32 = 640.0;
+const MIN_WINDOW_HEIGHT: f32 = 480.0;
+
+// keeps us under Twitch's limit of 20 messages per 30 seconds
+const CHAT_SEND_INTERVAL: Duration = Duration::from_millis(1500);
+
+type TwitchClient = TwitchIRCClient<SecureTCPTransport, StaticLoginCredentials>;
+
+// sink of the TTS message currently playing, if any
+type PlayingTTS = Arc<Mutex<Option<Arc<Sink>>>>;
+
+/// Long-lived state shared by the backend tasks and every chat connection.
+#[derive(Clone)]
+struct BackendState {
+    config: SharedConfig,
+    tts_service: Arc<TTSService>,
+    tts_queue: Arc<Mutex<TTSQueue>>,
+    error_sound_gate: Arc<Mutex<ErrorSoundGate>>,
+    command_registry: Arc<RwLock<CommandRegistry>>,
+    stream_handle: Arc<OutputStreamHandle>,
+    sfx_sinks: Arc<Mutex<SinkPool>>,
+    sfx_deduper: Arc<Mutex<SoundDeduper>>,
+    playing_tts: PlayingTTS,
+    tts_moderation: Arc<RwLock<TTSModeration>>,
+    transcript: Arc<Mutex<Transcript>>,
+    connection_status: Arc<Mutex<ConnectionStatus>>,
+    // the connected channels, keyed by lowercase channel name
+    chat_connections: Arc<Mutex<HashMap<String, ChatConnection>>>,
+}
+
+/// What commands run from the UI need of a connected channel.
+#[derive(Clone)]
+struct ChatConnection {
+    chat_tx: tokio::sync::mpsc::Sender<String>,
+    // their delayed steps, cancelled when the connection ends
+    scheduled_steps: ScheduledSteps,
+}
+
+/// TTS state owned by a single chat connection.
+struct TTSContext {
+    // false for extra channels unless audio is shared, they then only answer commands
+    plays_audio: bool,
+    config: SharedConfig,
+    service: Arc<TTSService>,
+    queue: Arc<Mutex<TTSQueue>>,
+    queue_full_notice: QueueFullNotice,
+    error_sound_gate: Arc<Mutex<ErrorSoundGate>>,
+    sfx_sinks: Arc<Mutex<SinkPool>>,
+    sfx_deduper: Arc<Mutex<SoundDeduper>>,
+    playing_tts: PlayingTTS,
+    moderation: Arc<RwLock<TTSModeration>>,
+    transcript: Arc<Mutex<Transcript>>,
+    message_rate: MessageRateTracker,
+    auto_pause: AutoPause,
+    last_languages: LastLanguages,
+    user_cooldowns: UserCooldowns,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatMessage {
@@ -26,6 +116,45 @@ pub struct ChatMessage {
     pub message_text: String,
     pub badges: Vec<String>,
     pub username: String,
+    pub user_id: String,
+}
+
+impl ChatMessage {
+    fn has_badge(&self, name: &str) -> bool {
+        self.badges
+            .iter()
+            .any(|badge| badge.split('-').next() == Some(name))
+    }
+
+    fn is_permitted(&self, roles: &PermitedRoles) -> bool {
+        self.has_badge("broadcaster")
+            || (roles.mods && self.has_badge("moderator"))
+            || (roles.vips && self.has_badge("vip"))
+            || (roles.subs && (self.has_badge("subscriber") || self.has_badge("founder")))
+    }
+
+    /// Elevated roles jump ahead of the rest of the TTS queue, the broadcaster always does.
+    fn tts_priority(&self, elevated_roles: &PermitedRoles) -> TTSPriority {
+        if self.is_permitted(elevated_roles) {
+            TTSPriority::Elevated
+        } else {
+            TTSPriority::Normal
+        }
+    }
+
+    fn user_level(&self) -> UserLevel {
+        if self.has_badge("broadcaster") {
+            UserLevel::Broadcaster
+        } else if self.has_badge("moderator") {
+            UserLevel::Moderator
+        } else if self.has_badge("vip") {
+            UserLevel::Vip
+        } else if self.has_badge("subscriber") || self.has_badge("founder") {
+            UserLevel::Subscriber
+        } else {
+            UserLevel::Viewer
+        }
+    }
 }
 
 impl From<PrivmsgMessage> for ChatMessage {
@@ -40,6 +169,7 @@ impl From<PrivmsgMessage> for ChatMessage {
             message_text: privmsg.message_text,
             badges,
             username: privmsg.sender.login,
+            user_id: privmsg.sender.id,
         }
     }
 }
@@ -49,16 +179,128 @@ async fn main() {
     env_logger::init();
     let (backend_tx, frontend_rx) = tokio::sync::mpsc::channel(100);
     let (frontend_tx, backend_rx) = tokio::sync::mpsc::channel(100);
+    let geometry = backend::window::load_geometry();
+    let size = geometry.map_or([WINDOW_WIDTH, WINDOW_HEIGHT], |geometry| {
+        [
+            geometry.width.max(MIN_WINDOW_WIDTH),
+            geometry.height.max(MIN_WINDOW_HEIGHT),
+        ]
+    });
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size(size)
+        .with_min_inner_size([MIN_WINDOW_WIDTH, MIN_WINDOW_HEIGHT])
+        .with_resizable(true);
+    if let Some(geometry) = geometry {
+        viewport = viewport.with_position([geometry.x, geometry.y]);
+    }
     let native_options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([WINDOW_WIDTH, WINDOW_HEIGHT])
-            .with_resizable(false),
+        viewport,
         ..Default::default()
     };
     let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let config = backend::config::load_config();
+    let stream_handle = Arc::new(stream_handle);
+    if let Some(problem) = backend::config::check_config() {
+        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+            ui::LogLevel::ERROR,
+            problem,
+        ));
+    }
+    let shared_config =
+        SharedConfig::new(backend::config::load_config(), backend::config::config_path());
+    let mut config = shared_config.get();
+    if let Some(notice) = backend::version::check_upgrade(&mut config) {
+        save_config(&shared_config, &config, &backend_tx);
+        let _ = backend_tx.try_send(BackendToFrontendMessage::ReleaseNotice(notice));
+    }
+    let state = BackendState {
+        config: shared_config,
+        tts_service: Arc::new(TTSService::new(
+            config.tts.provider,
+            Duration::from_secs(config.tts.request_timeout),
+            config.tts.cache_size_mb * 1024 * 1024,
+        )),
+        tts_queue: Arc::new(Mutex::new({
+            let mut queue = TTSQueue::new(config.tts.max_queue_length);
+            queue.set_memory_budget(config.tts.queue_memory_budget_kb * 1024);
+            queue
+        })),
+        error_sound_gate: Arc::new(Mutex::new(ErrorSoundGate::default())),
+        command_registry: Arc::new(RwLock::new(CommandRegistry::new(
+            backend::commands::load_commands(),
+        ))),
+        sfx_sinks: Arc::new(Mutex::new(SinkPool::new(
+            stream_handle.clone(),
+            config.sfx.sink_pool_size,
+        ))),
+        stream_handle,
+        sfx_deduper: Arc::new(Mutex::new(SoundDeduper::default())),
+        playing_tts: Arc::new(Mutex::new(None)),
+        tts_moderation: Arc::new(RwLock::new(backend::tts::moderation::load_moderation())),
+        transcript: Arc::new(Mutex::new(Transcript::default())),
+        connection_status: Arc::new(Mutex::new(ConnectionStatus::default())),
+        chat_connections: Arc::new(Mutex::new(HashMap::new())),
+    };
+    state
+        .tts_service
+        .set_fallback_providers(&config.tts.fallback_providers);
+    state.tts_service.set_retry_policy(RetryPolicy {
+        max_retries: config.tts.synthesis_retries,
+        base_delay: Duration::from_millis(config.tts.retry_base_delay_ms),
+    });
+    if config.tts.persist_queue {
+        let path = persisted_queue_path();
+        let requests = TTSQueue::load_from_disk(&path).unwrap_or_else(|e| {
+            warn!("Could not restore the TTS queue from {}: {}", path.display(), e);
+            Vec::new()
+        });
+        state.tts_queue.lock().unwrap().set_persist_path(Some(path));
+        if !requests.is_empty() {
+            tokio::spawn(restore_tts_queue(requests, state.clone(), backend_tx.clone()));
+        }
+    }
+    let _ = backend_tx.try_send(BackendToFrontendMessage::TTSModerationUpdated(
+        state.tts_moderation.read().unwrap().clone(),
+    ));
+    let _ = backend_tx.try_send(BackendToFrontendMessage::CommandsUpdated(
+        state.command_registry.read().unwrap().commands(),
+    ));
+    prepare_sounds_directory(&config.sfx, &backend_tx);
+    let _ = backend_tx.try_send(BackendToFrontendMessage::SFXListUpdated(sound_list(&state)));
+    if config.sfx.validate_on_startup {
+        tokio::spawn(validate_sounds_on_startup(
+            backend::sounds::sounds_directory(&config.sfx),
+            backend_tx.clone(),
+        ));
+    }
+    if config.dashboard.enabled {
+        if config.dashboard.token.is_empty() {
+            create_dashboard_token(&state.config, &backend_tx);
+        }
+        let context = DashboardContext {
+            frontend_tx: frontend_tx.clone(),
+            tts_queue: state.tts_queue.clone(),
+            config: state.config.clone(),
+        };
+        let dashboard_config = state.config.read().dashboard.clone();
+        let backend_tx = backend_tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = backend::dashboard::serve(dashboard_config, context).await {
+                let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                    ui::LogLevel::ERROR,
+                    format!("Could not start the dashboard: {}", e),
+                ));
+            }
+        });
+    }
+    tokio::spawn(tts_player_task(state.clone(), backend_tx.clone()));
+    tokio::spawn(command_cooldown_task(
+        state.command_registry.clone(),
+        backend_tx.clone(),
+    ));
+    tokio::spawn(sounds_watch_task(state.clone(), backend_tx.clone()));
+    tokio::spawn(config_watch_task(state.config.clone(), backend_tx.clone()));
     tokio::spawn(async move {
-        handle_frontend_to_backend_messages(backend_rx, backend_tx.clone(), stream_handle).await;
+        handle_frontend_to_backend_messages(backend_rx, backend_tx.clone(), state).await;
     });
     info!("Starting chatbot");
     let _ = eframe::run_native(
@@ -77,33 +319,415 @@ async fn main() {
                 frontend_rx,
                 config.sfx,
                 config.tts,
+                config.ui,
+                config.safe_mode,
             )))
         }),
     )
     .map_err(|e| error!("Error: {:?}", e));
 }
 
-async fn handle_twitch_messages(channel_name: String) {
+/// Keeps the bot in `channel_name`'s chat, connecting again with a growing wait whenever the
+/// connection drops, for as long as auto reconnect is on and attempts are left.
+async fn chat_connection_task(
+    channel_name: String,
+    backend_tx: tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+    state: BackendState,
+) {
+    // a changed token or reconnect setting applies from the next connect
+    let config = state.config.read().chatbot.clone();
+    let mut attempt = 0;
+    loop {
+        let connected_at = Instant::now();
+        let disconnect = handle_twitch_messages(
+            channel_name.clone(),
+            config.auth_token.clone(),
+            backend_tx.clone(),
+            state.clone(),
+        )
+        .await;
+        if let Some(connection) = state.chat_connections.lock().unwrap().remove(&channel_name) {
+            connection.scheduled_steps.cancel();
+        }
+        warn!("Disconnected from {}: {}", channel_name, disconnect.reason());
+        let next_attempt = backend::reconnect::next_attempt(
+            &disconnect,
+            connected_at.elapsed(),
+            attempt,
+            config.auto_reconnect,
+            config.max_reconnect_attempts,
+        );
+        let Some(next_attempt) = next_attempt else {
+            let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                ui::LogLevel::ERROR,
+                format!("Lost the connection to {}: {}", channel_name, disconnect.reason()),
+            ));
+            // other channels may still be connected
+            if state.chat_connections.lock().unwrap().is_empty() {
+                let _ = backend_tx.try_send(BackendToFrontendMessage::ConnectionStateChanged(
+                    ui::ConnectionState::Disconnected,
+                ));
+            }
+            return;
+        };
+        attempt = next_attempt;
+        let delay = backend::reconnect::delay(attempt);
+        let attempts = match config.max_reconnect_attempts {
+            0 => attempt.to_string(),
+            max => format!("{} of {}", attempt, max),
+        };
+        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+            ui::LogLevel::WARN,
+            format!(
+                "Lost the connection to {}: {}, reconnecting in {}s (attempt {})",
+                channel_name,
+                disconnect.reason(),
+                delay.as_secs(),
+                attempts
+            ),
+        ));
+        let _ = backend_tx.try_send(BackendToFrontendMessage::ConnectionStateChanged(
+            ui::ConnectionState::Reconnecting,
+        ));
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Runs one connection to `channel_name`'s chat, returning why it ended. The client and every
+/// task spawned for the connection are dropped on return, closing it.
+async fn handle_twitch_messages(
+    channel_name: String,
+    auth_token: String,
+    backend_tx: tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+    state: BackendState,
+) -> Disconnect {
     // TODO: add messages to local db
     let mut messages: Vec<ChatMessage> = Vec::new();
-    let config: ClientConfig<StaticLoginCredentials> = ClientConfig::default();
-    let (mut incoming_messages, client) =
-        TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new(config);
-    client.join(channel_name.clone()).unwrap();
+    let connection_status = state.connection_status.clone();
+    let helix = match HelixClient::connect(&auth_token, &channel_name).await {
+        Ok(helix) => {
+            connection_status.lock().unwrap().scopes = Some(helix.scopes.clone());
+            Some(Arc::new(helix))
+        }
+        Err(e) => {
+            connection_status.lock().unwrap().last_error = Some(e.to_string());
+            let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                ui::LogLevel::WARN,
+                format!("Twitch API unavailable, API-backed variables disabled: {}", e),
+            ));
+            None
+        }
+    };
+    // the token decides which account the bot logs in as, without it assume the channel's own
+    let identity = Arc::new(Mutex::new(match &helix {
+        Some(helix) => BotIdentity::new(
+            helix.login.clone(),
+            Some(helix.user_id.clone()),
+            helix.user_id == helix.broadcaster_id,
+        ),
+        None => BotIdentity::new(channel_name.clone(), None, true),
+    }));
+    let bot_login = identity.lock().unwrap().login().to_string();
+    if !identity.lock().unwrap().is_shared_account() {
+        info!("Logging in as {} to chat in {}", bot_login, channel_name);
+    }
+    let config = if auth_token.is_empty() {
+        // anonymous logins can read chat but not send to it
+        warn!("No auth token set, joining {} read-only", channel_name);
+        ClientConfig::default()
+    } else {
+        ClientConfig::new_simple(StaticLoginCredentials::new(
+            bot_login.clone(),
+            Some(auth_token.trim_start_matches("oauth:").to_string()),
+        ))
+    };
+    let (mut incoming_messages, client) = TwitchClient::new(config);
+    if let Err(e) = client.join(channel_name.clone()) {
+        return Disconnect::JoinFailed(e.to_string());
+    }
+    // everything spawned for this connection, aborted together when the connection task is dropped
+    let mut tasks = JoinSet::new();
+    let (chat_tx, chat_rx) = tokio::sync::mpsc::channel(100);
+    state.chat_connections.lock().unwrap().insert(
+        channel_name.clone(),
+        ChatConnection {
+            chat_tx: chat_tx.clone(),
+            scheduled_steps: ScheduledSteps::default(),
+        },
+    );
+    tasks.spawn(chat_sender_task(
+        client.clone(),
+        channel_name.clone(),
+        chat_rx,
+        identity.clone(),
+    ));
+    let chatbot_config = state.config.read().chatbot.clone();
+    let plays_audio = chatbot_config.share_audio_across_channels
+        || channel_name.eq_ignore_ascii_case(&chatbot_config.channel_name);
+    if let Some(helix) = helix.as_ref().filter(|_| plays_audio) {
+        tasks.spawn(follow_alert_task(
+            helix.clone(),
+            state.config.clone(),
+            state.sfx_sinks.clone(),
+            state.playing_tts.clone(),
+            state.transcript.clone(),
+            backend_tx.clone(),
+        ));
+    }
+    let command_parser = CommandParser::new(&chatbot_config.command_prefix);
+    let mut spam_detector = SpamDetector::default();
+    let mut regular_greeter = RegularGreeter::default();
+    let mut automod = Automod::default();
+    // a new one per connection, so everyone gets welcomed again after reconnecting
+    let mut first_time_greeter = FirstTimeGreeter::default();
+    let transcript = state.transcript.clone();
+    let command_registry = state.command_registry.clone();
+    let tts_config = state.config.read().tts.clone();
+    let mut tts = TTSContext {
+        plays_audio,
+        config: state.config.clone(),
+        service: state.tts_service,
+        queue: state.tts_queue,
+        queue_full_notice: QueueFullNotice::new(Duration::from_secs(
+            tts_config.queue_full_reply_cooldown,
+        )),
+        error_sound_gate: state.error_sound_gate,
+        sfx_sinks: state.sfx_sinks,
+        sfx_deduper: state.sfx_deduper,
+        playing_tts: state.playing_tts,
+        moderation: state.tts_moderation,
+        transcript: state.transcript,
+        message_rate: MessageRateTracker::new(Duration::from_secs(tts_config.auto_pause_window)),
+        auto_pause: AutoPause::default(),
+        last_languages: LastLanguages::load(),
+        user_cooldowns: UserCooldowns::new(tts_config.user_cooldown),
+    };
 
-    while let Some(message) = incoming_messages.recv().await {
+    let disconnect = loop {
+        let message = match backend::reconnect::next_message(
+            &mut incoming_messages,
+            backend::reconnect::SILENCE_TIMEOUT,
+        )
+        .await
+        {
+            Ok(message) => message,
+            Err(disconnect) => break disconnect,
+        };
         match message {
             twitch_irc::message::ServerMessage::Privmsg(privmsg) => {
-                let chat_message: ChatMessage = privmsg.into();
+                let mut chat_message: ChatMessage = privmsg.into();
                 println!("Message: {:?}", chat_message);
+                if identity.lock().unwrap().is_own_message(
+                    &chat_message.user_id,
+                    &chat_message.username,
+                    &chat_message.message_text,
+                ) {
+                    continue;
+                }
+                transcript.lock().unwrap().record(
+                    TranscriptEventKind::Chat,
+                    &chat_message.username,
+                    &chat_message.message_text,
+                );
+                // one copy of the config for everything below, rather than one per check
+                let config = tts.config.get();
+                // runs in safe mode too, which is when it's needed most
+                let automod_config = &config.automod;
+                if !chat_message.is_permitted(&automod_config.exempt_roles) {
+                    if let Some(rule) = automod.check(&chat_message.message_text, automod_config)
+                    {
+                        let removed = apply_automod(
+                            rule,
+                            &chat_message,
+                            automod_config,
+                            helix.clone(),
+                            &mut tasks,
+                            &backend_tx,
+                        );
+                        if removed {
+                            messages.push(chat_message);
+                            continue;
+                        }
+                    }
+                }
+                let chatbot_config = &config.chatbot;
+                match backend::limits::limit_length(
+                    &chat_message.message_text,
+                    chatbot_config.max_message_length,
+                    chatbot_config.long_message_action,
+                ) {
+                    Some(text) => chat_message.message_text = text,
+                    None => {
+                        info!(
+                            "Ignoring message from {} longer than {} characters",
+                            chat_message.username, chatbot_config.max_message_length
+                        );
+                        messages.push(chat_message);
+                        continue;
+                    }
+                }
+                if let Some(parsed) = command_parser.parse(&chat_message.message_text) {
+                    if parsed.name == backend::safe_mode::SAFE_MODE_COMMAND {
+                        handle_safe_mode_command(
+                            &parsed.args,
+                            command_parser.prefix(),
+                            &chat_message,
+                            &chat_tx,
+                            &tts,
+                            &backend_tx,
+                        );
+                        messages.push(chat_message);
+                        continue;
+                    }
+                }
+                // the message is still shown, nothing reacts to it
+                if config.safe_mode {
+                    messages.push(chat_message);
+                    continue;
+                }
+                while tasks.try_join_next().is_some() {}
+                update_tts_auto_pause(&mut tts, &config.tts, &backend_tx);
+                let spam_config = &config.spam;
+                match spam_detector.check(
+                    &chat_message.username,
+                    &chat_message.message_text,
+                    spam_config,
+                ) {
+                    SpamCheck::Allowed => {}
+                    SpamCheck::Suppressed { newly } => {
+                        if newly {
+                            let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                                ui::LogLevel::WARN,
+                                format!(
+                                    "Suppressing repeated message for {}s: {}",
+                                    spam_config.suppression_secs, chat_message.message_text
+                                ),
+                            ));
+                        }
+                        messages.push(chat_message);
+                        continue;
+                    }
+                }
+                if let Some(regular) =
+                    regular_greeter.greeting_for(&chat_message.username, &config.regulars)
+                {
+                    greet_regular(regular, &chat_message, &config, &tts, &backend_tx);
+                }
+                let parsed = command_parser.parse_with_patterns(
+                    &chat_message.message_text,
+                    &command_registry.read().unwrap(),
+                );
+                if let Some(welcome) = first_time_greeter.welcome_for(
+                    &chat_message.username,
+                    &channel_name,
+                    parsed.is_some(),
+                    &config.welcome,
+                ) {
+                    let _ = chat_tx.try_send(welcome);
+                }
+                if let Some(parsed) = parsed {
+                    if parsed.name == "tts" {
+                        handle_tts_language_command(
+                            &parsed.args,
+                            &chat_message,
+                            &chat_tx,
+                            &tts.config,
+                            &backend_tx,
+                        );
+                    } else if parsed.name == backend::shoutout::SHOUTOUT_COMMAND {
+                        handle_shoutout_command(
+                            &parsed.args,
+                            &chat_message,
+                            &config.shoutout,
+                            &chat_tx,
+                            helix.clone(),
+                            &mut tasks,
+                        );
+                    } else if parsed.name == backend::helix::UPTIME_COMMAND {
+                        handle_uptime_command(&chat_tx, helix.clone(), &mut tasks);
+                    } else if is_random_sound_command(&parsed.name, &config.sfx) {
+                        handle_random_sound_command(&chat_message, &tts, &config.sfx);
+                    } else if let Some(target) = VolumeTarget::from_command(&parsed.name) {
+                        handle_volume_command(
+                            target,
+                            &parsed.args,
+                            command_parser.prefix(),
+                            &chat_message,
+                            &chat_tx,
+                            &tts.config,
+                            &backend_tx,
+                        );
+                    } else if !backend::tts::languages::is_supported(&parsed.name) {
+                        let context = CommandContext {
+                            username: chat_message.username.clone(),
+                            user_id: chat_message.user_id.clone(),
+                            channel: channel_name.clone(),
+                            args: parsed.args,
+                            user_level: chat_message.user_level(),
+                            bypass_cooldown: false,
+                        };
+                        let execution = handle_command(
+                            &parsed.name,
+                            &context,
+                            &command_registry,
+                            &chat_tx,
+                            &mut tasks,
+                            helix.clone(),
+                        );
+                        if let Some(text) = execution.transcript_text(command_parser.prefix()) {
+                            transcript.lock().unwrap().record(
+                                TranscriptEventKind::Command,
+                                &context.username,
+                                &text,
+                            );
+                        }
+                        let _ = backend_tx
+                            .try_send(BackendToFrontendMessage::CommandExecuted(execution));
+                    }
+                }
+                handle_tts_message(
+                    &chat_message,
+                    command_parser.prefix(),
+                    &chat_tx,
+                    &mut tts,
+                    &config.tts,
+                    &backend_tx,
+                )
+                .await;
                 messages.push(chat_message);
             }
             twitch_irc::message::ServerMessage::Join(join_msg) => {
                 println!("User joined: {}", join_msg.user_login);
+                if join_msg.user_login.eq_ignore_ascii_case(&bot_login) {
+                    let _ = backend_tx.try_send(BackendToFrontendMessage::ConnectionStateChanged(
+                        ui::ConnectionState::Connected,
+                    ));
+                }
             }
             twitch_irc::message::ServerMessage::Part(part_msg) => {
                 println!("User left: {}", part_msg.user_login);
             }
+            twitch_irc::message::ServerMessage::Notice(notice) => {
+                // e.g. failed logins, which otherwise only show up as a silent connection
+                warn!("Notice: {}", notice.message_text);
+                connection_status.lock().unwrap().last_error = Some(notice.message_text.clone());
+                if backend::reconnect::is_login_failure(&notice.message_text) {
+                    break Disconnect::LoginFailed(notice.message_text);
+                }
+            }
+            twitch_irc::message::ServerMessage::Reconnect(_) => {
+                // chat runs over IRC rather than EventSub, so there is no new URL to follow,
+                // the connection is dropped and opened again
+                info!("Twitch asked the bot to reconnect");
+                break Disconnect::ReconnectRequested;
+            }
+            twitch_irc::message::ServerMessage::UserNotice(notice) => {
+                if let twitch_irc::message::UserNoticeEvent::Raid { viewer_count, .. } =
+                    notice.event
+                {
+                    handle_raid(&notice.sender.login, viewer_count, &chat_tx, &tts, &backend_tx);
+                }
+            }
             twitch_irc::message::ServerMessage::Whisper(whisper_message) => {
                 println!(
                     "User {}, whispered message {}",
@@ -114,25 +738,800 @@ async fn handle_twitch_messages(channel_name: String) {
                 println!("Received other message: {:?}", message);
             }
         }
+    };
+    tts.last_languages.flush();
+    disconnect
+}
+
+/// Runs a command from chat and returns what happened, for the UI's recent runs.
+fn handle_command(
+    name: &str,
+    context: &CommandContext,
+    command_registry: &Arc<RwLock<CommandRegistry>>,
+    chat_tx: &tokio::sync::mpsc::Sender<String>,
+    tasks: &mut JoinSet<()>,
+    helix: Option<Arc<HelixClient>>,
+) -> CommandExecution {
+    let mut registry = command_registry.write().unwrap();
+    let result = CommandExecutor::execute(&mut registry, name, context);
+    let execution = CommandExecution::new(name, context, &result);
+    match result {
+        CommandResult::Success(response) => {
+            if let Some(response) = response {
+                send_command_response(response, context, chat_tx, tasks, helix);
+            }
+            if let Some(command) = registry.get(name) {
+                backend::commands::schedule_steps(&command.steps, chat_tx, tasks);
+            }
+        }
+        CommandResult::OnCooldown(remaining) => {
+            info!("Command {} is on cooldown for {:?}", name, remaining);
+        }
+        CommandResult::PermissionDenied => {
+            info!("{} is not permitted to use command {}", context.username, name);
+        }
+        CommandResult::NotFound => {}
+    }
+    execution
+}
+
+/// Sends a command's response to chat, as a whisper or as an announcement, filling in the
+/// Twitch API variables first when it has any.
+fn send_command_response(
+    response: String,
+    context: &CommandContext,
+    chat_tx: &tokio::sync::mpsc::Sender<String>,
+    tasks: &mut JoinSet<()>,
+    helix: Option<Arc<HelixClient>>,
+) {
+    let (target, text) = ResponseTarget::parse(&response);
+    if target == ResponseTarget::Chat && !backend::helix::has_user_variables(text) {
+        let _ = chat_tx.try_send(response);
+        return;
+    }
+    let text = text.to_string();
+    let chat_tx = chat_tx.clone();
+    let username = context.username.clone();
+    let user_id = context.user_id.clone();
+    tasks.spawn(async move {
+        let text =
+            backend::helix::resolve_user_variables(helix.as_deref(), &text, &username).await;
+        let result = match (target, &helix) {
+            (ResponseTarget::Chat, _) => {
+                let _ = chat_tx.send(text).await;
+                return;
+            }
+            (_, None) => Err("the Twitch API is unavailable".into()),
+            (ResponseTarget::Whisper, Some(helix)) => helix.send_whisper(&user_id, &text).await,
+            (ResponseTarget::Announcement(color), Some(helix)) => {
+                helix.send_announcement(&text, color).await
+            }
+        };
+        if let Err(e) = result {
+            error!("Could not send the command response to {}: {}", username, e);
+        }
+    });
+}
+
+/// Handles `!tts lang <code> on|off`, enabling or disabling a TTS language from chat.
+fn handle_tts_language_command(
+    args: &[String],
+    chat_message: &ChatMessage,
+    chat_tx: &tokio::sync::mpsc::Sender<String>,
+    shared_config: &SharedConfig,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    if chat_message.user_level() < UserLevel::Moderator {
+        info!("{} is not permitted to use command tts", chat_message.username);
+        return;
+    }
+    let (code, enabled) = match backend::tts::languages::parse_toggle(args) {
+        Ok(toggle) => toggle,
+        Err(reply) => {
+            let _ = chat_tx.try_send(reply);
+            return;
+        }
+    };
+    let mut config = shared_config.get();
+    backend::tts::languages::set_enabled(&mut config.tts, code, enabled);
+    save_config(shared_config, &config, backend_tx);
+    let state = if enabled { "enabled" } else { "disabled" };
+    let _ = chat_tx.try_send(format!("TTS language {} {}", code, state));
+    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+        ui::LogLevel::INFO,
+        format!("{} {} TTS language {}", chat_message.username, state, code),
+    ));
+    let _ = backend_tx.try_send(BackendToFrontendMessage::ConfigReloaded(config));
+}
+
+/// Handles `!safemode on|off`, replying with the current state when no argument is given.
+fn handle_safe_mode_command(
+    args: &[String],
+    command_prefix: &str,
+    chat_message: &ChatMessage,
+    chat_tx: &tokio::sync::mpsc::Sender<String>,
+    tts: &TTSContext,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    if chat_message.user_level() < backend::safe_mode::REQUIRED_LEVEL {
+        info!(
+            "{} is not permitted to use command {}",
+            chat_message.username,
+            backend::safe_mode::SAFE_MODE_COMMAND
+        );
+        return;
+    }
+    let Some(arg) = args.first() else {
+        let enabled = tts.config.read().safe_mode;
+        let _ = chat_tx.try_send(format!(
+            "Safe mode is {}",
+            backend::safe_mode::label(enabled)
+        ));
+        return;
+    };
+    let Some(enabled) = backend::safe_mode::parse_state(arg) else {
+        let _ = chat_tx.try_send(format!(
+            "Usage: {}{} on|off",
+            command_prefix,
+            backend::safe_mode::SAFE_MODE_COMMAND
+        ));
+        return;
+    };
+    set_safe_mode(
+        enabled,
+        &tts.config,
+        &tts.queue,
+        &tts.playing_tts,
+        &tts.sfx_sinks,
+        backend_tx,
+    );
+    let _ = chat_tx.try_send(format!(
+        "Safe mode {}",
+        backend::safe_mode::label(enabled)
+    ));
+}
+
+/// Generates the dashboard's API token the first time it's enabled and tells the user how to
+/// open the dashboard with it.
+fn create_dashboard_token(
+    shared_config: &SharedConfig,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let mut config = shared_config.get();
+    match backend::dashboard::generate_token() {
+        Ok(token) => config.dashboard.token = token,
+        Err(e) => {
+            let _ = backend_tx
+                .try_send(BackendToFrontendMessage::CreateLog(ui::LogLevel::ERROR, e));
+            return;
+        }
+    }
+    save_config(shared_config, &config, backend_tx);
+    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+        ui::LogLevel::INFO,
+        format!(
+            "Dashboard token created, open http://{}/?token={}",
+            config.dashboard.address, config.dashboard.token
+        ),
+    ));
+}
+
+/// Updates the shared config and writes it, logging in the UI when writing fails.
+fn save_config(
+    shared_config: &SharedConfig,
+    config: &AppConfig,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    if let Err(e) = shared_config.save(config) {
+        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(ui::LogLevel::ERROR, e));
+    }
+}
+
+/// Saves the safe mode flag, stopping the TTS message and the sounds that are playing when it
+/// turns on.
+fn set_safe_mode(
+    enabled: bool,
+    shared_config: &SharedConfig,
+    tts_queue: &Mutex<TTSQueue>,
+    playing_tts: &PlayingTTS,
+    sfx_sinks: &Mutex<SinkPool>,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let mut config = shared_config.get();
+    config.safe_mode = enabled;
+    save_config(shared_config, &config, backend_tx);
+    if enabled {
+        if tts_queue.lock().unwrap().skip() {
+            if let Some(sink) = playing_tts.lock().unwrap().as_ref() {
+                sink.stop();
+            }
+        }
+        sfx_sinks.lock().unwrap().stop_all();
     }
+    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+        ui::LogLevel::WARN,
+        format!("Safe mode {}", backend::safe_mode::label(enabled)),
+    ));
+    let _ = backend_tx.try_send(BackendToFrontendMessage::ConfigReloaded(config));
 }
+
+/// Handles `!uptime`, replying with how long the stream has been live.
+fn handle_uptime_command(
+    chat_tx: &tokio::sync::mpsc::Sender<String>,
+    helix: Option<Arc<HelixClient>>,
+    tasks: &mut JoinSet<()>,
+) {
+    let Some(helix) = helix else {
+        info!("Ignoring !uptime, the Twitch API is unavailable");
+        return;
+    };
+    let chat_tx = chat_tx.clone();
+    tasks.spawn(async move {
+        let reply = match helix.get_stream_uptime().await {
+            Ok(Some(uptime)) => format!("Live for {}", backend::helix::format_uptime(uptime)),
+            Ok(None) => "Stream is offline".to_string(),
+            Err(e) => {
+                warn!("Could not look up the stream uptime: {}", e);
+                return;
+            }
+        };
+        let _ = chat_tx.send(reply).await;
+    });
+}
+
+/// Handles `!so <user>`, posting the shoutout message and giving a Twitch shoutout when it's on.
+/// The message goes out even when Twitch refuses the shoutout, e.g. because of its cooldowns.
+fn handle_shoutout_command(
+    args: &[String],
+    chat_message: &ChatMessage,
+    config: &ShoutoutConfig,
+    chat_tx: &tokio::sync::mpsc::Sender<String>,
+    helix: Option<Arc<HelixClient>>,
+    tasks: &mut JoinSet<()>,
+) {
+    if chat_message.user_level() < backend::shoutout::REQUIRED_LEVEL {
+        info!(
+            "{} is not permitted to use command {}",
+            chat_message.username,
+            backend::shoutout::SHOUTOUT_COMMAND
+        );
+        return;
+    }
+    let Some(target) = backend::shoutout::parse_target(args) else {
+        return;
+    };
+    let config = config.clone();
+    let chat_tx = chat_tx.clone();
+    tasks.spawn(async move {
+        let user = match &helix {
+            Some(helix) => helix.get_user(&target).await.unwrap_or_else(|e| {
+                warn!("Could not look up {} for a shoutout: {}", target, e);
+                None
+            }),
+            None => None,
+        };
+        let game = match (&helix, &user) {
+            (Some(helix), Some(user)) => helix.get_channel_game(&user.id).await.ok().flatten(),
+            _ => None,
+        };
+        let message = backend::shoutout::shoutout_message(
+            &config.message,
+            &target,
+            game.as_deref().unwrap_or("something"),
+        );
+        let _ = chat_tx.send(message).await;
+        if let (true, Some(helix), Some(user)) = (config.use_twitch_shoutout, &helix, &user) {
+            if let Err(e) = helix.shoutout(&user.id).await {
+                warn!("Twitch shoutout for {} failed, only sent the message: {}", target, e);
+            }
+        }
+    });
+}
+
+/// Shows or sets the TTS/SFX volume from chat, for moderators and the broadcaster.
+fn handle_volume_command(
+    target: VolumeTarget,
+    args: &[String],
+    command_prefix: &str,
+    chat_message: &ChatMessage,
+    chat_tx: &tokio::sync::mpsc::Sender<String>,
+    shared_config: &SharedConfig,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    if chat_message.user_level() < backend::volume::REQUIRED_LEVEL {
+        info!(
+            "{} is not permitted to use command {}",
+            chat_message.username,
+            target.command()
+        );
+        return;
+    }
+    let mut config = shared_config.get();
+    let Some(arg) = args.first() else {
+        let _ = chat_tx.try_send(format!(
+            "{} volume is {:.2}",
+            target.label(),
+            target.get(&config)
+        ));
+        return;
+    };
+    let Some(volume) = backend::volume::parse_volume(arg) else {
+        let _ = chat_tx.try_send(format!(
+            "Usage: {}{} <0-1>",
+            command_prefix,
+            target.command()
+        ));
+        return;
+    };
+    target.set(&mut config, volume);
+    save_config(shared_config, &config, backend_tx);
+    let _ = chat_tx.try_send(format!("{} volume set to {:.2}", target.label(), volume));
+    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+        ui::LogLevel::INFO,
+        format!(
+            "{} set the {} volume to {:.2}",
+            chat_message.username,
+            target.label(),
+            volume
+        ),
+    ));
+    let _ = backend_tx.try_send(BackendToFrontendMessage::ConfigReloaded(config));
+}
+
+fn is_random_sound_command(name: &str, config: &ui::Config) -> bool {
+    let command = config.random_command.trim();
+    let command = if command.is_empty() {
+        backend::sounds::DEFAULT_RANDOM_SOUND_COMMAND
+    } else {
+        command
+    };
+    name.eq_ignore_ascii_case(command)
+}
+
+/// Handles `!random`, playing a sound picked by its configured weight.
+fn handle_random_sound_command(
+    chat_message: &ChatMessage,
+    tts: &TTSContext,
+    config: &ui::Config,
+) {
+    if !tts.plays_audio || !config.enabled || !chat_message.is_permitted(&config.permited_roles) {
+        return;
+    }
+    let sounds = backend::sounds::random_sound_candidates(
+        backend::sounds::list_sounds(&backend::sounds::sounds_directory(config)),
+        &config.random_ignored,
+    );
+    let Some(sound) =
+        backend::sounds::weighted_choice(&sounds, &config.sound_weights, fastrand::f64())
+    else {
+        info!("No sound to pick for {}", chat_message.username);
+        return;
+    };
+    tts.transcript.lock().unwrap().record(
+        TranscriptEventKind::Sound,
+        &chat_message.username,
+        sound,
+    );
+    tokio::spawn(play_sfx(
+        sound.to_string(),
+        Some(chat_message.user_level()),
+        tts.config.clone(),
+        tts.sfx_sinks.clone(),
+        tts.playing_tts.clone(),
+    ));
+}
+
+fn update_tts_auto_pause(
+    tts: &mut TTSContext,
+    config: &ui::TTSConfig,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    tts.message_rate
+        .set_window(Duration::from_secs(config.auto_pause_window));
+    let message_count = tts.message_rate.record();
+    if let Some(paused) = tts.auto_pause.update(message_count, config) {
+        let message = if paused {
+            format!(
+                "TTS paused, chat is too busy ({} messages in {}s)",
+                message_count, config.auto_pause_window
+            )
+        } else {
+            "TTS resumed, chat has calmed down".to_string()
+        };
+        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+            ui::LogLevel::WARN,
+            message,
+        ));
+    }
+}
+
+/// Turns a `<command_prefix><language> <text>` chat message, or `<same_language_prefix> <text>`
+/// for the user's last language, into a TTS request, replying in chat when the queue is full.
+async fn handle_tts_message(
+    chat_message: &ChatMessage,
+    command_prefix: &str,
+    chat_tx: &tokio::sync::mpsc::Sender<String>,
+    tts: &mut TTSContext,
+    config: &ui::TTSConfig,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    if !tts.plays_audio {
+        return;
+    }
+    let message = chat_message.message_text.as_str();
+    let same_language_prefix = config.same_language_prefix.as_str();
+    let (language, text) = match message.strip_prefix(same_language_prefix) {
+        Some(text) if !same_language_prefix.is_empty() && text.starts_with(' ') => (
+            tts.last_languages
+                .get_or(&chat_message.username, &config.default_language)
+                .to_string(),
+            text,
+        ),
+        _ if config.auto_detect && !message.starts_with(command_prefix) => (
+            backend::tts::languages::detect_language(message, &config.default_language),
+            message,
+        ),
+        _ => {
+            let Some((language, text)) = message
+                .strip_prefix(command_prefix)
+                .and_then(|message| message.split_once(' '))
+            else {
+                return;
+            };
+            (language.to_string(), text)
+        }
+    };
+    let mut text = text.trim().to_string();
+    if text.is_empty() || !backend::tts::languages::is_supported(&language) {
+        return;
+    }
+    if !config.enabled
+        || !backend::tts::languages::is_enabled(config, &language)
+        || tts.auto_pause.is_paused()
+        || !chat_message.is_permitted(&config.permited_roles)
+    {
+        return;
+    }
+    {
+        let moderation = tts.moderation.read().unwrap();
+        if moderation.is_ignored(&chat_message.username) {
+            return;
+        }
+        match moderation.filter_text(
+            &text,
+            config.banned_phrase_match,
+            config.banned_phrase_action,
+        ) {
+            Some(filtered) => text = filtered,
+            None => {
+                let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                    ui::LogLevel::WARN,
+                    format!(
+                        "Skipped TTS from {}, message contains a banned phrase",
+                        chat_message.username
+                    ),
+                ));
+                return;
+            }
+        }
+    }
+    let text = backend::tts::sanitize(&text, config);
+    let Some(text) =
+        backend::limits::limit_length_at_word(&text, config.max_message_length, config.on_overflow)
+    else {
+        info!(
+            "Skipped TTS from {}, message is over {} characters",
+            chat_message.username, config.max_message_length
+        );
+        return;
+    };
+    if text.is_empty() || text.chars().count() < config.min_message_length {
+        return;
+    }
+    tts.user_cooldowns.set_user_cooldown(config.user_cooldown);
+    if chat_message.user_level() != UserLevel::Broadcaster
+        && !tts.user_cooldowns.try_start(&chat_message.username)
+    {
+        info!("Skipped TTS from {}, user is on cooldown", chat_message.username);
+        return;
+    }
+
+    let rejected_length = {
+        let mut queue = tts.queue.lock().unwrap();
+        queue.set_max_length(config.max_queue_length);
+        if queue.is_full() && config.queue_full_policy == QueueFullPolicy::Reject {
+            Some(queue.len())
+        } else {
+            None
+        }
+    };
+    if let Some(queue_length) = rejected_length {
+        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+            ui::LogLevel::WARN,
+            format!(
+                "TTS queue is full, rejected message from {}",
+                chat_message.username
+            ),
+        ));
+        tts.queue_full_notice
+            .set_cooldown(Duration::from_secs(config.queue_full_reply_cooldown));
+        if let Some(reply) = tts
+            .queue_full_notice
+            .reply(queue_length, config.max_queue_length)
+        {
+            let _ = chat_tx.try_send(reply);
+        }
+        return;
+    }
+
+    tts.last_languages
+        .record(&chat_message.username, &language, config.remembered_languages);
+    tts.last_languages.save();
+    let text = if config.read_username {
+        backend::tts::announce_username(&config.username_template, &chat_message.username, &text)
+    } else {
+        text
+    };
+    let request = TTSRequest {
+        id: chat_message.message_id.clone(),
+        username: chat_message.username.clone(),
+        language,
+        text,
+        source: TTSSource::Chat,
+    };
+    let priority = chat_message.tts_priority(&config.elevated_roles);
+    spawn_tts_synthesis(request, priority, tts, backend_tx);
+}
+
+/// Synthesizes a request in the background and queues the audio for playback.
+fn spawn_tts_synthesis(
+    request: TTSRequest,
+    priority: TTSPriority,
+    tts: &TTSContext,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let shared_config = tts.config.clone();
+    let tts_service = tts.service.clone();
+    let tts_queue = tts.queue.clone();
+    let error_sound_gate = tts.error_sound_gate.clone();
+    let sfx_sinks = tts.sfx_sinks.clone();
+    let transcript = tts.transcript.clone();
+    let backend_tx = backend_tx.clone();
+    tokio::spawn(async move {
+        let config = shared_config.read().tts.clone();
+        match tts_service
+            .generate_tts(&request, config.max_chunks_per_message)
+            .await
+        {
+            Ok(audio_chunks) => {
+                let mut queue = tts_queue.lock().unwrap();
+                queue.set_memory_budget(config.queue_memory_budget_kb * 1024);
+                queue.set_full_policy(config.queue_full_policy);
+                let username = request.username.clone();
+                let log = match queue.add(TTSQueueItem {
+                    request,
+                    audio_chunks,
+                    priority,
+                }) {
+                    QueueAddResult::Added => None,
+                    QueueAddResult::DroppedOldest(dropped) => Some(format!(
+                        "TTS queue is full, dropped the oldest message from {}",
+                        dropped.request.username
+                    )),
+                    QueueAddResult::Rejected(QueueRejection::Full) => Some(format!(
+                        "TTS queue is full, rejected message from {}",
+                        username
+                    )),
+                    QueueAddResult::Rejected(QueueRejection::OverMemoryBudget) => Some(format!(
+                        "TTS queue is over its memory budget of {} KB, rejected message from {}",
+                        config.queue_memory_budget_kb, username
+                    )),
+                };
+                if let Some(log) = log {
+                    warn!("{}", log);
+                    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::WARN,
+                        log,
+                    ));
+                }
+                let _ = backend_tx.try_send(BackendToFrontendMessage::TTSQueueUpdated(
+                    queue.get_all_with_current(),
+                ));
+            }
+            Err(e) => {
+                error!("Failed to generate TTS: {}", e);
+                let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                    ui::LogLevel::ERROR,
+                    format!("Failed to generate TTS for {}: {}", request.username, e),
+                ));
+                if error_sound_gate.lock().unwrap().should_play(&config) {
+                    transcript.lock().unwrap().record(
+                        TranscriptEventKind::Sound,
+                        &request.username,
+                        &config.error_sound,
+                    );
+                    play_sound(config.error_sound, &shared_config, sfx_sinks).await;
+                }
+            }
+        }
+    });
+}
+
+/// Deletes the message or times its sender out for breaking automod `rule`, logging either way.
+/// Returns whether the message is being removed, in which case nothing else should react to it.
+fn apply_automod(
+    rule: &str,
+    chat_message: &ChatMessage,
+    config: &AutomodConfig,
+    helix: Option<Arc<HelixClient>>,
+    tasks: &mut JoinSet<()>,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) -> bool {
+    let action = match config.action {
+        AutomodAction::WarnOnly => "flagged",
+        AutomodAction::Delete => "deleted",
+        AutomodAction::Timeout => "timed out",
+    };
+    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+        ui::LogLevel::WARN,
+        format!(
+            "Automod {} {} for \"{}\": {}",
+            action, chat_message.username, rule, chat_message.message_text
+        ),
+    ));
+    if config.action == AutomodAction::WarnOnly {
+        return false;
+    }
+    let Some(helix) = helix else {
+        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+            ui::LogLevel::ERROR,
+            "Automod needs the Twitch API to remove messages, check the auth token".to_string(),
+        ));
+        return true;
+    };
+    let message_id = chat_message.message_id.clone();
+    let user_id = chat_message.user_id.clone();
+    let action = config.action;
+    let timeout_secs = config.timeout_secs;
+    let reason = format!("Automod: {}", rule);
+    let backend_tx = backend_tx.clone();
+    tasks.spawn(async move {
+        let result = match action {
+            AutomodAction::Timeout => helix.timeout_user(&user_id, timeout_secs, &reason).await,
+            _ => helix.delete_message(&message_id).await,
+        };
+        if let Err(e) = result {
+            let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                ui::LogLevel::ERROR,
+                format!("Automod could not remove the message: {}", e),
+            ));
+        }
+    });
+    true
+}
+
+/// Thanks the raider in chat and plays the raid sound, when raid reactions are on.
+fn handle_raid(
+    raider: &str,
+    viewers: u64,
+    chat_tx: &tokio::sync::mpsc::Sender<String>,
+    tts: &TTSContext,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let config = tts.config.get();
+    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+        ui::LogLevel::INFO,
+        format!("Raided by {} with {} viewers", raider, viewers),
+    ));
+    if !config.raids.enabled || config.safe_mode {
+        return;
+    }
+    if !config.raids.message.is_empty() {
+        let _ = chat_tx.try_send(backend::raids::raid_message(
+            &config.raids.message,
+            raider,
+            viewers,
+        ));
+    }
+    if !config.raids.sound.is_empty() && config.sfx.enabled && tts.plays_audio {
+        tts.transcript.lock().unwrap().record(
+            TranscriptEventKind::Sound,
+            raider,
+            &config.raids.sound,
+        );
+        tokio::spawn(play_sfx(
+            config.raids.sound,
+            None,
+            tts.config.clone(),
+            tts.sfx_sinks.clone(),
+            tts.playing_tts.clone(),
+        ));
+    }
+}
+
+fn greet_regular(
+    regular: &Regular,
+    chat_message: &ChatMessage,
+    config: &AppConfig,
+    tts: &TTSContext,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    if !tts.plays_audio {
+        return;
+    }
+    let dedupe_window = Duration::from_millis(config.sfx.dedupe_window_ms);
+    if !regular.sound.is_empty()
+        && config.sfx.enabled
+        && tts
+            .sfx_deduper
+            .lock()
+            .unwrap()
+            .should_play(&regular.sound, dedupe_window)
+    {
+        tts.transcript.lock().unwrap().record(
+            TranscriptEventKind::Sound,
+            &regular.username,
+            &regular.sound,
+        );
+        tokio::spawn(play_sfx(
+            regular.sound.clone(),
+            Some(chat_message.user_level()),
+            tts.config.clone(),
+            tts.sfx_sinks.clone(),
+            tts.playing_tts.clone(),
+        ));
+    }
+    if !regular.phrase.is_empty() && config.tts.enabled {
+        let request = TTSRequest {
+            id: format!("{}-greeting", chat_message.message_id),
+            username: regular.username.clone(),
+            language: regular.language.clone(),
+            text: regular.phrase_text(),
+            source: TTSSource::Chat,
+        };
+        let priority = chat_message.tts_priority(&config.tts.elevated_roles);
+        spawn_tts_synthesis(request, priority, tts, backend_tx);
+    }
+}
+
 async fn handle_frontend_to_backend_messages(
     mut backend_rx: tokio::sync::mpsc::Receiver<FrontendToBackendMessage>,
     backend_tx: tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
-    stream_handle: rodio::OutputStreamHandle,
+    state: BackendState,
 ) {
-    let stream_handle = Arc::new(stream_handle);
+    // one connection task per channel, keyed by lowercase channel name
+    let mut chat_handles: HashMap<String, tokio::task::JoinHandle<()>> = HashMap::new();
 
     while let Some(message) = backend_rx.recv().await {
         match message {
             FrontendToBackendMessage::UpdateTTSConfig(config) => {
-                let current_config: AppConfig = backend::config::load_config();
-                backend::config::save_config(
+                state
+                    .tts_service
+                    .set_cache_size(config.cache_size_mb * 1024 * 1024);
+                state.tts_service.set_provider(config.provider);
+                state
+                    .tts_service
+                    .set_fallback_providers(&config.fallback_providers);
+                state
+                    .tts_service
+                    .set_request_timeout(Duration::from_secs(config.request_timeout));
+                state.tts_service.set_retry_policy(RetryPolicy {
+                    max_retries: config.synthesis_retries,
+                    base_delay: Duration::from_millis(config.retry_base_delay_ms),
+                });
+                state
+                    .tts_queue
+                    .lock()
+                    .unwrap()
+                    .set_persist_path(config.persist_queue.then(persisted_queue_path));
+                let current_config: AppConfig = state.config.get();
+                save_config(
+                    &state.config,
                     &(AppConfig {
-                        chatbot: current_config.chatbot,
-                        sfx: current_config.sfx,
                         tts: config,
+                        ..current_config
                     }),
+                    &backend_tx,
                 );
                 let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
                     ui::LogLevel::INFO,
@@ -140,27 +1539,48 @@ async fn handle_frontend_to_backend_messages(
                 ));
             }
             FrontendToBackendMessage::UpdateSfxConfig(config) => {
-                let current_config: AppConfig = backend::config::load_config();
-                backend::config::save_config(
+                let current_config: AppConfig = state.config.get();
+                let sounds_dir_changed = config.sounds_dir != current_config.sfx.sounds_dir;
+                if sounds_dir_changed {
+                    prepare_sounds_directory(&config, &backend_tx);
+                }
+                save_config(
+                    &state.config,
                     &(AppConfig {
-                        chatbot: current_config.chatbot,
                         sfx: config,
-                        tts: current_config.tts,
+                        ..current_config
                     }),
+                    &backend_tx,
                 );
+                if sounds_dir_changed {
+                    let _ = backend_tx
+                        .try_send(BackendToFrontendMessage::SFXListUpdated(sound_list(&state)));
+                }
                 let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
                     ui::LogLevel::INFO,
                     "SFX config updated".to_string(),
                 ));
             }
+            FrontendToBackendMessage::UpdateUIConfig(config) => {
+                let current_config: AppConfig = state.config.get();
+                save_config(
+                    &state.config,
+                    &(AppConfig {
+                        ui: config,
+                        ..current_config
+                    }),
+                    &backend_tx,
+                );
+            }
             FrontendToBackendMessage::UpdateConfig(config) => {
-                let current_config: AppConfig = backend::config::load_config();
-                backend::config::save_config(
+                let current_config: AppConfig = state.config.get();
+                save_config(
+                    &state.config,
                     &(AppConfig {
                         chatbot: config,
-                        sfx: current_config.sfx,
-                        tts: current_config.tts,
+                        ..current_config
                     }),
+                    &backend_tx,
                 );
                 let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
                     ui::LogLevel::INFO,
@@ -168,9 +1588,319 @@ async fn handle_frontend_to_backend_messages(
                 ));
             }
             FrontendToBackendMessage::ConnectToChat(channel_name) => {
-                tokio::spawn(async move {
-                    handle_twitch_messages(channel_name).await;
-                });
+                let channel_name = channel_name.to_lowercase();
+                let backend_tx = backend_tx.clone();
+                let state = state.clone();
+                if let Some(handle) = chat_handles.remove(&channel_name) {
+                    handle.abort();
+                }
+                {
+                    let mut connection_status = state.connection_status.lock().unwrap();
+                    if !connection_status.channels.contains(&channel_name) {
+                        connection_status.channels.push(channel_name.clone());
+                    }
+                    connection_status.last_error = None;
+                }
+                // aborting this handle stops the reconnect loop too, waits included
+                let handle = tokio::spawn(chat_connection_task(
+                    channel_name.clone(),
+                    backend_tx,
+                    state,
+                ));
+                chat_handles.insert(channel_name, handle);
+            }
+            FrontendToBackendMessage::DisconnectFromChat(channel_name) => {
+                let channel_name = channel_name.to_lowercase();
+                // aborting the connection task also cancels its pending scheduled sends
+                if let Some(handle) = chat_handles.remove(&channel_name) {
+                    handle.abort();
+                }
+                if let Some(connection) =
+                    state.chat_connections.lock().unwrap().remove(&channel_name)
+                {
+                    connection.scheduled_steps.cancel();
+                }
+                {
+                    let mut connection_status = state.connection_status.lock().unwrap();
+                    connection_status
+                        .channels
+                        .retain(|channel| *channel != channel_name);
+                    if connection_status.channels.is_empty() {
+                        connection_status.scopes = None;
+                    }
+                }
+                let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                    ui::LogLevel::INFO,
+                    format!("Disconnected from {}", channel_name),
+                ));
+            }
+            FrontendToBackendMessage::ExportTranscript(path, format) => {
+                let log = match state.transcript.lock().unwrap().export(&path, format) {
+                    Ok(()) => BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::INFO,
+                        format!("Transcript exported to {}", path.display()),
+                    ),
+                    Err(e) => BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::ERROR,
+                        format!("Failed to export transcript: {}", e),
+                    ),
+                };
+                let _ = backend_tx.try_send(log);
+            }
+            FrontendToBackendMessage::AddTTSModerationEntry(list, entry) => {
+                let mut moderation = state.tts_moderation.write().unwrap();
+                if moderation.add(list, &entry) {
+                    backend::tts::moderation::save_moderation(&moderation);
+                }
+                let _ = backend_tx.try_send(BackendToFrontendMessage::TTSModerationUpdated(
+                    moderation.clone(),
+                ));
+            }
+            FrontendToBackendMessage::RemoveTTSModerationEntry(list, entry) => {
+                let mut moderation = state.tts_moderation.write().unwrap();
+                if moderation.remove(list, &entry) {
+                    backend::tts::moderation::save_moderation(&moderation);
+                }
+                let _ = backend_tx.try_send(BackendToFrontendMessage::TTSModerationUpdated(
+                    moderation.clone(),
+                ));
+            }
+            FrontendToBackendMessage::ImportTTSModeration(path) => {
+                match TTSModeration::import(&path) {
+                    Ok(imported) => {
+                        backend::tts::moderation::save_moderation(&imported);
+                        *state.tts_moderation.write().unwrap() = imported.clone();
+                        let _ = backend_tx
+                            .try_send(BackendToFrontendMessage::TTSModerationUpdated(imported));
+                        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                            ui::LogLevel::INFO,
+                            format!("TTS moderation lists imported from {}", path.display()),
+                        ));
+                    }
+                    Err(e) => {
+                        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                            ui::LogLevel::ERROR,
+                            format!("Failed to import TTS moderation lists: {}", e),
+                        ));
+                    }
+                }
+            }
+            FrontendToBackendMessage::ExportTTSModeration(path) => {
+                let log = match state.tts_moderation.read().unwrap().export(&path) {
+                    Ok(()) => BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::INFO,
+                        format!("TTS moderation lists exported to {}", path.display()),
+                    ),
+                    Err(e) => BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::ERROR,
+                        format!("Failed to export TTS moderation lists: {}", e),
+                    ),
+                };
+                let _ = backend_tx.try_send(log);
+            }
+            FrontendToBackendMessage::SetSafeMode(enabled) => {
+                set_safe_mode(
+                    enabled,
+                    &state.config,
+                    &state.tts_queue,
+                    &state.playing_tts,
+                    &state.sfx_sinks,
+                    &backend_tx,
+                );
+            }
+            FrontendToBackendMessage::RefreshSounds => {
+                let _ = backend_tx
+                    .try_send(BackendToFrontendMessage::SFXListUpdated(sound_list(&state)));
+            }
+            FrontendToBackendMessage::ImportSound(path) => {
+                let directory = backend::sounds::sounds_directory(&state.config.read().sfx);
+                let log = match backend::sounds::import_sound(&path, &directory) {
+                    Ok(name) => {
+                        let _ = backend_tx
+                            .try_send(BackendToFrontendMessage::SFXListUpdated(sound_list(&state)));
+                        BackendToFrontendMessage::CreateLog(
+                            ui::LogLevel::INFO,
+                            format!("Added sound {}", name),
+                        )
+                    }
+                    Err(e) => BackendToFrontendMessage::CreateLog(ui::LogLevel::ERROR, e),
+                };
+                let _ = backend_tx.try_send(log);
+            }
+            FrontendToBackendMessage::PlaySound(name) => {
+                if let Err(e) = start_sound(&name, None, &state.config, &state.sfx_sinks) {
+                    let _ = backend_tx
+                        .try_send(BackendToFrontendMessage::CreateLog(ui::LogLevel::WARN, e));
+                }
+            }
+            FrontendToBackendMessage::StopSounds => {
+                state.sfx_sinks.lock().unwrap().stop_all();
+            }
+            FrontendToBackendMessage::SkipTTS => {
+                if state.tts_queue.lock().unwrap().skip() {
+                    if let Some(sink) = state.playing_tts.lock().unwrap().as_ref() {
+                        sink.stop();
+                    }
+                    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::INFO,
+                        "Skipped the current TTS message".to_string(),
+                    ));
+                }
+            }
+            FrontendToBackendMessage::SkipTTSMessage(id) => {
+                let mut queue = state.tts_queue.lock().unwrap();
+                let is_playing = queue
+                    .get_currently_playing()
+                    .is_some_and(|request| request.id == id);
+                if is_playing && queue.skip() {
+                    if let Some(sink) = state.playing_tts.lock().unwrap().as_ref() {
+                        sink.stop();
+                    }
+                } else if queue.remove(&id) {
+                    let _ = backend_tx.try_send(BackendToFrontendMessage::TTSQueueUpdated(
+                        queue.get_all_with_current(),
+                    ));
+                }
+            }
+            FrontendToBackendMessage::ClearTTSQueue => {
+                let mut queue = state.tts_queue.lock().unwrap();
+                let cleared = queue.clear();
+                let _ = backend_tx.try_send(BackendToFrontendMessage::TTSQueueUpdated(
+                    queue.get_all_with_current(),
+                ));
+                let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                    ui::LogLevel::INFO,
+                    format!("Cleared {} queued TTS messages", cleared),
+                ));
+            }
+            FrontendToBackendMessage::GetTTSQueue => {
+                let _ = backend_tx.try_send(BackendToFrontendMessage::TTSQueueUpdated(
+                    state.tts_queue.lock().unwrap().get_all_with_current(),
+                ));
+            }
+            FrontendToBackendMessage::SetVolume(target, volume) => {
+                let mut config = state.config.get();
+                target.set(&mut config, volume);
+                save_config(&state.config, &config, &backend_tx);
+                let _ = backend_tx.try_send(BackendToFrontendMessage::ConfigReloaded(config));
+            }
+            FrontendToBackendMessage::ClearTTSCache => {
+                let freed = state.tts_service.cache_size();
+                state.tts_service.clear_cache();
+                let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                    ui::LogLevel::INFO,
+                    format!("TTS cache cleared, freed {} KB", freed / 1024),
+                ));
+            }
+            FrontendToBackendMessage::ReloadCommands => {
+                let log = match backend::commands::try_load_commands() {
+                    Ok(commands) => {
+                        let count = commands.len();
+                        let mut registry = state.command_registry.write().unwrap();
+                        registry.replace(commands);
+                        let _ = backend_tx.try_send(BackendToFrontendMessage::CommandsUpdated(
+                            registry.commands(),
+                        ));
+                        BackendToFrontendMessage::CreateLog(
+                            ui::LogLevel::INFO,
+                            format!("Reloaded {} commands", count),
+                        )
+                    }
+                    Err(e) => BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::ERROR,
+                        format!("Failed to reload commands, keeping the current ones: {}", e),
+                    ),
+                };
+                let _ = backend_tx.try_send(log);
+            }
+            FrontendToBackendMessage::ImportCommands { path, merge } => {
+                let log = match backend::commands::import_commands(&path, merge) {
+                    Ok((commands, summary)) => {
+                        let mut registry = state.command_registry.write().unwrap();
+                        registry.replace(commands);
+                        let _ = backend_tx.try_send(BackendToFrontendMessage::CommandsUpdated(
+                            registry.commands(),
+                        ));
+                        BackendToFrontendMessage::CreateLog(
+                            ui::LogLevel::INFO,
+                            format!(
+                                "Imported {} commands from {}, skipped {}",
+                                summary.imported,
+                                path.display(),
+                                summary.skipped
+                            ),
+                        )
+                    }
+                    Err(e) => BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::ERROR,
+                        format!("Failed to import commands: {}", e),
+                    ),
+                };
+                let _ = backend_tx.try_send(log);
+            }
+            FrontendToBackendMessage::AddCommand(command) => {
+                let done = format!("Added !{}", command.name);
+                let result = backend::commands::save_command(None, command);
+                apply_command_edit(result, done, &state, &backend_tx);
+            }
+            FrontendToBackendMessage::UpdateCommand { original, command } => {
+                let done = format!("Saved !{}", command.name);
+                let result = backend::commands::save_command(Some(&original), command);
+                apply_command_edit(result, done, &state, &backend_tx);
+            }
+            FrontendToBackendMessage::RemoveCommand(name) => {
+                let result = backend::commands::remove_command(&name);
+                apply_command_edit(result, format!("Removed !{}", name), &state, &backend_tx);
+            }
+            FrontendToBackendMessage::ToggleCommand(name, enabled) => {
+                let state_label = if enabled { "enabled" } else { "disabled" };
+                let result = backend::commands::set_command_enabled(&name, enabled);
+                let done = format!("!{} {}", name, state_label);
+                apply_command_edit(result, done, &state, &backend_tx);
+            }
+            FrontendToBackendMessage::ExportCommands(path) => {
+                let commands = state.command_registry.read().unwrap().commands();
+                let log = match backend::commands::export_commands(&commands, &path) {
+                    Ok(()) => BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::INFO,
+                        format!("Exported {} commands to {}", commands.len(), path.display()),
+                    ),
+                    Err(e) => BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::ERROR,
+                        format!("Failed to export commands: {}", e),
+                    ),
+                };
+                let _ = backend_tx.try_send(log);
+            }
+            FrontendToBackendMessage::TestTTSLanguage(language) => {
+                let text = backend::tts::languages::sample_phrase(&language).to_string();
+                queue_test_tts(language, text, &state, &backend_tx);
+            }
+            FrontendToBackendMessage::SpeakText(text) => {
+                let config = state.config.get();
+                let Some(text) = backend::limits::limit_length(
+                    text.trim(),
+                    config.chatbot.max_message_length,
+                    LongMessageAction::Truncate,
+                ) else {
+                    continue;
+                };
+                if text.is_empty() {
+                    continue;
+                }
+                queue_test_tts(config.tts.default_language, text, &state, &backend_tx);
+            }
+            FrontendToBackendMessage::RunCommand {
+                trigger,
+                as_broadcaster,
+            } => {
+                run_command_manually(&trigger, as_broadcaster, &state, &backend_tx);
+            }
+            FrontendToBackendMessage::Diagnostics => {
+                let connected = chat_handles.values().any(|handle| !handle.is_finished());
+                let _ = backend_tx.try_send(BackendToFrontendMessage::DiagnosticsReport(
+                    diagnostics_report(&state, connected),
+                ));
             }
             _ => {
                 println!("Received other message: {:?}", message);
@@ -179,15 +1909,712 @@ async fn handle_frontend_to_backend_messages(
     }
 }
 
-async fn play_sound(sound_file: String, stream_handle: Arc<OutputStreamHandle>) {
-    let sound_path = "./assets/sounds/".to_string() + &sound_file;
-    if let Ok(file) = File::open(Path::new(&sound_path)) {
-        let source = Decoder::new(BufReader::new(file)).unwrap();
-        let sink = Sink::try_new(&*stream_handle).unwrap();
-        sink.set_volume(0.5);
-        sink.append(source);
-        sink.detach();
+/// Swaps in the commands a change from the command editor left in commands.json, or logs why
+/// it was refused.
+fn apply_command_edit(
+    result: Result<Vec<Command>, Box<dyn std::error::Error>>,
+    done: String,
+    state: &BackendState,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let log = match result {
+        Ok(commands) => {
+            let mut registry = state.command_registry.write().unwrap();
+            registry.replace(commands);
+            let _ = backend_tx.try_send(BackendToFrontendMessage::CommandsUpdated(
+                registry.commands(),
+            ));
+            BackendToFrontendMessage::CreateLog(ui::LogLevel::INFO, done)
+        }
+        Err(e) => BackendToFrontendMessage::CreateLog(
+            ui::LogLevel::ERROR,
+            format!("Could not change the commands: {}", e),
+        ),
+    };
+    let _ = backend_tx.try_send(log);
+}
+
+/// Synthesizes `text` in `language` and queues it, for the test buttons in the TTS panel.
+fn queue_test_tts(
+    language: String,
+    text: String,
+    state: &BackendState,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let request = TTSRequest {
+        id: format!("test-{}", chrono::Local::now().timestamp_millis()),
+        username: "test".to_string(),
+        text,
+        language,
+        source: TTSSource::Test,
+    };
+    let max_chunks = state.config.read().tts.max_chunks_per_message;
+    let tts_service = state.tts_service.clone();
+    let tts_queue = state.tts_queue.clone();
+    let backend_tx = backend_tx.clone();
+    tokio::spawn(async move {
+        let log = match tts_service.generate_tts(&request, max_chunks).await {
+            Ok(audio_chunks) => {
+                let mut queue = tts_queue.lock().unwrap();
+                let language = request.language.clone();
+                let result = queue.add(TTSQueueItem {
+                    request,
+                    audio_chunks,
+                    priority: TTSPriority::Normal,
+                });
+                let _ = backend_tx.try_send(BackendToFrontendMessage::TTSQueueUpdated(
+                    queue.get_all_with_current(),
+                ));
+                match result {
+                    QueueAddResult::Added | QueueAddResult::DroppedOldest(_) => return,
+                    QueueAddResult::Rejected(rejection) => BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::WARN,
+                        format!("Could not queue {} test: {:?}", language, rejection),
+                    ),
+                }
+            }
+            Err(e) => BackendToFrontendMessage::CreateLog(
+                ui::LogLevel::ERROR,
+                format!("Failed to generate {} test: {}", request.language, e),
+            ),
+        };
+        let _ = backend_tx.try_send(log);
+    });
+}
+
+/// Synthesizes the requests left in the queue by the previous run again, one at a time so they
+/// keep their order.
+async fn restore_tts_queue(
+    requests: Vec<TTSRequest>,
+    state: BackendState,
+    backend_tx: tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+        ui::LogLevel::INFO,
+        format!("Restoring {} TTS messages from the last session", requests.len()),
+    ));
+    for request in requests {
+        let max_chunks = state.config.read().tts.max_chunks_per_message;
+        match state.tts_service.generate_tts(&request, max_chunks).await {
+            Ok(audio_chunks) => {
+                let mut queue = state.tts_queue.lock().unwrap();
+                let username = request.username.clone();
+                if let QueueAddResult::Rejected(rejection) = queue.add(TTSQueueItem {
+                    request,
+                    audio_chunks,
+                    priority: TTSPriority::Normal,
+                }) {
+                    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::WARN,
+                        format!(
+                            "Could not restore TTS message from {}: {:?}",
+                            username, rejection
+                        ),
+                    ));
+                }
+                let _ = backend_tx.try_send(BackendToFrontendMessage::TTSQueueUpdated(
+                    queue.get_all_with_current(),
+                ));
+            }
+            Err(e) => {
+                let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                    ui::LogLevel::ERROR,
+                    format!(
+                        "Failed to generate restored TTS for {}: {}",
+                        request.username, e
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Runs a command from the UI, replying in chat when connected and in the log otherwise.
+fn run_command_manually(
+    trigger: &str,
+    as_broadcaster: bool,
+    state: &BackendState,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let (channel, command_prefix) = {
+        let config = state.config.read();
+        let parser = CommandParser::new(&config.chatbot.command_prefix);
+        (config.chatbot.channel_name.clone(), parser.prefix().to_string())
+    };
+    let context = CommandContext {
+        username: channel.clone(),
+        user_id: String::new(),
+        channel,
+        args: Vec::new(),
+        user_level: if as_broadcaster {
+            UserLevel::Broadcaster
+        } else {
+            UserLevel::Viewer
+        },
+        bypass_cooldown: as_broadcaster,
+    };
+    // run in the main channel, or any connected one when only extra channels are
+    let connection = {
+        let connections = state.chat_connections.lock().unwrap();
+        connections
+            .get(&context.channel.to_lowercase())
+            .or_else(|| connections.values().next())
+            .cloned()
+    };
+    // checked before running, so cooldowns and counters aren't used up by a run that can't
+    // reach chat
+    let Some(connection) = connection else {
+        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+            ui::LogLevel::WARN,
+            format!("Not connected to chat, {}{} was not run", command_prefix, trigger),
+        ));
+        return;
+    };
+    let mut registry = state.command_registry.write().unwrap();
+    let result = CommandExecutor::execute(&mut registry, trigger, &context);
+    let execution = CommandExecution::new(trigger, &context, &result);
+    if let Some(text) = execution.transcript_text(&command_prefix) {
+        state.transcript.lock().unwrap().record(
+            TranscriptEventKind::Command,
+            &context.username,
+            &text,
+        );
+    }
+    let _ = backend_tx.try_send(BackendToFrontendMessage::CommandExecuted(execution));
+    let log = match result {
+        CommandResult::Success(response) => {
+            // there is no Twitch API client outside a connection, so whispers and
+            // announcements go to chat as plain messages
+            if let Some(response) = response {
+                let (_, text) = ResponseTarget::parse(&response);
+                let _ = connection.chat_tx.try_send(text.to_string());
+            }
+            if let Some(command) = registry.get(trigger) {
+                connection
+                    .scheduled_steps
+                    .schedule(&command.steps, &connection.chat_tx);
+            }
+            format!("Ran {}{}", command_prefix, trigger)
+        }
+        CommandResult::OnCooldown(remaining) => format!(
+            "{}{} is on cooldown for {}s",
+            command_prefix,
+            trigger,
+            remaining.as_secs() + 1
+        ),
+        CommandResult::PermissionDenied => {
+            format!("{}{} needs a higher user level", command_prefix, trigger)
+        }
+        CommandResult::NotFound => format!("Command {}{} not found", command_prefix, trigger),
+    };
+    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(ui::LogLevel::INFO, log));
+}
+
+fn diagnostics_report(state: &BackendState, connected: bool) -> DiagnosticsReport {
+    let (tts_queue_length, tts_queue_capacity, tts_queue_bytes, tts_queue_budget) = {
+        let queue = state.tts_queue.lock().unwrap();
+        (
+            queue.len(),
+            queue.max_length(),
+            queue.memory_usage(),
+            queue.memory_budget(),
+        )
+    };
+    let audio_device = Sink::try_new(&state.stream_handle)
+        .map_err(|e| e.to_string())
+        .map(|_| {
+            rodio::cpal::default_host()
+                .default_output_device()
+                .and_then(|device| device.name().ok())
+                .unwrap_or_else(|| "default".to_string())
+        });
+    DiagnosticsReport {
+        version: backend::version::CURRENT_VERSION,
+        connected,
+        connection: state.connection_status.lock().unwrap().clone(),
+        tts_queue_length,
+        tts_queue_capacity,
+        tts_queue_bytes,
+        tts_queue_budget,
+        audio_device,
+        config_path: backend::config::config_path(),
+    }
+}
+
+/// Creates the configured sounds directory if it's missing, logging where sounds are read from.
+fn prepare_sounds_directory(
+    config: &ui::Config,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let directory = backend::sounds::sounds_directory(config);
+    let log = match backend::sounds::ensure_sounds_directory(&directory) {
+        Ok(()) => BackendToFrontendMessage::CreateLog(
+            ui::LogLevel::INFO,
+            format!("Playing sounds from {}", directory.display()),
+        ),
+        Err(e) => BackendToFrontendMessage::CreateLog(
+            ui::LogLevel::ERROR,
+            format!("Could not create the sounds directory: {}", e),
+        ),
+    };
+    let _ = backend_tx.try_send(log);
+}
+
+async fn validate_sounds_on_startup(
+    directory: std::path::PathBuf,
+    backend_tx: tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let validate = move || backend::sounds::validate_sounds(&directory);
+    let Ok(failures) = tokio::task::spawn_blocking(validate).await else {
+        return;
+    };
+    if failures.is_empty() {
+        info!("All sounds decoded successfully");
+    }
+    for (sound, reason) in failures {
+        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+            ui::LogLevel::WARN,
+            format!("Sound {} could not be decoded: {}", sound, reason),
+        ));
+    }
+}
+
+/// The sounds directory with this session's play counts, for the SFX panel.
+fn sound_list(state: &BackendState) -> Vec<SoundInfo> {
+    let directory = backend::sounds::sounds_directory(&state.config.read().sfx);
+    backend::sounds::list_sound_info(&directory, &state.transcript.lock().unwrap().sound_plays())
+}
+
+async fn play_sound(sound_file: String, config: &SharedConfig, sfx_sinks: Arc<Mutex<SinkPool>>) {
+    if let Err(e) = start_sound(&sound_file, None, config, &sfx_sinks) {
+        warn!("{}", e);
+    }
+}
+
+/// Starts a sound, at the volume of the role that triggered it when `level` is set. Returns
+/// `Ok(None)` when the sound is skipped on purpose and `Err` when it could not be played.
+fn start_sound(
+    sound_file: &str,
+    level: Option<UserLevel>,
+    config: &SharedConfig,
+    sfx_sinks: &Mutex<SinkPool>,
+) -> Result<Option<Arc<Sink>>, String> {
+    let config = config.read();
+    let sounds_directory = backend::sounds::sounds_directory(&config.sfx);
+    let Some(sound_file) = backend::sounds::resolve_sound_file(sound_file, &sounds_directory)
+    else {
+        return Err(format!("Could not find sound file: {}", sound_file));
+    };
+    let sound_file = sound_file.as_str();
+    let sound_override = backend::sounds::sound_override(sound_file);
+    if config.safe_mode || !sound_override.enabled {
+        return Ok(None);
+    }
+    let sound_path = sounds_directory.join(sound_file);
+    let file = File::open(&sound_path)
+        .map_err(|e| format!("Could not open sound file {}: {}", sound_path.display(), e))?;
+    let source = Decoder::new(BufReader::new(file))
+        .map_err(|e| format!("Could not decode sound file {}: {}", sound_path.display(), e))?;
+    let sfx_config = &config.sfx;
+    let gain = if sfx_config.normalize {
+        backend::sounds::normalization_gain(&sound_path)
     } else {
-        println!("Could not open sound file: {}", sound_path);
+        1.0
+    };
+    let repeat_policy = sfx_config.repeat_policy;
+    let mut sfx_sinks = sfx_sinks.lock().unwrap();
+    if let Some(cooldown) = sound_override.cooldown_secs {
+        if sfx_sinks.is_cooling_down(sound_file, Duration::from_secs(cooldown)) {
+            info!("Skipped {}, the sound is on cooldown", sound_file);
+            return Ok(None);
+        }
+    }
+    let max_concurrent = sfx_config.max_concurrent_sounds;
+    if !sfx_config.serial_playback
+        && max_concurrent > 0
+        && sfx_sinks.playing_count() >= max_concurrent
+    {
+        info!("Skipped {}, {} sounds are already playing", sound_file, max_concurrent);
+        return Ok(None);
+    }
+    let playing = sfx_sinks.playing(sound_file);
+    match repeat_policy.action(playing.is_some()) {
+        RepeatAction::Play => {}
+        RepeatAction::StopAndPlay => playing.unwrap().stop(),
+        RepeatAction::Skip => return Ok(None),
+    }
+    let volume = sfx_config
+        .role_volumes
+        .effective_volume(sound_override.volume(sfx_config.volume), level);
+    let sink = sfx_sinks
+        .acquire(sound_file)
+        .map_err(|e| format!("Could not play {}: {}", sound_file, e))?;
+    sink.set_volume(volume as f32 * gain);
+    sink.append(source);
+    Ok(Some(sink))
+}
+
+/// Plays a sound effect, waiting for its turn first in serial playback mode.
+async fn play_sfx(
+    sound_file: String,
+    level: Option<UserLevel>,
+    config: SharedConfig,
+    sfx_sinks: Arc<Mutex<SinkPool>>,
+    playing_tts: PlayingTTS,
+) {
+    let (serial_playback, max_queued_sounds) = {
+        let config = config.read();
+        (config.sfx.serial_playback, config.sfx.max_queued_sounds)
+    };
+    if !serial_playback {
+        play_routed_sfx(&sound_file, level, &config, &sfx_sinks, &playing_tts).await;
+        return;
+    }
+    let Some(ticket) = sfx_sinks.lock().unwrap().queue.join(max_queued_sounds) else {
+        info!("Skipped {}, the sound queue is full", sound_file);
+        return;
+    };
+    loop {
+        match sfx_sinks.lock().unwrap().queue.turn(ticket) {
+            SfxTurn::Play => break,
+            SfxTurn::Dropped => return,
+            SfxTurn::Wait => {}
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    if let Some(sink) = play_routed_sfx(&sound_file, level, &config, &sfx_sinks, &playing_tts).await
+    {
+        let _ = tokio::task::spawn_blocking(move || sink.sleep_until_end()).await;
+    }
+    sfx_sinks.lock().unwrap().queue.finish();
+}
+
+/// Starts a sound effect, routed around any TTS that is playing according to the `AudioPolicy`.
+async fn play_routed_sfx(
+    sound_file: &str,
+    level: Option<UserLevel>,
+    config: &SharedConfig,
+    sfx_sinks: &Mutex<SinkPool>,
+    playing_tts: &PlayingTTS,
+) -> Option<Arc<Sink>> {
+    let policy = config.read().sfx.audio_policy;
+    let tts_sink = playing_tts.lock().unwrap().clone();
+    let start = || match start_sound(sound_file, level, config, sfx_sinks) {
+        Ok(sink) => sink,
+        Err(e) => {
+            warn!("{}", e);
+            None
+        }
+    };
+    match policy.route_sfx(tts_sink.is_some()) {
+        SfxRoute::PlayNow => start(),
+        SfxRoute::WaitForTTS => {
+            while playing_tts.lock().unwrap().is_some() {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            start()
+        }
+        SfxRoute::DuckTTS => {
+            let sink = start()?;
+            let tts_sink = tts_sink.unwrap();
+            let volume = tts_sink.volume();
+            tts_sink.set_volume(volume * backend::audio::DUCKED_TTS_VOLUME);
+            let waiting = sink.clone();
+            let _ = tokio::task::spawn_blocking(move || waiting.sleep_until_end()).await;
+            tts_sink.set_volume(volume);
+            Some(sink)
+        }
+    }
+}
+
+/// Tells the UI whenever a command goes on or comes off cooldown.
+async fn command_cooldown_task(
+    command_registry: Arc<RwLock<CommandRegistry>>,
+    backend_tx: tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let mut watcher = CooldownWatcher::default();
+    let mut interval = tokio::time::interval(Duration::from_millis(500));
+    loop {
+        interval.tick().await;
+        let cooling = command_registry.read().unwrap().on_cooldown();
+        if !watcher.update(cooling).is_empty() {
+            let _ = backend_tx.try_send(BackendToFrontendMessage::CommandCooldownsUpdated(
+                watcher.cooling().clone(),
+            ));
+        }
+    }
+}
+
+/// Sends the UI a fresh sounds list whenever files are added to or removed from the sounds
+/// directory outside the app, e.g. copied in with a file manager.
+async fn sounds_watch_task(
+    state: BackendState,
+    backend_tx: tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let sounds_directory = || backend::sounds::sounds_directory(&state.config.read().sfx);
+    let mut sounds = backend::sounds::list_sounds(&sounds_directory());
+    let mut interval = tokio::time::interval(Duration::from_secs(2));
+    loop {
+        interval.tick().await;
+        let current = backend::sounds::list_sounds(&sounds_directory());
+        if current != sounds {
+            sounds = current;
+            let _ = backend_tx
+                .try_send(BackendToFrontendMessage::SFXListUpdated(sound_list(&state)));
+        }
+    }
+}
+
+/// Reloads config.toml when it's edited outside the app and sends the new values to the UI.
+async fn config_watch_task(
+    shared_config: SharedConfig,
+    backend_tx: tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let mut watcher = ConfigWatcher::new(shared_config);
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        match watcher.poll() {
+            Some(Ok(config)) => {
+                let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                    ui::LogLevel::INFO,
+                    "Reloaded config.toml".to_string(),
+                ));
+                let _ = backend_tx.try_send(BackendToFrontendMessage::ConfigReloaded(config));
+            }
+            Some(Err(e)) => {
+                let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                    ui::LogLevel::WARN,
+                    e,
+                ));
+            }
+            None => {}
+        }
+    }
+}
+
+/// Polls the follower list and plays the follow sound for new follows.
+async fn follow_alert_task(
+    helix: Arc<HelixClient>,
+    shared_config: SharedConfig,
+    sfx_sinks: Arc<Mutex<SinkPool>>,
+    playing_tts: PlayingTTS,
+    transcript: Arc<Mutex<Transcript>>,
+    backend_tx: tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let mut tracker = FollowTracker::default();
+    loop {
+        let config = shared_config.read().follows.clone();
+        if config.enabled {
+            match helix.recent_follows().await {
+                Ok(follows) => {
+                    let new_follows = tracker.new_follows(&follows);
+                    for login in &new_follows {
+                        let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                            ui::LogLevel::INFO,
+                            format!("New follower: {}", login),
+                        ));
+                    }
+                    if let Some(login) = new_follows.last() {
+                        if !config.sound.is_empty()
+                            && tracker.should_play(Duration::from_secs(config.cooldown_secs))
+                        {
+                            transcript.lock().unwrap().record(
+                                TranscriptEventKind::Sound,
+                                login,
+                                &config.sound,
+                            );
+                            tokio::spawn(play_sfx(
+                                config.sound.clone(),
+                                None,
+                                shared_config.clone(),
+                                sfx_sinks.clone(),
+                                playing_tts.clone(),
+                            ));
+                        }
+                    }
+                }
+                Err(e) => warn!("Could not fetch followers: {}", e),
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(config.poll_interval_secs.max(1))).await;
+    }
+}
+
+async fn chat_sender_task(
+    client: TwitchClient,
+    channel_name: String,
+    mut chat_rx: tokio::sync::mpsc::Receiver<String>,
+    identity: Arc<Mutex<BotIdentity>>,
+) {
+    while let Some(text) = chat_rx.recv().await {
+        identity.lock().unwrap().record_sent(&text);
+        if let Err(e) = client.say(channel_name.clone(), text).await {
+            error!("Failed to send chat message: {}", e);
+        }
+        tokio::time::sleep(CHAT_SEND_INTERVAL).await;
+    }
+}
+
+/// Starts the incoming or all-clear sound of the TTS player, which is skipped with a warning
+/// when it can't be played.
+fn start_tts_cue(
+    sound_file: &str,
+    state: &BackendState,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) -> Option<Arc<Sink>> {
+    match start_sound(sound_file, None, &state.config, &state.sfx_sinks) {
+        Ok(sink) => sink,
+        Err(e) => {
+            warn!("{}", e);
+            let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                ui::LogLevel::WARN,
+                format!("Skipped the TTS sound: {}", e),
+            ));
+            None
+        }
+    }
+}
+
+async fn tts_player_task(
+    state: BackendState,
+    backend_tx: tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let mut transitions = PlaybackTransitions::default();
+    loop {
+        // queued messages wait until safe mode is turned off
+        let paused = !state.tts_queue.lock().unwrap().is_empty()
+            && state.config.read().safe_mode;
+        if paused {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            continue;
+        }
+        let item = {
+            let mut queue = state.tts_queue.lock().unwrap();
+            let item = queue.pop();
+            if let Some(item) = &item {
+                queue.set_currently_playing(Some(item.request.clone()));
+                let _ = backend_tx.try_send(BackendToFrontendMessage::TTSQueueUpdated(
+                    queue.get_all_with_current(),
+                ));
+            }
+            item
+        };
+        let Some(item) = item else {
+            if transitions.queue_empty() {
+                let all_clear_sound = state.config.read().tts.all_clear_sound.clone();
+                if !all_clear_sound.is_empty() {
+                    start_tts_cue(&all_clear_sound, &state, &backend_tx);
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            continue;
+        };
+        state.transcript.lock().unwrap().record(
+            TranscriptEventKind::Tts,
+            &item.request.username,
+            &item.request.text,
+        );
+        let config = state.config.read().tts.clone();
+        if transitions.item_started() && !config.incoming_sound.is_empty() {
+            if let Some(sink) = start_tts_cue(&config.incoming_sound, &state, &backend_tx) {
+                let _ = tokio::task::spawn_blocking(move || sink.sleep_until_end()).await;
+            }
+        }
+        let chunk_gap = backend::tts::chunk_gap(&config, &item.request.language);
+        let sink = match Sink::try_new(&state.stream_handle) {
+            Ok(sink) => Arc::new(sink),
+            Err(e) => {
+                error!("Could not create TTS sink: {}", e);
+                finish_tts_message(&state, &backend_tx);
+                continue;
+            }
+        };
+        sink.set_volume(config.volume as f32);
+        *state.playing_tts.lock().unwrap() = Some(sink.clone());
+        let mut failed_chunks = 0;
+        for chunk in &item.audio_chunks {
+            if state.tts_queue.lock().unwrap().get_skip_flag() {
+                break;
+            }
+            let Some(source) =
+                decode_tts_chunk(&item.request, chunk, &state, &config, &backend_tx).await
+            else {
+                failed_chunks += 1;
+                if failed_chunks >= config.max_failed_chunks {
+                    let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                        ui::LogLevel::ERROR,
+                        format!(
+                            "Skipping TTS from {}, {} chunks failed to play",
+                            item.request.username, failed_chunks
+                        ),
+                    ));
+                    break;
+                }
+                continue;
+            };
+            sink.append(source);
+            let sink = sink.clone();
+            let _ = tokio::task::spawn_blocking(move || sink.sleep_until_end()).await;
+            tokio::time::sleep(chunk_gap).await;
+        }
+        *state.playing_tts.lock().unwrap() = None;
+        finish_tts_message(&state, &backend_tx);
+    }
+}
+
+fn finish_tts_message(
+    state: &BackendState,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) {
+    let mut queue = state.tts_queue.lock().unwrap();
+    queue.set_currently_playing(None);
+    let _ = backend_tx.try_send(BackendToFrontendMessage::TTSQueueUpdated(
+        queue.get_all_with_current(),
+    ));
+}
+
+/// Decodes a chunk of TTS audio, synthesizing it again up to `decode_retries` times
+/// when it doesn't decode, and plays the error sound if it never does.
+async fn decode_tts_chunk(
+    request: &TTSRequest,
+    chunk: &TTSAudioChunk,
+    state: &BackendState,
+    config: &ui::TTSConfig,
+    backend_tx: &tokio::sync::mpsc::Sender<BackendToFrontendMessage>,
+) -> Option<Decoder<Cursor<Vec<u8>>>> {
+    let mut audio_data = chunk.audio_data.clone();
+    let mut retries = 0;
+    loop {
+        let error = match Decoder::new(Cursor::new(audio_data)) {
+            Ok(source) => return Some(source),
+            Err(e) => e,
+        };
+        if retries >= config.decode_retries {
+            let _ = backend_tx.try_send(BackendToFrontendMessage::CreateLog(
+                ui::LogLevel::ERROR,
+                format!(
+                    "Could not decode TTS audio for {} after {} retries: {}",
+                    request.username, retries, error
+                ),
+            ));
+            if state.error_sound_gate.lock().unwrap().should_play(config) {
+                let error_sound = config.error_sound.clone();
+                play_sound(error_sound, &state.config, state.sfx_sinks.clone()).await;
+            }
+            return None;
+        }
+        retries += 1;
+        warn!(
+            "Could not decode TTS audio for request {}, synthesizing again ({}/{}): {}",
+            request.id, retries, config.decode_retries, error
+        );
+        audio_data = match state.tts_service.regenerate_chunk(request, chunk).await {
+            Ok(audio_data) => audio_data,
+            Err(e) => {
+                error!("Failed to synthesize TTS chunk again: {}", e);
+                Vec::new()
+            }
+        };
     }
 }
@@ -0,0 +1,225 @@
+use egui::Color32;
+
+use crate::backend::commands::{ Command, TriggerKind, UserLevel };
+
+use super::{ Chatbot, FrontendToBackendMessage };
+
+// longest cooldown the editor offers, in seconds
+const MAX_COOLDOWN: u64 = 24 * 60 * 60;
+
+/// The add/edit form of the commands section.
+pub struct CommandEditor {
+    // name of the command being edited, None for a new one
+    original: Option<String>,
+    command: Command,
+    // 0 when the command has no per-user cooldown
+    user_cooldown: u64,
+}
+
+impl CommandEditor {
+    fn add() -> Self {
+        Self { original: None, command: Command::new(""), user_cooldown: 0 }
+    }
+
+    fn edit(command: &Command) -> Self {
+        Self {
+            original: Some(command.name.clone()),
+            command: command.clone(),
+            user_cooldown: command.user_cooldown.unwrap_or(0),
+        }
+    }
+
+    fn message(&self) -> FrontendToBackendMessage {
+        let mut command = self.command.clone();
+        command.name = command.name.trim().trim_start_matches('!').to_string();
+        command.user_cooldown = (self.user_cooldown > 0).then_some(self.user_cooldown);
+        match &self.original {
+            Some(original) =>
+                FrontendToBackendMessage::UpdateCommand { original: original.clone(), command },
+            None => FrontendToBackendMessage::AddCommand(command),
+        }
+    }
+}
+
+impl Chatbot {
+    pub fn show_commands(&mut self, ui: &mut egui::Ui) {
+        ui.set_min_width(ui.max_rect().width());
+        ui.horizontal(|ui| {
+            ui.heading(egui::widget_text::RichText::new("Commands").color(Color32::WHITE));
+            if ui.button("Reload from disk").clicked() {
+                let _ = self.frontend_tx.try_send(FrontendToBackendMessage::ReloadCommands);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.commands_file);
+            if ui.button("Import and merge").clicked() {
+                self.send(FrontendToBackendMessage::ImportCommands {
+                    path: self.commands_file.clone().into(),
+                    merge: true,
+                });
+            }
+            if ui.button("Import and replace").clicked() {
+                self.send(FrontendToBackendMessage::ImportCommands {
+                    path: self.commands_file.clone().into(),
+                    merge: false,
+                });
+            }
+            if ui.button("Export").clicked() {
+                self.send(
+                    FrontendToBackendMessage::ExportCommands(self.commands_file.clone().into())
+                );
+            }
+        });
+        if self.command_editor.is_none() && ui.button("Add command").clicked() {
+            self.command_editor = Some(CommandEditor::add());
+        }
+        self.show_command_editor(ui);
+        ui.collapsing("Recent runs", |ui| {
+            if self.command_executions.is_empty() {
+                ui.label("No commands run yet");
+            }
+            for execution in self.command_executions.iter() {
+                ui.horizontal(|ui| {
+                    ui.label(egui::widget_text::RichText::new(&execution.timestamp).weak());
+                    ui.label(format!("!{}", execution.trigger));
+                    ui.label(&execution.username);
+                    ui.label(execution.outcome.label());
+                    if let Some(response) = &execution.response {
+                        ui.label(egui::widget_text::RichText::new(response).weak());
+                    }
+                });
+            }
+        });
+        if self.commands.is_empty() {
+            ui.label("No commands yet");
+            return;
+        }
+        let mut run = None;
+        let mut edit = None;
+        egui::ScrollArea
+            ::vertical()
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                for command in self.commands.iter() {
+                    let on_cooldown = self.commands_on_cooldown.contains(&command.name.to_lowercase());
+                    ui.horizontal(|ui| {
+                        let mut enabled = command.enabled;
+                        if ui.checkbox(&mut enabled, "").on_hover_text("Enabled").changed() {
+                            let name = command.name.clone();
+                            run = Some(FrontendToBackendMessage::ToggleCommand(name, enabled));
+                        }
+                        if ui.button("Edit").clicked() {
+                            edit = Some(CommandEditor::edit(command));
+                        }
+                        if ui.button("Delete").clicked() {
+                            let name = command.name.clone();
+                            run = Some(FrontendToBackendMessage::RemoveCommand(name));
+                        }
+                        if ui.add_enabled(!on_cooldown, egui::Button::new("Run")).clicked() {
+                            run = Some(FrontendToBackendMessage::RunCommand {
+                                trigger: command.name.clone(),
+                                as_broadcaster: false,
+                            });
+                        }
+                        // broadcaster runs skip the cooldown, so this one stays enabled
+                        if ui.button("Run as broadcaster").clicked() {
+                            run = Some(FrontendToBackendMessage::RunCommand {
+                                trigger: command.name.clone(),
+                                as_broadcaster: true,
+                            });
+                        }
+                        match command.trigger_kind {
+                            TriggerKind::Prefix => ui.label(format!("!{}", command.name)),
+                            TriggerKind::Regex =>
+                                ui.label(format!("{} (regex: {})", command.name, command.pattern)),
+                            TriggerKind::Contains =>
+                                ui.label(
+                                    format!("{} (contains: {})", command.name, command.pattern)
+                                ),
+                        };
+                        if !command.aliases.is_empty() {
+                            let aliases: Vec<String> = command.aliases
+                                .iter()
+                                .map(|alias| format!("!{}", alias))
+                                .collect();
+                            ui.label(
+                                egui::widget_text::RichText
+                                    ::new(format!("also {}", aliases.join(", ")))
+                                    .small()
+                                    .weak()
+                            );
+                        }
+                        if on_cooldown {
+                            ui.label(egui::widget_text::RichText::new("cooldown").small().weak());
+                        }
+                        let response = if command.responses.is_empty() {
+                            command.response.clone()
+                        } else {
+                            format!("one of {} responses", command.responses.len())
+                        };
+                        ui.label(egui::widget_text::RichText::new(response).weak());
+                    });
+                    ui.separator();
+                }
+            });
+        if let Some(message) = run {
+            self.send(message);
+        }
+        if edit.is_some() {
+            self.command_editor = edit;
+        }
+    }
+
+    fn show_command_editor(&mut self, ui: &mut egui::Ui) {
+        let Some(editor) = self.command_editor.as_mut() else {
+            return;
+        };
+        let mut close = false;
+        let mut save = None;
+        ui.group(|ui| {
+            ui.label(if editor.original.is_some() { "Edit command" } else { "New command" });
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut editor.command.name);
+            });
+            ui.label("Response:");
+            ui.text_edit_multiline(&mut editor.command.response);
+            ui.horizontal(|ui| {
+                ui.label("Cooldown (seconds):");
+                ui.add(egui::DragValue::new(&mut editor.command.cooldown).range(0..=MAX_COOLDOWN));
+                ui.label("Per user (seconds, 0 for none):");
+                ui.add(egui::DragValue::new(&mut editor.user_cooldown).range(0..=MAX_COOLDOWN));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Who can use it:");
+                egui::ComboBox
+                    ::from_id_salt("command_permission")
+                    .selected_text(editor.command.permission.label())
+                    .show_ui(ui, |ui| {
+                        let permission = &mut editor.command.permission;
+                        for level in UserLevel::ALL {
+                            ui.selectable_value(permission, level, level.label());
+                        }
+                    });
+                ui.checkbox(&mut editor.command.enabled, "Enabled");
+            });
+            ui.horizontal(|ui| {
+                let has_name = !editor.command.name.trim().trim_start_matches('!').is_empty();
+                if ui.add_enabled(has_name, egui::Button::new("Save")).clicked() {
+                    save = Some(editor.message());
+                    close = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    close = true;
+                }
+            });
+        });
+        if let Some(message) = save {
+            self.send(message);
+        }
+        if close {
+            self.command_editor = None;
+        }
+    }
+}
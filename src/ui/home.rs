@@ -1,12 +1,14 @@
 use egui::Color32;
 
-use super::{ FrontendToBackendMessage, Chatbot, LogLevel, LogMessage };
+use crate::backend::transcript::TranscriptFormat;
+
+use super::{ FrontendToBackendMessage, Chatbot, ConnectionState, LogLevel, LogMessage };
 
 impl Chatbot {
     pub fn show_home(&mut self, ui: &mut egui::Ui) {
         ui.set_min_height(ui.max_rect().height());
         ui.set_min_width(ui.max_rect().width());
-        ui.horizontal(|ui| {
+        ui.horizontal_wrapped(|ui| {
             if ui.button(&self.labels.connect_button).clicked() {
                 if self.labels.connect_button == "Connect" {
                     if self.config.auth_token == "" {
@@ -18,28 +20,49 @@ impl Chatbot {
                         return;
                     }
                     self.labels.connect_button = "Disconnect".to_string();
-                    let _ = self.frontend_tx
-                        .try_send(
-                            FrontendToBackendMessage::ConnectToChat(
-                                self.config.channel_name.clone()
-                            )
-                        )
-                        .unwrap();
-                    self.labels.bot_status = "Connected".to_string();
+                    for channel in self.config.channels() {
+                        self.send(FrontendToBackendMessage::ConnectToChat(channel));
+                    }
+                    self.set_connection_state(ConnectionState::Connected);
                 } else {
                     self.labels.connect_button = "Connect".to_string();
-                    let _ = self.frontend_tx
-                        .try_send(
-                            FrontendToBackendMessage::DisconnectFromChat(
-                                self.config.channel_name.clone()
-                            )
-                        )
-                        .unwrap();
-                    self.labels.bot_status = "Disconnected".to_string();
+                    for channel in self.config.channels() {
+                        self.send(FrontendToBackendMessage::DisconnectFromChat(channel));
+                    }
+                    self.set_connection_state(ConnectionState::Disconnected);
                 }
             }
+            let (indicator, _) = ui.allocate_exact_size(egui::vec2(10.0, 10.0), egui::Sense::hover());
+            ui.painter().circle_filled(
+                indicator.center(),
+                5.0,
+                self.connection_state.indicator_color()
+            );
             ui.label(format!("Status: {}", self.labels.bot_status));
+            for format in [TranscriptFormat::Json, TranscriptFormat::Csv] {
+                let label = format!("Export transcript ({})", format.extension());
+                if ui.button(label).clicked() {
+                    let path = format!(
+                        "transcript-{}.{}",
+                        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                        format.extension()
+                    );
+                    let _ = self.frontend_tx.try_send(
+                        FrontendToBackendMessage::ExportTranscript(path.into(), format)
+                    );
+                }
+            }
+            if ui.button("Copy diagnostics").clicked() {
+                let _ = self.frontend_tx.try_send(FrontendToBackendMessage::Diagnostics);
+            }
         });
+        if self.connection_state == ConnectionState::Disconnected && self.config.auth_token.is_empty() {
+            ui.label(
+                egui::widget_text::RichText
+                    ::new("No auth token set, add one in SETTINGS to connect")
+                    .color(Color32::from_rgb(255, 190, 0))
+            );
+        }
         ui.separator();
         ui.heading(egui::widget_text::RichText::new("Bot logs").color(Color32::WHITE));
         egui::ScrollArea
@@ -62,10 +85,7 @@ impl Chatbot {
             });
         // for testing purposes
         if ui.button("test".to_string()).clicked() {
-            let _ = self
-                .frontend_tx
-                .try_send(FrontendToBackendMessage::PlaySound("test.wav".to_string()))
-                .unwrap();
+            self.send(FrontendToBackendMessage::PlaySound("test.wav".to_string()));
         }
     }
 }
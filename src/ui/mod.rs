@@ -1,27 +1,117 @@
 use egui::{ CentralPanel, Color32, TopBottomPanel };
 use serde::{ Deserialize, Serialize };
+use std::collections::{ HashMap, HashSet, VecDeque };
+use std::path::PathBuf;
+use tokio::sync::mpsc::error::TrySendError;
 
+use crate::backend::audio::{ AudioPolicy, RepeatPolicy };
+use crate::backend::commands::{ Command, CommandExecution };
+use crate::backend::config::AppConfig;
+use crate::backend::diagnostics::DiagnosticsReport;
+use crate::backend::limits::LongMessageAction;
+use crate::backend::sounds::{ SoundInfo, SoundSort };
+use crate::backend::transcript::TranscriptFormat;
+use crate::backend::tts::moderation::{
+    BannedPhraseAction,
+    ModerationList,
+    PhraseMatch,
+    TTSModeration,
+};
+use crate::backend::tts::provider::TTSProviderKind;
+use crate::backend::tts::queue::QueueFullPolicy;
+use crate::backend::tts::TTSSource;
+use crate::backend::volume::{ RoleVolumes, VolumeTarget };
+use crate::backend::window::{ GeometrySaver, WindowGeometry };
+
+pub mod commands;
 pub mod home;
 pub mod settings;
 pub mod sfx;
+pub mod toasts;
 pub mod tts;
 
+// command runs kept for the recent runs list
+const MAX_COMMAND_EXECUTIONS: usize = 10;
+// the settings column of the SFX and TTS sections never gets narrower than this
+const MIN_SETTINGS_WIDTH: f32 = 280.0;
+
+/// Width of the settings column next to the sounds and languages lists, a share of the
+/// window that keeps room for the list.
+fn settings_column_width(available_width: f32) -> f32 {
+    (available_width * 0.45).max(MIN_SETTINGS_WIDTH).min(available_width)
+}
+
+#[derive(Clone, Copy)]
 enum Section {
     Home,
     Sfx,
     Tts,
+    Commands,
     Settings,
 }
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
 pub enum FrontendToBackendMessage {
     RemoveTTSLang(String),
     AddTTSLang(String),
     UpdateConfig(ChatbotConfig),
     UpdateSfxConfig(Config),
-    UpdateTTSConfig(Config),
+    UpdateTTSConfig(TTSConfig),
+    UpdateUIConfig(UIConfig),
     ConnectToChat(String),
     DisconnectFromChat(String),
+    // plays a sound locally, without going through chat permissions
     PlaySound(String),
+    StopSounds,
+    ExportTranscript(PathBuf, TranscriptFormat),
+    // copies a file dropped onto the window into the sounds directory
+    ImportSound(PathBuf),
+    // asks for the sounds list again, e.g. for fresh play counts
+    RefreshSounds,
+    AddTTSModerationEntry(ModerationList, String),
+    RemoveTTSModerationEntry(ModerationList, String),
+    ImportTTSModeration(PathBuf),
+    ExportTTSModeration(PathBuf),
+    Diagnostics,
+    TestTTSLanguage(String),
+    // reads the text in the default language, like a language test
+    SpeakText(String),
+    ReloadCommands,
+    // merge keeps the current commands, otherwise the file's commands replace them
+    ImportCommands {
+        path: PathBuf,
+        merge: bool,
+    },
+    ExportCommands(PathBuf),
+    AddCommand(Command),
+    // `original` is the name the command had before the edit
+    UpdateCommand {
+        original: String,
+        command: Command,
+    },
+    RemoveCommand(String),
+    ToggleCommand(String, bool),
+    ClearTTSCache,
+    RunCommand {
+        trigger: String,
+        as_broadcaster: bool,
+    },
+    // stops the TTS message that is playing, the rest of the queue carries on
+    SkipTTS,
+    // skips the message with this id, whether it's playing or still queued
+    SkipTTSMessage(String),
+    ClearTTSQueue,
+    GetTTSQueue,
+    SetVolume(VolumeTarget, f64),
+    SetSafeMode(bool),
+}
+
+impl FrontendToBackendMessage {
+    /// Messages that do nothing useful without a chat connection. This isn't a setting: without
+    /// a connection there is nowhere to send a command's response, so the backend refuses these
+    /// too and the check here only saves the round trip.
+    pub fn requires_connection(&self) -> bool {
+        matches!(self, FrontendToBackendMessage::RunCommand { .. })
+    }
 }
 
 #[derive(Debug)]
@@ -29,16 +119,325 @@ pub enum BackendToFrontendMessage {
     ConnectionSuccess(String),
     ConnectionFailure(String),
     TTSLangListUpdated,
-    SFXListUpdated,
+    SFXListUpdated(Vec<SoundInfo>),
     ChatMessageReceived(String),
     CreateLog(LogLevel, String),
+    TTSQueueUpdated(Vec<TTSQueueItemUI>),
+    ReleaseNotice(String),
+    TTSModerationUpdated(TTSModeration),
+    DiagnosticsReport(DiagnosticsReport),
+    // the config was changed from outside the UI, e.g. by a chat command
+    ConfigReloaded(AppConfig),
+    ConnectionStateChanged(ConnectionState),
+    // sent whenever a command goes on or comes off cooldown
+    CommandCooldownsUpdated(HashSet<String>),
+    CommandsUpdated(Vec<Command>),
+    CommandExecuted(CommandExecution),
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     // https://github.com/emilk/egui/discussions/4670
-    volume: f64,
-    enabled: bool,
-    permited_roles: PermitedRoles,
+    pub volume: f64,
+    pub enabled: bool,
+    pub permited_roles: PermitedRoles,
+    // decode every sound on launch and log the ones that fail
+    #[serde(default)]
+    pub validate_on_startup: bool,
+    // how sounds interact with TTS that is already playing
+    #[serde(default)]
+    pub audio_policy: AudioPolicy,
+    // sinks created up front for sound effects
+    #[serde(default = "default_sink_pool_size")]
+    pub sink_pool_size: usize,
+    // what to do when a sound is triggered again while it's still playing
+    #[serde(default)]
+    pub repeat_policy: RepeatPolicy,
+    // scale each sound by its peak amplitude so they all play at a similar loudness
+    #[serde(default)]
+    pub normalize: bool,
+    // identical triggers of a sound within this many milliseconds play only once
+    #[serde(default = "default_dedupe_window_ms")]
+    pub dedupe_window_ms: u64,
+    // relative chance of each sound being picked by the random sound command, 1.0 when missing
+    #[serde(default)]
+    pub sound_weights: HashMap<String, f64>,
+    // name of the random sound command, without the prefix
+    #[serde(default = "default_random_command")]
+    pub random_command: String,
+    // sounds the random sound command never picks
+    #[serde(default)]
+    pub random_ignored: Vec<String>,
+    // volume of sounds triggered by each role, relative to the volume above
+    #[serde(default)]
+    pub role_volumes: RoleVolumes,
+    // order of the sounds list, kept between sessions
+    #[serde(default)]
+    pub sort: SoundSort,
+    // play sounds one after another instead of all at once
+    #[serde(default)]
+    pub serial_playback: bool,
+    // sounds allowed to wait in serial playback mode, 0 for no limit
+    #[serde(default = "default_max_queued_sounds")]
+    pub max_queued_sounds: usize,
+    // sounds allowed to play at once outside serial playback mode, 0 for no limit
+    #[serde(default)]
+    pub max_concurrent_sounds: usize,
+    // where sounds are played from, created when missing
+    #[serde(default = "default_sounds_dir")]
+    pub sounds_dir: String,
+}
+
+fn default_max_queued_sounds() -> usize {
+    10
+}
+
+fn default_random_command() -> String {
+    crate::backend::sounds::DEFAULT_RANDOM_SOUND_COMMAND.to_string()
+}
+
+fn default_sounds_dir() -> String {
+    crate::backend::sounds::DEFAULT_SOUNDS_DIRECTORY.to_string()
+}
+
+fn default_dedupe_window_ms() -> u64 {
+    500
+}
+
+fn default_sink_pool_size() -> usize {
+    4
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TTSConfig {
+    pub volume: f64,
+    pub enabled: bool,
+    pub permited_roles: PermitedRoles,
+    #[serde(default = "default_max_queue_length")]
+    pub max_queue_length: usize,
+    // seconds between "queue is full" replies in chat
+    #[serde(default = "default_queue_full_reply_cooldown")]
+    pub queue_full_reply_cooldown: u64,
+    #[serde(default = "default_true")]
+    pub strip_mentions: bool,
+    // read "@name" as "name" instead of dropping the mention entirely
+    #[serde(default = "default_true")]
+    pub keep_mention_names: bool,
+    // SFX file played when synthesis fails, empty to disable
+    #[serde(default)]
+    pub error_sound: String,
+    #[serde(default = "default_error_sound_cooldown")]
+    pub error_sound_cooldown: u64,
+    // pause TTS while chat is too busy, e.g. during a raid
+    #[serde(default)]
+    pub auto_pause: bool,
+    // seconds
+    #[serde(default = "default_auto_pause_window")]
+    pub auto_pause_window: u64,
+    // messages within the window that pause TTS
+    #[serde(default = "default_auto_pause_threshold")]
+    pub auto_pause_threshold: usize,
+    // messages within the window below which TTS resumes
+    #[serde(default = "default_auto_resume_threshold")]
+    pub auto_resume_threshold: usize,
+    // silence between the chunks of a long message, in milliseconds
+    #[serde(default = "default_chunk_gap_ms")]
+    pub chunk_gap_ms: u64,
+    // per-language replacements for `chunk_gap_ms`, keyed by language code
+    #[serde(default)]
+    pub chunk_gap_overrides: HashMap<String, u64>,
+    // times a chunk is synthesized again when its audio fails to decode
+    #[serde(default = "default_decode_retries")]
+    pub decode_retries: u32,
+    // chunks a single message may add to the queue, the rest of the message is dropped
+    #[serde(default = "default_max_chunks_per_message")]
+    pub max_chunks_per_message: usize,
+    // reads the rest of the message in the user's last TTS language, empty to disable
+    #[serde(default = "default_same_language_prefix")]
+    pub same_language_prefix: String,
+    // used by the same-language prefix when the user hasn't used TTS yet
+    #[serde(default = "default_language")]
+    pub default_language: String,
+    // language codes that can't be used for TTS
+    #[serde(default)]
+    pub disabled_languages: Vec<String>,
+    // SFX files played when TTS starts after a quiet period and when the queue drains,
+    // empty to disable
+    #[serde(default)]
+    pub incoming_sound: String,
+    #[serde(default)]
+    pub all_clear_sound: String,
+    // audio the queue may hold in memory, in kilobytes, 0 for no limit
+    #[serde(default = "default_queue_memory_budget_kb")]
+    pub queue_memory_budget_kb: usize,
+    // synthesized audio kept for repeated phrases, in megabytes
+    #[serde(default = "default_cache_size_mb")]
+    pub cache_size_mb: usize,
+    // service the text is synthesized with
+    #[serde(default)]
+    pub provider: TTSProviderKind,
+    // tried in this order when the provider above fails
+    #[serde(default)]
+    pub fallback_providers: Vec<TTSProviderKind>,
+    // users whose last language is remembered for the same-language prefix
+    #[serde(default = "default_remembered_languages")]
+    pub remembered_languages: usize,
+    // seconds before the same user can request TTS again, 0 for no limit
+    #[serde(default)]
+    pub user_cooldown: u64,
+    // whether a full queue rejects new messages or drops the oldest one
+    #[serde(default)]
+    pub queue_full_policy: QueueFullPolicy,
+    #[serde(default)]
+    pub banned_phrase_match: PhraseMatch,
+    // skip messages with a banned phrase or read them with the phrase bleeped out
+    #[serde(default)]
+    pub banned_phrase_action: BannedPhraseAction,
+    // collapse whitespace and strip control and zero-width characters
+    #[serde(default = "default_true")]
+    pub normalize_whitespace: bool,
+    // shortest message read out after sanitizing, in characters
+    #[serde(default = "default_min_message_length")]
+    pub min_message_length: usize,
+    // seconds a synthesis request may take before it is given up on
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+    // attempts after a failed synthesis request, only for server and connection errors
+    #[serde(default = "default_synthesis_retries")]
+    pub synthesis_retries: u32,
+    // milliseconds before the first retry, doubled for each one after it
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub retry_base_delay_ms: u64,
+    // only the first this many words are read, 0 for no limit
+    #[serde(default)]
+    pub max_words: usize,
+    // read after a message cut short by `max_words`
+    #[serde(default = "default_truncation_suffix")]
+    pub truncation_suffix: String,
+    // longest message read in characters, 0 for no limit
+    #[serde(default)]
+    pub max_message_length: usize,
+    // what happens to messages over `max_message_length`
+    #[serde(default)]
+    pub on_overflow: LongMessageAction,
+    // announce who sent each chat message before reading it
+    #[serde(default)]
+    pub read_username: bool,
+    // `{user}` is replaced with the sender's name
+    #[serde(default = "default_username_template")]
+    pub username_template: String,
+    // read every non-command message, guessing its language instead of requiring a !<language>
+    #[serde(default)]
+    pub auto_detect: bool,
+    // chunks of a message that may fail to decode before the rest of it is skipped
+    #[serde(default = "default_max_failed_chunks")]
+    pub max_failed_chunks: u32,
+    #[serde(default = "default_true")]
+    pub strip_urls: bool,
+    // read a word repeated more than twice in a row, e.g. emote spam, only once
+    #[serde(default = "default_true")]
+    pub collapse_repeats: bool,
+    // keep pending messages in tts_queue.json and synthesize them again after a restart
+    #[serde(default)]
+    pub persist_queue: bool,
+    // messages from these roles are read before everyone else's
+    #[serde(default = "default_elevated_roles")]
+    pub elevated_roles: PermitedRoles,
+}
+
+fn default_elevated_roles() -> PermitedRoles {
+    PermitedRoles {
+        subs: false,
+        vips: false,
+        mods: false,
+    }
+}
+
+fn default_max_failed_chunks() -> u32 {
+    2
+}
+
+fn default_username_template() -> String {
+    "{user} says".to_string()
+}
+
+fn default_truncation_suffix() -> String {
+    "and more".to_string()
+}
+
+fn default_request_timeout() -> u64 {
+    10
+}
+
+fn default_synthesis_retries() -> u32 {
+    2
+}
+
+fn default_retry_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_min_message_length() -> usize {
+    1
+}
+
+fn default_remembered_languages() -> usize {
+    1000
+}
+
+fn default_cache_size_mb() -> usize {
+    200
+}
+
+fn default_queue_memory_budget_kb() -> usize {
+    10240
+}
+
+fn default_same_language_prefix() -> String {
+    "!!".to_string()
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+fn default_max_chunks_per_message() -> usize {
+    5
+}
+
+fn default_decode_retries() -> u32 {
+    2
+}
+
+fn default_chunk_gap_ms() -> u64 {
+    100
+}
+
+fn default_auto_pause_window() -> u64 {
+    10
+}
+
+fn default_auto_pause_threshold() -> usize {
+    30
+}
+
+fn default_auto_resume_threshold() -> usize {
+    10
+}
+
+fn default_error_sound_cooldown() -> u64 {
+    10
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_queue_length() -> usize {
+    20
+}
+
+fn default_queue_full_reply_cooldown() -> u64 {
+    30
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -48,12 +447,88 @@ pub struct PermitedRoles {
     pub mods: bool,
 }
 
+#[derive(Serialize, Debug, Clone)]
+pub struct TTSQueueItemUI {
+    pub id: String,
+    pub username: String,
+    pub language: String,
+    pub text: String,
+    pub source: TTSSource,
+    // the message being read out right now
+    pub playing: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UIConfig {
+    pub keyboard_navigation: bool,
+    pub key_bindings: KeyBindings,
+    // pop up log messages in the corner of the window as well
+    #[serde(default = "default_true")]
+    pub toasts: bool,
+}
+
+impl Default for UIConfig {
+    fn default() -> Self {
+        Self {
+            keyboard_navigation: true,
+            toasts: true,
+            key_bindings: KeyBindings {
+                home: "1".to_string(),
+                sfx: "2".to_string(),
+                tts: "3".to_string(),
+                settings: "4".to_string(),
+                commands: default_commands_key(),
+            },
+        }
+    }
+}
+
+// key names as understood by `egui::Key::from_name`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeyBindings {
+    pub home: String,
+    pub sfx: String,
+    pub tts: String,
+    pub settings: String,
+    #[serde(default = "default_commands_key")]
+    pub commands: String,
+}
+
+fn default_commands_key() -> String {
+    "5".to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionState {
+    Disconnected,
+    Reconnecting,
+    Connected,
+}
+
+impl ConnectionState {
+    fn label(&self) -> &'static str {
+        match self {
+            ConnectionState::Disconnected => "Disconnected",
+            ConnectionState::Reconnecting => "Reconnecting",
+            ConnectionState::Connected => "Connected",
+        }
+    }
+
+    fn indicator_color(&self) -> Color32 {
+        match self {
+            ConnectionState::Disconnected => Color32::from_rgb(255, 50, 0),
+            ConnectionState::Reconnecting => Color32::from_rgb(255, 190, 0),
+            ConnectionState::Connected => Color32::from_rgb(0, 255, 0),
+        }
+    }
+}
+
 struct ChatbotUILabels {
     bot_status: String,
     connect_button: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum LogLevel {
     INFO,
     WARN,
@@ -78,6 +553,59 @@ struct LogMessage {
 pub struct ChatbotConfig {
     pub channel_name: String,
     pub auth_token: String,
+    // longest message passed on to commands and TTS, 0 for no limit
+    #[serde(default = "default_max_message_length")]
+    pub max_message_length: usize,
+    #[serde(default)]
+    pub long_message_action: LongMessageAction,
+    // what chat commands start with, `!` when empty
+    #[serde(default = "default_command_prefix")]
+    pub command_prefix: String,
+    // connect again on its own when the chat connection drops
+    #[serde(default = "default_auto_reconnect")]
+    pub auto_reconnect: bool,
+    // reconnects tried in a row before giving up, 0 for no limit
+    #[serde(default = "default_max_reconnect_attempts")]
+    pub max_reconnect_attempts: u32,
+    // joined together with `channel_name`, e.g. channels the bot moderates
+    #[serde(default)]
+    pub extra_channels: Vec<String>,
+    // TTS and sounds react to the extra channels too, otherwise they only answer commands
+    #[serde(default)]
+    pub share_audio_across_channels: bool,
+    // store the token encrypted with a key kept next to the config, see backend::secrets
+    #[serde(default = "default_true")]
+    pub encrypt_tokens: bool,
+}
+
+impl ChatbotConfig {
+    /// `channel_name` followed by the extra channels, without blanks or duplicates.
+    pub fn channels(&self) -> Vec<String> {
+        let mut channels: Vec<String> = Vec::new();
+        for channel in std::iter::once(&self.channel_name).chain(self.extra_channels.iter()) {
+            let channel = channel.trim().to_lowercase();
+            if !channel.is_empty() && !channels.contains(&channel) {
+                channels.push(channel);
+            }
+        }
+        channels
+    }
+}
+
+fn default_max_message_length() -> usize {
+    300
+}
+
+fn default_auto_reconnect() -> bool {
+    true
+}
+
+fn default_max_reconnect_attempts() -> u32 {
+    10
+}
+
+fn default_command_prefix() -> String {
+    crate::backend::commands::DEFAULT_PREFIX.to_string()
 }
 
 pub struct Chatbot {
@@ -86,9 +614,32 @@ pub struct Chatbot {
     frontend_tx: tokio::sync::mpsc::Sender<FrontendToBackendMessage>,
     frontend_rx: tokio::sync::mpsc::Receiver<BackendToFrontendMessage>,
     labels: ChatbotUILabels,
+    connection_state: ConnectionState,
     log_messages: Vec<LogMessage>,
     sfx_config: Config,
-    tts_config: Config,
+    tts_config: TTSConfig,
+    tts_queue: Vec<TTSQueueItemUI>,
+    ui_config: UIConfig,
+    safe_mode: bool,
+    release_notice: Option<String>,
+    tts_moderation: TTSModeration,
+    commands: Vec<Command>,
+    commands_on_cooldown: HashSet<String>,
+    // newest first, at most MAX_COMMAND_EXECUTIONS
+    command_executions: VecDeque<CommandExecution>,
+    sounds: Vec<SoundInfo>,
+    sound_search: String,
+    language_search: String,
+    banned_phrase_input: String,
+    ignored_user_input: String,
+    moderation_file: String,
+    commands_file: String,
+    // the command being added or edited, None when the form is closed
+    command_editor: Option<commands::CommandEditor>,
+    toasts: toasts::Toasts,
+    window_geometry: GeometrySaver,
+    // comma separated, parsed into the config on save
+    extra_channels_input: String,
 }
 
 impl Chatbot {
@@ -97,9 +648,12 @@ impl Chatbot {
         frontend_tx: tokio::sync::mpsc::Sender<FrontendToBackendMessage>,
         frontend_rx: tokio::sync::mpsc::Receiver<BackendToFrontendMessage>,
         sfx_config: Config,
-        tts_config: Config
+        tts_config: TTSConfig,
+        ui_config: UIConfig,
+        safe_mode: bool
     ) -> Self {
         Self {
+            extra_channels_input: config.extra_channels.join(", "),
             config,
             selected_section: Section::Home,
             frontend_tx: frontend_tx,
@@ -108,15 +662,120 @@ impl Chatbot {
                 bot_status: "Disconnected".to_string(),
                 connect_button: "Connect".to_string(),
             },
+            connection_state: ConnectionState::Disconnected,
             log_messages: Vec::new(),
             sfx_config,
             tts_config,
+            tts_queue: Vec::new(),
+            ui_config,
+            safe_mode,
+            release_notice: None,
+            tts_moderation: TTSModeration::default(),
+            commands: Vec::new(),
+            commands_on_cooldown: HashSet::new(),
+            command_executions: VecDeque::new(),
+            sounds: Vec::new(),
+            sound_search: String::new(),
+            language_search: String::new(),
+            banned_phrase_input: String::new(),
+            ignored_user_input: String::new(),
+            moderation_file: "tts_moderation_export.json".to_string(),
+            commands_file: "commands_export.json".to_string(),
+            command_editor: None,
+            toasts: toasts::Toasts::default(),
+            window_geometry: GeometrySaver::default(),
+        }
+    }
+
+    fn log(&mut self, log_level: LogLevel, message: &str) {
+        if self.ui_config.toasts {
+            self.toasts.push(log_level, message);
+        }
+        self.log_messages.push(LogMessage {
+            message: message.to_string(),
+            timestamp: chrono::Local::now().to_string(),
+            log_level,
+        });
+    }
+
+    /// Sends a message to the backend, logging why it couldn't be sent instead of panicking.
+    /// Returns whether the message was sent.
+    fn send(&mut self, message: FrontendToBackendMessage) -> bool {
+        if message.requires_connection() && self.connection_state == ConnectionState::Disconnected {
+            self.log(LogLevel::WARN, "Not connected to chat, connect first");
+            return false;
+        }
+        match self.frontend_tx.try_send(message) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.log(LogLevel::WARN, "The bot is busy, try again in a moment");
+                false
+            }
+            Err(TrySendError::Closed(_)) => {
+                self.log(LogLevel::ERROR, "The bot stopped responding, restart the app");
+                false
+            }
+        }
+    }
+
+    fn set_connection_state(&mut self, state: ConnectionState) {
+        self.connection_state = state;
+        self.labels.bot_status = state.label().to_string();
+    }
+
+    /// Switches sections with the configured keys, unless a text field has keyboard focus.
+    fn open_section(&mut self, section: Section) {
+        if matches!(section, Section::Tts) {
+            self.send(FrontendToBackendMessage::GetTTSQueue);
+        }
+        self.selected_section = section;
+    }
+
+    fn track_window_geometry(&mut self, ctx: &egui::Context) {
+        let (outer, inner, minimized) = ctx.input(|input| {
+            let viewport = input.viewport();
+            (viewport.outer_rect, viewport.inner_rect, viewport.minimized.unwrap_or(false))
+        });
+        let (Some(outer), Some(inner)) = (outer, inner) else {
+            return;
+        };
+        if minimized {
+            return;
+        }
+        self.window_geometry.update(WindowGeometry {
+            x: outer.min.x,
+            y: outer.min.y,
+            width: inner.width(),
+            height: inner.height(),
+        });
+    }
+
+    fn handle_section_keys(&mut self, ctx: &egui::Context) {
+        if !self.ui_config.keyboard_navigation || ctx.wants_keyboard_input() {
+            return;
+        }
+        let bindings = &self.ui_config.key_bindings;
+        let section = [
+            (&bindings.home, Section::Home),
+            (&bindings.sfx, Section::Sfx),
+            (&bindings.tts, Section::Tts),
+            (&bindings.commands, Section::Commands),
+            (&bindings.settings, Section::Settings),
+        ]
+            .into_iter()
+            .find_map(|(name, section)| {
+                let key = egui::Key::from_name(name)?;
+                ctx.input(|input| input.key_pressed(key)).then_some(section)
+            });
+        if let Some(section) = section {
+            self.open_section(section);
         }
     }
 }
 
 impl eframe::App for Chatbot {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.handle_section_keys(ctx);
         TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.set_height(25.0);
@@ -125,19 +784,35 @@ impl eframe::App for Chatbot {
                     ui.image(egui::include_image!("../../assets/img/logo.png"));
                     ui.label("Yambot");
                 });
-                ui.add_space(ui.available_width() - (ui.available_width() - 495.0));
-                ui.horizontal(|ui| {
-                    if ui.button("HOME").clicked() {
-                        self.selected_section = Section::Home;
-                    }
-                    if ui.button("SFX").clicked() {
-                        self.selected_section = Section::Sfx;
-                    }
-                    if ui.button("TTS").clicked() {
-                        self.selected_section = Section::Tts;
+                // laid out from the right edge, so the buttons stay there at any window width
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let safe_mode_button = if self.safe_mode {
+                        let label = egui::widget_text::RichText
+                            ::new("SAFE MODE ON")
+                            .color(Color32::WHITE);
+                        egui::Button::new(label).fill(Color32::from_rgb(200, 30, 0))
+                    } else {
+                        egui::Button::new("Safe mode")
+                    };
+                    if
+                        ui
+                            .add(safe_mode_button)
+                            .on_hover_text("Turns off TTS, sounds and reactions to chat")
+                            .clicked()
+                    {
+                        self.send(FrontendToBackendMessage::SetSafeMode(!self.safe_mode));
                     }
-                    if ui.button("SETTINGS").clicked() {
-                        self.selected_section = Section::Settings;
+                    ui.separator();
+                    for (label, section) in [
+                        ("SETTINGS", Section::Settings),
+                        ("COMMANDS", Section::Commands),
+                        ("TTS", Section::Tts),
+                        ("SFX", Section::Sfx),
+                        ("HOME", Section::Home),
+                    ] {
+                        if ui.button(label).clicked() {
+                            self.open_section(section);
+                        }
                     }
                 });
             });
@@ -148,10 +823,27 @@ impl eframe::App for Chatbot {
                 Section::Home => self.show_home(ui),
                 Section::Sfx => self.show_sfx(ui),
                 Section::Tts => self.show_tts(ui),
+                Section::Commands => self.show_commands(ui),
                 Section::Settings => self.show_settings(ui),
             }
         });
 
+        if let Some(notice) = &self.release_notice {
+            let mut dismissed = false;
+            egui::Window
+                ::new("What's new")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(notice);
+                    dismissed = ui.button("OK").clicked();
+                });
+            if dismissed {
+                self.release_notice = None;
+            }
+        }
+
         TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
             ui.vertical_centered(|ui| {
                 ui.label(format!("Version: {}", env!("CARGO_PKG_VERSION")));
@@ -162,19 +854,123 @@ impl eframe::App for Chatbot {
         while let Ok(message) = self.frontend_rx.try_recv() {
             match message {
                 BackendToFrontendMessage::ConnectionSuccess(response) => {
+                    if self.ui_config.toasts {
+                        self.toasts.push(LogLevel::INFO, &response);
+                    }
                     self.labels.bot_status = response;
                     self.labels.connect_button = "Disconnect".to_string();
                 }
                 BackendToFrontendMessage::ConnectionFailure(response) => {
+                    if self.ui_config.toasts {
+                        self.toasts.push(LogLevel::ERROR, &response);
+                    }
                     self.labels.bot_status = response;
                     self.labels.connect_button = "Connect".to_string();
                 }
+                BackendToFrontendMessage::CreateLog(log_level, message) => {
+                    self.log(log_level, &message);
+                }
+                BackendToFrontendMessage::TTSQueueUpdated(queue) => {
+                    self.tts_queue = queue;
+                }
+                BackendToFrontendMessage::ReleaseNotice(notice) => {
+                    self.release_notice = Some(notice);
+                }
+                BackendToFrontendMessage::TTSModerationUpdated(moderation) => {
+                    self.tts_moderation = moderation;
+                }
+                BackendToFrontendMessage::CommandsUpdated(commands) => {
+                    self.commands = commands;
+                }
+                BackendToFrontendMessage::CommandCooldownsUpdated(cooling) => {
+                    self.commands_on_cooldown = cooling;
+                }
+                BackendToFrontendMessage::CommandExecuted(execution) => {
+                    self.command_executions.push_front(execution);
+                    self.command_executions.truncate(MAX_COMMAND_EXECUTIONS);
+                }
+                BackendToFrontendMessage::SFXListUpdated(sounds) => {
+                    self.sounds = sounds;
+                }
+                BackendToFrontendMessage::ConnectionStateChanged(state) => {
+                    self.set_connection_state(state);
+                }
+                BackendToFrontendMessage::ConfigReloaded(config) => {
+                    self.extra_channels_input = config.chatbot.extra_channels.join(", ");
+                    self.config = config.chatbot;
+                    self.sfx_config = config.sfx;
+                    self.tts_config = config.tts;
+                    self.ui_config = config.ui;
+                    self.safe_mode = config.safe_mode;
+                }
+                BackendToFrontendMessage::DiagnosticsReport(report) => {
+                    ctx.copy_text(report.to_string());
+                    self.log(LogLevel::INFO, "Diagnostics copied to clipboard");
+                }
                 _ => {
                     println!("Received message");
                 }
             }
         }
+        self.toasts.show(ctx);
+        self.track_window_geometry(ctx);
 
         ctx.request_repaint();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chatbot() -> (Chatbot, tokio::sync::mpsc::Receiver<FrontendToBackendMessage>) {
+        let (frontend_tx, frontend_rx) = tokio::sync::mpsc::channel(10);
+        let (_, backend_rx) = tokio::sync::mpsc::channel(10);
+        let config = AppConfig::default();
+        let chatbot = Chatbot::new(
+            config.chatbot,
+            frontend_tx,
+            backend_rx,
+            config.sfx,
+            config.tts,
+            config.ui,
+            false
+        );
+        (chatbot, frontend_rx)
+    }
+
+    fn run_command() -> FrontendToBackendMessage {
+        FrontendToBackendMessage::RunCommand {
+            trigger: "hello".to_string(),
+            as_broadcaster: false,
+        }
+    }
+
+    #[test]
+    fn only_command_runs_require_a_connection() {
+        assert!(run_command().requires_connection());
+        assert!(!FrontendToBackendMessage::SkipTTS.requires_connection());
+        assert!(!FrontendToBackendMessage::SetSafeMode(true).requires_connection());
+    }
+
+    #[test]
+    fn sending_while_disconnected_reports_not_connected() {
+        let (mut chatbot, mut frontend_rx) = chatbot();
+        assert!(!chatbot.send(run_command()));
+        assert!(frontend_rx.try_recv().is_err());
+        let log = chatbot.log_messages.last().unwrap();
+        assert!(matches!(log.log_level, LogLevel::WARN));
+        assert_eq!(log.message, "Not connected to chat, connect first");
+        // messages that don't need chat still go through
+        assert!(chatbot.send(FrontendToBackendMessage::SkipTTS));
+        assert!(matches!(frontend_rx.try_recv(), Ok(FrontendToBackendMessage::SkipTTS)));
+    }
+
+    #[test]
+    fn sending_while_connected_passes_command_runs_on() {
+        let (mut chatbot, mut frontend_rx) = chatbot();
+        chatbot.set_connection_state(ConnectionState::Connected);
+        assert!(chatbot.send(run_command()));
+        assert!(matches!(frontend_rx.try_recv(), Ok(FrontendToBackendMessage::RunCommand { .. })));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::backend::limits::LongMessageAction;
+
 use super::{ FrontendToBackendMessage, Chatbot, ChatbotConfig };
 
 impl Chatbot {
@@ -7,19 +9,74 @@ impl Chatbot {
                 ui.label("Channel name:");
                 ui.text_edit_singleline(&mut self.config.channel_name);
             });
+            ui.horizontal(|ui| {
+                ui.label("Extra channels (comma separated):");
+                ui.text_edit_singleline(&mut self.extra_channels_input);
+            });
+            ui.checkbox(
+                &mut self.config.share_audio_across_channels,
+                "TTS and sounds react to the extra channels too"
+            );
             ui.horizontal(|ui| {
                 ui.label("Auth token:");
                 ui.text_edit_singleline(&mut self.config.auth_token);
             });
+            ui.horizontal(|ui| {
+                ui.label("Command prefix:");
+                ui.add(
+                    egui::TextEdit
+                        ::singleline(&mut self.config.command_prefix)
+                        .desired_width(40.0)
+                );
+                ui.label(
+                    egui::widget_text::RichText
+                        ::new("applies on the next connect, empty means !")
+                        .small()
+                        .weak()
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Max message length (0 for no limit):");
+                ui.add(egui::DragValue::new(&mut self.config.max_message_length).range(0..=500));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Longer messages are:");
+                ui.radio_value(
+                    &mut self.config.long_message_action,
+                    LongMessageAction::Truncate,
+                    "Truncated"
+                );
+                ui.radio_value(
+                    &mut self.config.long_message_action,
+                    LongMessageAction::Drop,
+                    "Ignored"
+                );
+            });
+            ui.checkbox(&mut self.config.auto_reconnect, "Reconnect when the connection drops");
+            ui.add_enabled_ui(self.config.auto_reconnect, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Reconnect attempts (0 for no limit):");
+                    ui.add(
+                        egui::DragValue::new(&mut self.config.max_reconnect_attempts).range(0..=100)
+                    );
+                });
+            });
+            if ui.checkbox(&mut self.ui_config.toasts, "Pop up log messages").changed() {
+                self.send(FrontendToBackendMessage::UpdateUIConfig(self.ui_config.clone()));
+            }
             if ui.button("Save").clicked() {
-                let _ = self.frontend_tx
-                    .try_send(
-                        FrontendToBackendMessage::UpdateConfig(ChatbotConfig {
-                            channel_name: self.config.channel_name.clone(),
-                            auth_token: self.config.auth_token.clone(),
-                        })
-                    )
-                    .unwrap();
+                self.config.extra_channels = self.extra_channels_input
+                    .split(',')
+                    .map(|channel| channel.trim().to_string())
+                    .filter(|channel| !channel.is_empty())
+                    .collect();
+                self.send(
+                    FrontendToBackendMessage::UpdateConfig(ChatbotConfig {
+                        channel_name: self.config.channel_name.clone(),
+                        auth_token: self.config.auth_token.clone(),
+                        ..self.config.clone()
+                    })
+                );
             }
         });
     }
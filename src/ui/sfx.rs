@@ -1,12 +1,22 @@
 use egui::Color32;
 
-use super::Chatbot;
+use crate::backend::sounds::{ self, SoundSort };
+
+use super::{ Chatbot, FrontendToBackendMessage };
 
 impl Chatbot {
     pub fn show_sfx(&mut self, ui: &mut egui::Ui) {
+        let dropped_files = ui.ctx().input(|input| input.raw.dropped_files.clone());
+        for file in dropped_files {
+            if let Some(path) = file.path {
+                let _ = self.frontend_tx.try_send(FrontendToBackendMessage::ImportSound(path));
+            }
+        }
         ui.set_height(ui.available_height());
         ui.horizontal(|ui| {
+            let settings_width = super::settings_column_width(ui.available_width());
             ui.vertical(|ui| {
+                ui.set_width(settings_width);
                 ui.horizontal(|ui: &mut egui::Ui| {
                     ui.label("SFX status: ");
                     if ui.button(if self.sfx_config.enabled { "ON" } else { "OFF" }).clicked() {
@@ -15,78 +25,170 @@ impl Chatbot {
                         } else {
                             self.sfx_config.enabled = true;
                         }
-                        self.frontend_tx
-                            .try_send(
-                                super::FrontendToBackendMessage::UpdateSfxConfig(
-                                    self.sfx_config.clone()
-                                )
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateSfxConfig(
+                                self.sfx_config.clone()
                             )
-                            .unwrap();
+                        );
                     }
                 });
                 ui.add_space(10.0);
                 ui.label("SFX volume (0-1 range):");
                 if ui.add(egui::Slider::new(&mut self.sfx_config.volume, 0.0..=1.0)).drag_stopped() {
-                    self.frontend_tx
-                        .try_send(
-                            super::FrontendToBackendMessage::UpdateSfxConfig(
-                                self.sfx_config.clone()
-                            )
-                        )
-                        .unwrap();
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateSfxConfig(self.sfx_config.clone())
+                    );
+                }
+                ui.add_space(10.0);
+                ui.label("Sounds directory:");
+                if ui.text_edit_singleline(&mut self.sfx_config.sounds_dir).lost_focus() {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateSfxConfig(self.sfx_config.clone())
+                    );
                 }
                 ui.add_space(10.0);
                 ui.label("SFX permissions:");
                 if ui.checkbox(&mut self.sfx_config.permited_roles.subs, "Subs").changed() {
-                    self.frontend_tx
-                        .try_send(
-                            super::FrontendToBackendMessage::UpdateSfxConfig(
-                                self.sfx_config.clone()
-                            )
-                        )
-                        .unwrap();
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateSfxConfig(self.sfx_config.clone())
+                    );
                 }
                 if ui.checkbox(&mut self.sfx_config.permited_roles.vips, "VIPS").changed() {
-                    self.frontend_tx
-                        .try_send(
-                            super::FrontendToBackendMessage::UpdateSfxConfig(
-                                self.sfx_config.clone()
-                            )
-                        )
-                        .unwrap();
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateSfxConfig(self.sfx_config.clone())
+                    );
                 }
                 if ui.checkbox(&mut self.sfx_config.permited_roles.mods, "Mods").changed() {
-                    self.frontend_tx
-                        .try_send(
-                            super::FrontendToBackendMessage::UpdateSfxConfig(
-                                self.sfx_config.clone()
-                            )
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateSfxConfig(self.sfx_config.clone())
+                    );
+                }
+                ui.add_space(10.0);
+                ui.label("Volume by role (relative to SFX volume):");
+                let mut role_volumes_changed = false;
+                for (label, multiplier) in [
+                    ("Subs", &mut self.sfx_config.role_volumes.subs),
+                    ("VIPS", &mut self.sfx_config.role_volumes.vips),
+                    ("Mods", &mut self.sfx_config.role_volumes.mods),
+                ] {
+                    role_volumes_changed |= ui
+                        .add(
+                            egui::Slider
+                                ::new(multiplier, 0.0..=crate::backend::volume::MAX_ROLE_MULTIPLIER)
+                                .text(label)
                         )
-                        .unwrap();
+                        .drag_stopped();
+                }
+                if role_volumes_changed {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateSfxConfig(self.sfx_config.clone())
+                    );
+                }
+                ui.add_space(10.0);
+                if ui.checkbox(&mut self.sfx_config.normalize, "Normalize loudness").changed() {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateSfxConfig(self.sfx_config.clone())
+                    );
+                }
+                let mut playback_changed = ui
+                    .checkbox(&mut self.sfx_config.serial_playback, "Play sounds one at a time")
+                    .changed();
+                ui.horizontal(|ui| {
+                    if self.sfx_config.serial_playback {
+                        ui.label("Max queued sounds (0 for no limit):");
+                        playback_changed |= ui
+                            .add(
+                                egui::DragValue
+                                    ::new(&mut self.sfx_config.max_queued_sounds)
+                                    .range(0..=100)
+                            )
+                            .changed();
+                    } else {
+                        ui.label("Max sounds at once (0 for no limit):");
+                        playback_changed |= ui
+                            .add(
+                                egui::DragValue
+                                    ::new(&mut self.sfx_config.max_concurrent_sounds)
+                                    .range(0..=100)
+                            )
+                            .changed();
+                    }
+                });
+                if playback_changed {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateSfxConfig(self.sfx_config.clone())
+                    );
                 }
-                ui.add_space(350.0);
             });
-            ui.add_space(250.0);
             ui.separator();
             ui.vertical(|ui| {
                 ui.set_height(ui.available_height());
                 ui.heading(
                     egui::widget_text::RichText::new("Available sounds").color(Color32::WHITE)
                 );
+                ui.horizontal(|ui| {
+                    ui.label("Search:");
+                    ui.text_edit_singleline(&mut self.sound_search);
+                    let previous = self.sfx_config.sort;
+                    egui::ComboBox
+                        ::from_id_salt("sound_sort")
+                        .selected_text(self.sfx_config.sort.label())
+                        .show_ui(ui, |ui| {
+                            for sort in SoundSort::ALL {
+                                ui.selectable_value(&mut self.sfx_config.sort, sort, sort.label());
+                            }
+                        });
+                    if self.sfx_config.sort != previous {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateSfxConfig(
+                                self.sfx_config.clone()
+                            )
+                        );
+                    }
+                    if ui.button("Refresh").clicked() {
+                        self.send(FrontendToBackendMessage::RefreshSounds);
+                    }
+                    if ui.button("Stop all").clicked() {
+                        self.send(FrontendToBackendMessage::StopSounds);
+                    }
+                });
                 egui::ScrollArea
                     ::vertical()
                     .max_height(ui.available_height() - 100.0)
                     .max_width(ui.available_width())
                     .auto_shrink(false)
                     .show(ui, |ui| {
-                        for i in 0..100 {
+                        let shown = sounds::sort_and_filter(
+                            &self.sounds,
+                            &self.sound_search,
+                            self.sfx_config.sort
+                        );
+                        let mut preview = None;
+                        for (i, sound) in shown.into_iter().enumerate() {
                             ui.horizontal(|ui| {
-                                ui.label(i.to_string());
-                                ui.label("sound name");
+                                if ui.small_button("▶").on_hover_text("Preview").clicked() {
+                                    preview = Some(sound.name.clone());
+                                }
+                                ui.label((i + 1).to_string());
+                                ui.label(&sound.name);
+                                if sound.plays > 0 {
+                                    ui.label(
+                                        egui::widget_text::RichText
+                                            ::new(format!("played {}x", sound.plays))
+                                            .small()
+                                            .weak()
+                                    );
+                                }
                             });
                             ui.separator();
                         }
+                        if let Some(name) = preview {
+                            self.send(FrontendToBackendMessage::PlaySound(name));
+                        }
                     });
+                ui.label(
+                    egui::widget_text::RichText::new("Drop audio files here to add them").weak()
+                );
             });
         });
     }
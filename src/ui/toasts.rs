@@ -0,0 +1,58 @@
+use std::collections::VecDeque;
+use std::time::{ Duration, Instant };
+
+use super::LogLevel;
+
+// how long a toast stays on screen
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+// the oldest toast makes room once this many are showing
+const MAX_TOASTS: usize = 5;
+
+struct Toast {
+    level: LogLevel,
+    message: String,
+    shown_at: Instant,
+}
+
+/// Short-lived notifications in the corner of the window, so log messages get noticed without
+/// watching the log panel.
+#[derive(Default)]
+pub struct Toasts {
+    toasts: VecDeque<Toast>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, level: LogLevel, message: &str) {
+        if self.toasts.len() >= MAX_TOASTS {
+            self.toasts.pop_front();
+        }
+        self.toasts.push_back(Toast {
+            level,
+            message: message.to_string(),
+            shown_at: Instant::now(),
+        });
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        self.toasts.retain(|toast| toast.shown_at.elapsed() < TOAST_DURATION);
+        if self.toasts.is_empty() {
+            return;
+        }
+        egui::Area
+            ::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -50.0])
+            .order(egui::Order::Foreground)
+            .interactable(false)
+            .show(ctx, |ui| {
+                for toast in self.toasts.iter() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(
+                            egui::widget_text::RichText
+                                ::new(&toast.message)
+                                .color(toast.level.color())
+                        );
+                    });
+                }
+            });
+    }
+}
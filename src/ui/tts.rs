@@ -1,10 +1,18 @@
-use super::Chatbot;
+use crate::backend::limits::LongMessageAction;
+use crate::backend::tts::languages;
+use crate::backend::tts::moderation::{ BannedPhraseAction, ModerationList, PhraseMatch };
+use crate::backend::tts::provider::TTSProviderKind;
+use crate::backend::tts::queue::QueueFullPolicy;
+
+use super::{ Chatbot, FrontendToBackendMessage };
 
 impl Chatbot {
     pub fn show_tts(&mut self, ui: &mut egui::Ui) {
         ui.set_height(ui.available_height());
         ui.horizontal(|ui| {
+            let settings_width = super::settings_column_width(ui.available_width());
             ui.vertical(|ui| {
+                ui.set_width(settings_width);
                 ui.horizontal(|ui: &mut egui::Ui| {
                     ui.label("TTS status: ");
                     if ui.button(if self.tts_config.enabled { "ON" } else { "OFF" }).clicked() {
@@ -13,62 +21,552 @@ impl Chatbot {
                         } else {
                             self.tts_config.enabled = true;
                         }
-                        self.frontend_tx
-                            .try_send(
-                                super::FrontendToBackendMessage::UpdateTTSConfig(
-                                    self.tts_config.clone()
-                                )
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
                             )
-                            .unwrap();
+                        );
                     }
                 });
                 ui.add_space(10.0);
                 ui.label("TTS volume (0-1 range):");
                 // funny cus this returns giant floating point numbers
                 if ui.add(egui::Slider::new(&mut self.tts_config.volume, 0.0..=1.0)).drag_stopped() {
-                    self.frontend_tx
-                        .try_send(
-                            super::FrontendToBackendMessage::UpdateTTSConfig(
-                                self.tts_config.clone()
-                            )
-                        )
-                        .unwrap();
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
                 }
                 ui.add_space(10.0);
                 ui.label("TTS permissions:");
                 if ui.checkbox(&mut self.tts_config.permited_roles.subs, "Subs").changed() {
-                    self.frontend_tx
-                        .try_send(
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                if ui.checkbox(&mut self.tts_config.permited_roles.vips, "VIPS").changed() {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                if ui.checkbox(&mut self.tts_config.permited_roles.mods, "Mods").changed() {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                ui.add_space(10.0);
+                ui.label("Read first:");
+                if ui.checkbox(&mut self.tts_config.elevated_roles.subs, "Subs").changed() {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                if ui.checkbox(&mut self.tts_config.elevated_roles.vips, "VIPS").changed() {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                if ui.checkbox(&mut self.tts_config.elevated_roles.mods, "Mods").changed() {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    let queued = self.tts_queue
+                        .iter()
+                        .filter(|item| !item.playing)
+                        .count();
+                    ui.label(format!("TTS queue: {}/{}", queued, self.tts_config.max_queue_length));
+                    let playing = self.tts_queue.iter().any(|item| item.playing);
+                    if ui.add_enabled(playing, egui::Button::new("Skip current")).clicked() {
+                        self.send(super::FrontendToBackendMessage::SkipTTS);
+                    }
+                    if ui.add_enabled(queued > 0, egui::Button::new("Clear queue")).clicked() {
+                        self.send(super::FrontendToBackendMessage::ClearTTSQueue);
+                    }
+                });
+                let mut skipped = None;
+                for item in self.tts_queue.iter() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("Skip").clicked() {
+                            skipped = Some(item.id.clone());
+                        }
+                        ui.label(
+                            egui::widget_text::RichText
+                                ::new(format!("[{}] [{}]", item.source.label(), item.language))
+                                .small()
+                                .weak()
+                        );
+                        let text = egui::widget_text::RichText::new(
+                            format!("{}: {}", item.username, item.text)
+                        );
+                        if item.playing {
+                            ui.label(text.strong().color(egui::Color32::LIGHT_GREEN));
+                        } else {
+                            ui.label(text);
+                        }
+                    });
+                }
+                if let Some(id) = skipped {
+                    self.send(super::FrontendToBackendMessage::SkipTTSMessage(id));
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Max queue length:");
+                    if
+                        ui
+                            .add(egui::DragValue::new(&mut self.tts_config.max_queue_length).range(1..=100))
+                            .changed()
+                    {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("When full:");
+                    let previous = self.tts_config.queue_full_policy;
+                    ui.radio_value(
+                        &mut self.tts_config.queue_full_policy,
+                        QueueFullPolicy::Reject,
+                        "Reject new messages"
+                    );
+                    ui.radio_value(
+                        &mut self.tts_config.queue_full_policy,
+                        QueueFullPolicy::DropOldest,
+                        "Drop the oldest"
+                    );
+                    if self.tts_config.queue_full_policy != previous {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Per-user cooldown (seconds):");
+                    if
+                        ui
+                            .add(egui::DragValue::new(&mut self.tts_config.user_cooldown).range(0..=3600))
+                            .changed()
+                    {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Max words (0 for no limit):");
+                    if
+                        ui
+                            .add(egui::DragValue::new(&mut self.tts_config.max_words).range(0..=200))
+                            .changed()
+                    {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Cut messages end with:");
+                    if ui.text_edit_singleline(&mut self.tts_config.truncation_suffix).lost_focus() {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Max characters (0 for no limit):");
+                    if
+                        ui
+                            .add(
+                                egui::DragValue
+                                    ::new(&mut self.tts_config.max_message_length)
+                                    .range(0..=500)
+                            )
+                            .changed()
+                    {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                    let previous = self.tts_config.on_overflow;
+                    ui.radio_value(
+                        &mut self.tts_config.on_overflow,
+                        LongMessageAction::Truncate,
+                        "Truncate"
+                    );
+                    ui.radio_value(&mut self.tts_config.on_overflow, LongMessageAction::Drop, "Skip");
+                    if self.tts_config.on_overflow != previous {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Request timeout (seconds):");
+                    if
+                        ui
+                            .add(egui::DragValue::new(&mut self.tts_config.request_timeout).range(1..=60))
+                            .changed()
+                    {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Retries on server errors:");
+                    if
+                        ui
+                            .add(egui::DragValue::new(&mut self.tts_config.synthesis_retries).range(0..=5))
+                            .changed()
+                    {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                    ui.label("first retry after (ms):");
+                    if
+                        ui
+                            .add(
+                                egui::DragValue
+                                    ::new(&mut self.tts_config.retry_base_delay_ms)
+                                    .range(0..=10000)
+                            )
+                            .changed()
+                    {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Max chunks per message:");
+                    if
+                        ui
+                            .add(
+                                egui::DragValue
+                                    ::new(&mut self.tts_config.max_chunks_per_message)
+                                    .range(1..=20)
+                            )
+                            .changed()
+                    {
+                        self.send(
                             super::FrontendToBackendMessage::UpdateTTSConfig(
                                 self.tts_config.clone()
                             )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Provider:");
+                    let previous = self.tts_config.provider;
+                    egui::ComboBox
+                        ::from_id_salt("tts_provider")
+                        .selected_text(self.tts_config.provider.label())
+                        .show_ui(ui, |ui| {
+                            for provider in TTSProviderKind::ALL {
+                                ui.selectable_value(
+                                    &mut self.tts_config.provider,
+                                    provider,
+                                    provider.label()
+                                );
+                            }
+                        });
+                    if self.tts_config.provider != previous {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Fall back to:");
+                    for provider in TTSProviderKind::ALL {
+                        if provider == self.tts_config.provider {
+                            continue;
+                        }
+                        let mut enabled = self.tts_config.fallback_providers.contains(&provider);
+                        if ui.checkbox(&mut enabled, provider.label()).changed() {
+                            if enabled {
+                                self.tts_config.fallback_providers.push(provider);
+                            } else {
+                                self.tts_config.fallback_providers.retain(|fallback| *fallback != provider);
+                            }
+                            self.send(
+                                super::FrontendToBackendMessage::UpdateTTSConfig(
+                                    self.tts_config.clone()
+                                )
+                            );
+                        }
+                    }
+                });
+                ui.add_space(10.0);
+                if ui.checkbox(&mut self.tts_config.strip_urls, "Strip links").changed() {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                if
+                    ui
+                        .checkbox(&mut self.tts_config.collapse_repeats, "Collapse repeated words")
+                        .changed()
+                {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                if
+                    ui
+                        .checkbox(&mut self.tts_config.persist_queue, "Keep queue across restarts")
+                        .changed()
+                {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                if ui.checkbox(&mut self.tts_config.strip_mentions, "Strip @mentions").changed() {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                if
+                    ui
+                        .add_enabled(
+                            self.tts_config.strip_mentions,
+                            egui::Checkbox::new(
+                                &mut self.tts_config.keep_mention_names,
+                                "Keep mentioned names"
+                            )
                         )
-                        .unwrap();
+                        .changed()
+                {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
                 }
-                if ui.checkbox(&mut self.tts_config.permited_roles.vips, "VIPS").changed() {
-                    self.frontend_tx
-                        .try_send(
+                if
+                    ui
+                        .checkbox(
+                            &mut self.tts_config.auto_detect,
+                            "Read all messages, detecting their language"
+                        )
+                        .changed()
+                {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                if
+                    ui
+                        .checkbox(
+                            &mut self.tts_config.normalize_whitespace,
+                            "Collapse whitespace and strip invisible characters"
+                        )
+                        .changed()
+                {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Min message length:");
+                    if
+                        ui
+                            .add(
+                                egui::DragValue
+                                    ::new(&mut self.tts_config.min_message_length)
+                                    .range(1..=100)
+                            )
+                            .changed()
+                    {
+                        self.send(
                             super::FrontendToBackendMessage::UpdateTTSConfig(
                                 self.tts_config.clone()
                             )
-                        )
-                        .unwrap();
+                        );
+                    }
+                });
+                if
+                    ui
+                        .checkbox(&mut self.tts_config.auto_pause, "Pause when chat is busy")
+                        .changed()
+                {
+                    self.send(
+                        super::FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                    );
                 }
-                if ui.checkbox(&mut self.tts_config.permited_roles.mods, "Mods").changed() {
-                    self.frontend_tx
-                        .try_send(
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    ui.label("Error sound:");
+                    if ui.text_edit_singleline(&mut self.tts_config.error_sound).lost_focus() {
+                        self.send(
                             super::FrontendToBackendMessage::UpdateTTSConfig(
                                 self.tts_config.clone()
                             )
-                        )
-                        .unwrap();
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Incoming sound:");
+                    if ui.text_edit_singleline(&mut self.tts_config.incoming_sound).lost_focus() {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("All clear sound:");
+                    if ui.text_edit_singleline(&mut self.tts_config.all_clear_sound).lost_focus() {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.checkbox(&mut self.tts_config.read_username, "Read username:").changed() {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                    if
+                        ui
+                            .add_enabled(
+                                self.tts_config.read_username,
+                                egui::TextEdit::singleline(&mut self.tts_config.username_template)
+                            )
+                            .lost_focus()
+                    {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Same language prefix:");
+                    if
+                        ui
+                            .text_edit_singleline(&mut self.tts_config.same_language_prefix)
+                            .lost_focus()
+                    {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Remembered user languages:");
+                    if
+                        ui
+                            .add(
+                                egui::DragValue
+                                    ::new(&mut self.tts_config.remembered_languages)
+                                    .range(1..=10000)
+                            )
+                            .changed()
+                    {
+                        self.send(
+                            super::FrontendToBackendMessage::UpdateTTSConfig(
+                                self.tts_config.clone()
+                            )
+                        );
+                    }
+                });
+                if ui.button("Speak clipboard").clicked() {
+                    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+                        Ok(text) => {
+                            let _ = self.frontend_tx.try_send(
+                                FrontendToBackendMessage::SpeakText(text)
+                            );
+                        }
+                        Err(e) => {
+                            self.log_messages.push(super::LogMessage {
+                                message: format!("Could not read the clipboard: {}", e),
+                                timestamp: chrono::Local::now().to_string(),
+                                log_level: super::LogLevel::WARN,
+                            });
+                        }
+                    }
                 }
-                ui.add_space(350.0);
+                if ui.button("Clear TTS cache").clicked() {
+                    let _ = self.frontend_tx.try_send(FrontendToBackendMessage::ClearTTSCache);
+                }
+                ui.add_space(10.0);
+                ui.collapsing("TTS Moderation", |ui| self.show_tts_moderation(ui));
             });
-            ui.add_space(250.0);
             ui.separator();
             ui.vertical(|ui| {
                 ui.set_height(ui.available_height());
+                let shown = languages::search(&self.language_search);
+                ui.horizontal(|ui| {
+                    ui.label("Search:");
+                    ui.text_edit_singleline(&mut self.language_search);
+                    let enabled = languages::LANGUAGES
+                        .iter()
+                        .filter(|(code, _)| languages::is_enabled(&self.tts_config, code))
+                        .count();
+                    ui.label(format!("{}/{} enabled", enabled, languages::LANGUAGES.len()));
+                });
+                ui.horizontal(|ui| {
+                    let mut changed = false;
+                    if ui.button("Enable all").clicked() {
+                        self.tts_config.disabled_languages.clear();
+                        changed = true;
+                    }
+                    if ui.button("Disable all").clicked() {
+                        languages::enable_only(&mut self.tts_config, &[]);
+                        changed = true;
+                    }
+                    let filtered = !self.language_search.trim().is_empty();
+                    if
+                        ui
+                            .add_enabled(filtered, egui::Button::new("Enable only these"))
+                            .on_hover_text("Enables the languages matching the search")
+                            .clicked()
+                    {
+                        let codes: Vec<&str> = shown
+                            .iter()
+                            .map(|(code, _)| *code)
+                            .collect();
+                        languages::enable_only(&mut self.tts_config, &codes);
+                        changed = true;
+                    }
+                    if changed {
+                        self.send(
+                            FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone())
+                        );
+                    }
+                });
                 let available_height = ui.available_height();
                 let table = egui_extras::TableBuilder
                     ::new(ui)
@@ -78,6 +576,7 @@ impl Chatbot {
                     .column(egui_extras::Column::auto())
                     .column(egui_extras::Column::initial(200.0))
                     .column(egui_extras::Column::auto())
+                    .column(egui_extras::Column::auto())
                     .min_scrolled_height(0.0)
                     .max_scroll_height(available_height);
 
@@ -92,19 +591,39 @@ impl Chatbot {
                         header.col(|ui| {
                             ui.strong("Enabled");
                         });
+                        header.col(|ui| {
+                            ui.strong("Test");
+                        });
                     })
                     .body(|mut body| {
-                        for row_index in 1..100 {
+                        for (row_index, (code, name)) in shown.iter().enumerate() {
                             let row_height = 18.0;
                             body.row(row_height, |mut row| {
                                 row.col(|ui| {
-                                    ui.label(row_index.to_string());
+                                    ui.label((row_index + 1).to_string());
                                 });
                                 row.col(|ui| {
-                                    ui.label("test");
+                                    ui.label(format!("{} ({})", name, code));
                                 });
                                 row.col(|ui| {
-                                    ui.checkbox(&mut false, "");
+                                    let mut enabled = languages::is_enabled(&self.tts_config, code);
+                                    if ui.checkbox(&mut enabled, "").changed() {
+                                        languages::set_enabled(&mut self.tts_config, code, enabled);
+                                        self.send(
+                                            FrontendToBackendMessage::UpdateTTSConfig(
+                                                self.tts_config.clone()
+                                            )
+                                        );
+                                    }
+                                });
+                                row.col(|ui| {
+                                    if ui.small_button("Play").clicked() {
+                                        let _ = self.frontend_tx.try_send(
+                                            FrontendToBackendMessage::TestTTSLanguage(
+                                                code.to_string()
+                                            )
+                                        );
+                                    }
                                 });
                             });
                         }
@@ -112,4 +631,87 @@ impl Chatbot {
             });
         });
     }
+
+    fn show_tts_moderation(&mut self, ui: &mut egui::Ui) {
+        for (list, title) in [
+            (ModerationList::BannedPhrases, "Banned phrases"),
+            (ModerationList::IgnoredUsers, "Ignored users"),
+        ] {
+            ui.label(title);
+            let mut removed = None;
+            for entry in self.tts_moderation.list(list) {
+                ui.horizontal(|ui| {
+                    ui.label(entry);
+                    if ui.small_button("x").clicked() {
+                        removed = Some(entry.clone());
+                    }
+                });
+            }
+            if let Some(entry) = removed {
+                let _ = self.frontend_tx.try_send(
+                    FrontendToBackendMessage::RemoveTTSModerationEntry(list, entry)
+                );
+            }
+            let input = match list {
+                ModerationList::BannedPhrases => &mut self.banned_phrase_input,
+                ModerationList::IgnoredUsers => &mut self.ignored_user_input,
+            };
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(input);
+                if ui.button("Add").clicked() && !input.trim().is_empty() {
+                    let _ = self.frontend_tx.try_send(
+                        FrontendToBackendMessage::AddTTSModerationEntry(list, std::mem::take(input))
+                    );
+                }
+            });
+            ui.add_space(5.0);
+        }
+        let previous = (self.tts_config.banned_phrase_match, self.tts_config.banned_phrase_action);
+        ui.horizontal(|ui| {
+            ui.label("Banned phrases match:");
+            ui.radio_value(
+                &mut self.tts_config.banned_phrase_match,
+                PhraseMatch::Substring,
+                "Anywhere"
+            );
+            ui.radio_value(
+                &mut self.tts_config.banned_phrase_match,
+                PhraseMatch::WholeWord,
+                "Whole words"
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Messages with a banned phrase are:");
+            ui.radio_value(
+                &mut self.tts_config.banned_phrase_action,
+                BannedPhraseAction::Block,
+                "Skipped"
+            );
+            ui.radio_value(
+                &mut self.tts_config.banned_phrase_action,
+                BannedPhraseAction::Censor,
+                "Bleeped"
+            );
+        });
+        if (self.tts_config.banned_phrase_match, self.tts_config.banned_phrase_action) != previous {
+            self.send(FrontendToBackendMessage::UpdateTTSConfig(self.tts_config.clone()));
+        }
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.moderation_file);
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Import").clicked() {
+                let _ = self.frontend_tx.try_send(
+                    FrontendToBackendMessage::ImportTTSModeration(self.moderation_file.clone().into())
+                );
+            }
+            if ui.button("Export").clicked() {
+                let _ = self.frontend_tx.try_send(
+                    FrontendToBackendMessage::ExportTTSModeration(self.moderation_file.clone().into())
+                );
+            }
+        });
+    }
 }